@@ -23,7 +23,8 @@ pub fn setup_sigabrt_handler() {
     //
     // SAFETY:
     // - This signal is registered as early as possible in `main`.
-    // - It is the only signal handler registered by `seal`; registering any other signal handlers may cause UB.
+    // - The only other signal handlers `seal` registers are `process.on_signal`'s (SIGINT, SIGTERM, SIGHUP), which
+    //   also only do async-signal-safe things.
     // - Only async-signal-safe functions (such as `exit()`) are called in the signal handler.
     // - Behavior has been tested on x86_64 Arch Linux.
     #[cfg(unix)]
@@ -70,9 +71,13 @@ pub fn setup_panic_hook() {
     }));
 }
 
-pub fn display_error_and_exit(err: LuaError) -> ! {
+pub fn display_error(err: LuaError) {
     let err = parse_traceback(err.to_string());
     eprintln!("{}[ERR]{} {}", colors::BOLD_RED, colors::RESET, err);
+}
+
+pub fn display_error_and_exit(err: LuaError) -> ! {
+    display_error(err);
     std::process::exit(1);
 }
 
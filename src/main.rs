@@ -26,11 +26,13 @@ mod std_net;
 mod std_serde;
 mod std_str_internal;
 mod std_thread;
+mod std_task;
 mod std_luau;
 mod std_err;
 mod sealconfig;
 mod setup;
 mod compile;
+mod repl;
 mod std_args;
 
 use err::display_error_and_exit;
@@ -81,7 +83,13 @@ enum SealCommand {
     /// `seal test` (runs test_path from config.luau)
     Test,
     Version,
-    /// not yet implemented
+    /**
+    Start an interactive `seal` session (REPL); `fs`, `http`, and `process` libs are already loaded in for convenience.
+
+    ## Examples:
+    * `seal repl`
+    * `seal i`
+    */
     Repl,
     ExecStandalone(Vec<u8>),
     /// Compiles project codebase to standalone executable (or bundles to a .luau file)
@@ -141,9 +149,7 @@ fn main() -> LuaResult<()> {
         help @ SealCommand::DefaultHelp | 
         help @ SealCommand::HelpCommandHelp |
         help @ SealCommand::SealConfigHelp => help.help(),
-        SealCommand::Repl => {
-            wrap_err!("seal repl coming SOON (tm)")
-        },
+        SealCommand::Repl => seal_repl(),
         SealCommand::Compile(args) => seal_compile(args),
         SealCommand::ExecStandalone(bytecode) => seal_standalone(bytecode),
     };
@@ -154,7 +160,12 @@ fn main() -> LuaResult<()> {
         Err(err) => display_error_and_exit(err),
     };
 
-    match luau.load(src).set_name(chunk_name).exec() {
+    // the main chunk runs as the first @std/task task, so blocking stdlib calls in it can yield to other tasks
+    let main_chunk = match luau.load(src).set_name(chunk_name).into_function() {
+        Ok(main_chunk) => main_chunk,
+        Err(err) => display_error_and_exit(err),
+    };
+    match std_task::run(&luau, main_chunk) {
         Ok(_) => Ok(()),
         Err(err) => display_error_and_exit(err),
     }
@@ -256,6 +267,11 @@ fn seal_setup(options: SetupOptions) -> LuauLoadResult {
     Ok(None)
 }
 
+fn seal_repl() -> LuauLoadResult {
+    repl::run()?;
+    Ok(None)
+}
+
 fn seal_compile(mut args: Args) -> LuauLoadResult {
     let function_name = "seal compile";
 
@@ -430,6 +446,7 @@ impl SealCommand {
             Self::HelpCommandHelp => "help",
             Self::SealConfigHelp => "config",
             Self::Compile(_) => "compile",
+            Self::Repl => "repl",
             other => {
                 return wrap_err!("help not yet implemented for command {:#?}", other);
             },
@@ -0,0 +1,279 @@
+use std::env;
+use std::path::PathBuf;
+
+use mluau::prelude::*;
+use crate::prelude::*;
+use crate::err::display_error;
+use crate::{globals, require, std_env, std_fs, std_io, std_net, std_process};
+
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+
+const PROMPT: &str = "> ";
+const CONTINUATION_PROMPT: &str = ">> ";
+const HISTORY_FILE_NAME: &str = ".seal_history";
+
+const LUAU_KEYWORDS: &[&str] = &[
+    "and", "break", "continue", "do", "else", "elseif", "end", "export", "false", "for", "function",
+    "if", "in", "local", "nil", "not", "or", "repeat", "return", "then", "true", "type", "until", "while",
+];
+
+/// Tab completion for `seal repl`; holds onto the repl's Luau state so we can complete whatever's currently in scope.
+struct ReplHelper {
+    luau: Lua,
+}
+
+impl ReplHelper {
+    /// completes `@std/*` require paths when the cursor's inside a string that starts with `@`
+    fn complete_require_path(&self, line_before: &str) -> Option<(usize, Vec<Pair>)> {
+        let quote_position = line_before.rfind(['"', '\'', '`'])?;
+        let quote = &line_before[quote_position..quote_position + 1];
+        // an odd number of quotes before this one means this quote closes a string instead of opening one
+        if line_before[..quote_position].matches(quote).count() % 2 != 0 {
+            return None;
+        }
+        let partial = &line_before[quote_position + 1..];
+        if !partial.starts_with('@') {
+            return None;
+        }
+        let candidates = require::STANDARD_LIBRARIES
+            .iter()
+            .filter(|library| library.starts_with(partial))
+            .map(|library| Pair { display: library.to_string(), replacement: library.to_string() })
+            .collect();
+        Some((quote_position + 1, candidates))
+    }
+
+    /// completes globals, keywords, and keys of (nested) tables like `fs.path.jo`
+    fn complete_identifier(&self, line_before: &str) -> LuaResult<(usize, Vec<Pair>)> {
+        let word_start = line_before
+            .char_indices()
+            .rev()
+            .find(|(_, c)| !(c.is_alphanumeric() || *c == '_' || *c == '.' || *c == ':'))
+            .map(|(index, c)| index + c.len_utf8())
+            .unwrap_or(0);
+        let word = &line_before[word_start..];
+
+        let (table_path, partial, partial_start) = match word.rfind(['.', ':']) {
+            Some(separator) => (Some(&word[..separator]), &word[separator + 1..], word_start + separator + 1),
+            None => (None, word, word_start),
+        };
+
+        let mut names: Vec<String> = Vec::new();
+        match table_path {
+            Some(table_path) => {
+                let mut current = LuaValue::Table(self.luau.globals());
+                for segment in table_path.split(['.', ':']) {
+                    current = match current {
+                        LuaValue::Table(t) => index_without_calling(&t, segment)?,
+                        _ => return Ok((partial_start, Vec::new())),
+                    };
+                }
+                if let LuaValue::Table(t) = current {
+                    collect_string_keys(&t, &mut names)?;
+                }
+            },
+            None => {
+                collect_string_keys(&self.luau.globals(), &mut names)?;
+                names.extend(LUAU_KEYWORDS.iter().map(|keyword| keyword.to_string()));
+            }
+        }
+
+        names.retain(|name| name.starts_with(partial));
+        names.sort();
+        names.dedup();
+
+        let candidates = names
+            .into_iter()
+            .map(|name| Pair { display: name.clone(), replacement: name })
+            .collect();
+        Ok((partial_start, candidates))
+    }
+}
+
+/// like `t[key]` but only follows `__index` when it's a table, so completing never runs user code
+fn index_without_calling(t: &LuaTable, key: &str) -> LuaValueResult {
+    match t.raw_get::<LuaValue>(key)? {
+        LuaNil => match t.metatable() {
+            Some(metatable) => match metatable.raw_get::<LuaValue>("__index")? {
+                LuaValue::Table(index) => index_without_calling(&index, key),
+                _ => Ok(LuaNil),
+            },
+            None => Ok(LuaNil),
+        },
+        value => Ok(value),
+    }
+}
+
+fn collect_string_keys(t: &LuaTable, names: &mut Vec<String>) -> LuaEmptyResult {
+    for pair in t.pairs::<LuaValue, LuaValue>() {
+        let (key, _) = pair?;
+        if let LuaValue::String(key) = key {
+            names.push(key.to_string_lossy());
+        }
+    }
+    if let Some(metatable) = t.metatable()
+        && let LuaValue::Table(index) = metatable.raw_get::<LuaValue>("__index")?
+    {
+        collect_string_keys(&index, names)?;
+    }
+    Ok(())
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let line_before = &line[..pos];
+        if let Some(completions) = self.complete_require_path(line_before) {
+            return Ok(completions);
+        }
+        // a completion failing shouldn't take down the whole repl, so we just don't suggest anything
+        Ok(self.complete_identifier(line_before).unwrap_or((pos, Vec::new())))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+impl Highlighter for ReplHelper {}
+impl Validator for ReplHelper {}
+impl Helper for ReplHelper {}
+
+enum ReplInput {
+    Complete(LuaFunction),
+    /// chunk isn't finished yet (unclosed `function`, `{`, string, etc.); keep reading lines
+    Incomplete,
+}
+
+fn compile_input(luau: &Lua, src: &str, chunk_name: &str) -> LuaResult<ReplInput> {
+    // try it as an expression first so `1 + 1` or `fs.readfile("./meow.txt")` displays its result
+    if let Ok(function) = luau.load(format!("return {}", src)).set_name(chunk_name).into_function() {
+        return Ok(ReplInput::Complete(function));
+    }
+    match luau.load(src).set_name(chunk_name).into_function() {
+        Ok(function) => Ok(ReplInput::Complete(function)),
+        Err(LuaError::SyntaxError { incomplete_input: true, .. }) => Ok(ReplInput::Incomplete),
+        Err(err) => Err(err),
+    }
+}
+
+fn display_results(luau: &Lua, results: LuaMultiValue) -> LuaEmptyResult {
+    if results.is_empty() {
+        return Ok(());
+    }
+    let mut formatted: Vec<String> = Vec::with_capacity(results.len());
+    for value in results {
+        formatted.push(std_io::format::pretty(luau, value)?);
+    }
+    println!("{}", formatted.join(", "));
+    Ok(())
+}
+
+fn get_history_path() -> Option<PathBuf> {
+    env::home_dir().map(|home| home.join(HISTORY_FILE_NAME))
+}
+
+/// Runs `seal repl`: an interactive Luau session that keeps one Luau state alive between inputs.
+pub fn run() -> LuaEmptyResult {
+    let function_name = "seal repl";
+
+    let luau = Lua::default();
+    let chunk_name = std_env::get_cwd(function_name)?
+        .join("repl")
+        .to_string_lossy()
+        .into_owned();
+    globals::set_globals(&luau, &chunk_name)?;
+
+    // like seal eval, the repl comes with a few libs builtin
+    let globals = luau.globals();
+    globals.raw_set("fs", ok_table(std_fs::create(&luau))?)?;
+    globals.raw_set("process", ok_table(std_process::create(&luau))?)?;
+    globals.raw_set("http", ok_table(std_net::http::create(&luau))?)?;
+
+    let mut editor: Editor<ReplHelper, DefaultHistory> = match Editor::new() {
+        Ok(editor) => editor,
+        Err(err) => {
+            return wrap_err!("{}: unable to make rustyline Editor due to ReadlineError: {}", function_name, err);
+        }
+    };
+    editor.set_helper(Some(ReplHelper { luau: luau.clone() }));
+
+    let history_path = get_history_path();
+    if let Some(history_path) = &history_path && history_path.exists()
+        && let Err(err) = editor.load_history(history_path)
+    {
+        eprintln!("{}[WARN]{} {}: unable to load history from '{}' due to err: {}",
+            colors::BOLD_YELLOW, colors::RESET, function_name, history_path.display(), err);
+    }
+
+    println!(
+        "{}seal{} {} {}| {} | ctrl+d to exit{}",
+        colors::BOLD_BLUE, colors::RESET, globals::SEAL_VERSION, colors::BRIGHT_BLACK, function_name, colors::RESET
+    );
+
+    let mut chunk = String::new();
+    loop {
+        let prompt = if chunk.is_empty() { PROMPT } else { CONTINUATION_PROMPT };
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => {
+                // ctrl+c throws away whatever's been typed so far (like python's repl)
+                if chunk.is_empty() {
+                    println!("{}(to exit, press ctrl+d or call process.exit()){}", colors::BRIGHT_BLACK, colors::RESET);
+                }
+                chunk.clear();
+                continue;
+            },
+            Err(ReadlineError::Eof) => break,
+            Err(err) => {
+                return wrap_err!("{}: encountered unexpected ReadlineError: {}", function_name, err);
+            }
+        };
+
+        if chunk.is_empty() && line.trim().is_empty() {
+            continue;
+        }
+        if !chunk.is_empty() {
+            chunk.push('\n');
+        }
+        chunk.push_str(&line);
+
+        let function = match compile_input(&luau, &chunk, &chunk_name) {
+            Ok(ReplInput::Complete(function)) => Some(function),
+            Ok(ReplInput::Incomplete) => continue,
+            Err(err) => {
+                display_error(err);
+                None
+            }
+        };
+
+        if let Some(function) = function {
+            match function.call::<LuaMultiValue>(()) {
+                Ok(results) => {
+                    if let Err(err) = display_results(&luau, results) {
+                        display_error(err);
+                    }
+                },
+                Err(err) => display_error(err),
+            }
+        }
+
+        // we save history after every input because process.exit() in the repl won't give us a chance to later
+        let _ = editor.add_history_entry(chunk.as_str());
+        if let Some(history_path) = &history_path
+            && let Err(err) = editor.save_history(history_path)
+        {
+            eprintln!("{}[WARN]{} {}: unable to save history to '{}' due to err: {}",
+                colors::BOLD_YELLOW, colors::RESET, function_name, history_path.display(), err);
+        }
+        chunk.clear();
+    }
+
+    Ok(())
+}
@@ -4,6 +4,29 @@ use std::{fs, io};
 
 const RESERVED_ALIASES: [&str; 3] = ["@std", "@interop", "@internal"];
 
+/// every public require path handled by `get_standard_library`; used for tab completion in `seal repl`
+pub const STANDARD_LIBRARIES: &[&str] = &[
+    "@std",
+    "@std/fs", "@std/fs/path", "@std/fs/file", "@std/fs/dir",
+    "@std/env",
+    "@std/err",
+    "@std/io", "@std/io/input", "@std/io/output", "@std/io/colors", "@std/io/clear", "@std/io/format", "@std/io/prompt",
+    "@std/colors",
+    "@std/time", "@std/datetime", "@std/time/datetime",
+    "@std/process",
+    "@std/serde", "@std/serde/base64", "@std/serde/toml", "@std/serde/yaml", "@std/serde/json", "@std/serde/hex",
+    "@std/json",
+    "@std/net", "@std/net/http", "@std/net/http/server", "@std/net/request", "@std/net/websocket", "@std/net/tcp", "@std/net/udp", "@std/net/url",
+    "@std/crypt", "@std/crypt/aes", "@std/crypt/rsa", "@std/crypt/hash", "@std/crypt/password",
+    "@std/str",
+    "@std/semver",
+    "@std/thread",
+    "@std/task",
+    "@std/luau",
+    "@std/args",
+    "@interop", "@interop/standalone", "@interop/mlua",
+];
+
 #[inline(always)]
 fn is_reserved(path: &str) -> bool {
     RESERVED_ALIASES.iter().any(|alias| path.starts_with(alias))
@@ -88,7 +111,11 @@ fn get_standard_library(luau: &Lua, path: String) -> LuaValueResult {
         "@std/net" => ok_table(std_net::create(luau)),
         "@std/net/http" => ok_table(std_net::http::create(luau)),
         "@std/net/http/server" => ok_table(std_net::serve::create(luau)),
-        "@std/net/request" => ok_function(std_net::http::request, luau),
+        "@std/net/request" => Ok(LuaValue::Function(std_net::http::create_request(luau)?)),
+        "@std/net/websocket" => ok_table(std_net::websocket::create(luau)),
+        "@std/net/tcp" => ok_table(std_net::tcp::create(luau)),
+        "@std/net/udp" => ok_table(std_net::udp::create(luau)),
+        "@std/net/url" => ok_table(std_net::url::create(luau)),
 
         "@std/crypt" => ok_table(std_crypt::create(luau)),
         "@std/crypt/aes" => ok_table(std_crypt::create_aes(luau)),
@@ -103,6 +130,8 @@ fn get_standard_library(luau: &Lua, path: String) -> LuaValueResult {
 
         "@std/thread" => ok_table(std_thread::create(luau)),
 
+        "@std/task" => ok_table(std_task::create(luau)),
+
         "@std/luau" => ok_table(std_luau::create(luau)),
 
         "@std/args" => ok_table(std_args::create(luau)),
@@ -124,6 +153,7 @@ fn get_standard_library(luau: &Lua, path: String) -> LuaValueResult {
                 .with_value("net", std_net::create(luau)?)?
                 .with_value("crypt", std_crypt::create(luau)?)?
                 .with_value("thread", std_thread::create(luau)?)?
+                .with_value("task", std_task::create(luau)?)?
                 .with_value("luau", std_luau::create(luau)?)?
                 .build_readonly()
             )
@@ -1,8 +1,9 @@
 use mluau::prelude::*;
 use crate::prelude::*;
 use crate::std_fs::pathlib::normalize_path;
-use std::{path::Path, time::Duration};
-use std::sync::{Arc, Mutex};
+use crate::std_task;
+use std::{path::Path, time::{Duration, Instant}};
+use std::sync::{Arc, Mutex, MutexGuard};
 
 use notify::{event::{
     AccessKind, AccessMode, 
@@ -12,7 +13,7 @@ use notify::{event::{
     Event, EventKind, 
     RecursiveMode, Watcher
 };
-use crossbeam_channel::RecvTimeoutError;
+use crossbeam_channel::{Receiver, RecvTimeoutError, TryRecvError};
 
 #[derive(Clone, Copy)]
 pub struct WatchOptions {
@@ -200,59 +201,89 @@ pub fn watch<P: AsRef<Path>>(luau: &Lua, paths: Vec<P>, options: WatchOptions, f
     let arc_rx = Arc::new(Mutex::new(rx));
     let watcher = Arc::new(watcher);
 
-    ok_function_mut({
+    let blocking = luau.create_function({
         let arc_rx = Arc::clone(&arc_rx);
-        move | luau: &Lua, _value: LuaValue | -> LuaMultiResult {
+        let watcher = Arc::clone(&watcher);
+        move | luau: &Lua, _value: LuaMultiValue | -> LuaMultiResult {
             // need to clone watcher here just to keep it alive (so it doesn't disconnect while we're iterating)
             let _watcher = Arc::clone(&watcher);
-            let rx = match arc_rx.try_lock() {
-                Ok(rx) => rx,
-                Err(err) => {
-                    panic!("{} unexpectedly cannot lock the crossbeam event receiver due to err: {}", function_name, err);
-                }
-            };
-            match rx.recv_timeout(options.timeout) {
-                // if an event is received we return its category and an event info table describing
-                // what specific kind of event was received and what paths were accessed/modified/written to/etc.
-                Ok(event) => {
-                    let event_category = EventCategory::new(event.kind);
-                    let event_table = ok_table(create_event_table(event, event_category, luau))?;
-                    let category_str = ok_string(event_category.category(), luau)?;
-                    Ok(LuaMultiValue::from_vec(vec![category_str, event_table]))
-                },
-                // if no event recv by timeout we return "Timeout", { kind = "None", paths = {} }
-                // so we don't indefinitely block the luau vm until the next event recv
-                Err(RecvTimeoutError::Timeout) => {
-                    Ok(LuaMultiValue::from_vec(vec![
-                        ok_string("None", luau)?,
-                        ok_table(
-                            TableBuilder::create(luau)?
-                                .with_value("paths", luau.create_table()?)?
-                                .with_value("kind", "None::Timeout")?
-                                .with_value("is_write", false)?
-                                .build()
-                        )?
-                    ]))
+            let rx = lock_receiver(&arc_rx, function_name);
+            event_values(luau, rx.recv_timeout(options.timeout))
+        }
+    })?;
+    // in @std/task tasks, other tasks run while we wait for the next event
+    let start = luau.create_function(move | luau: &Lua, _value: LuaMultiValue | -> LuaResult<LuaFunction> {
+        let give_up_at = Instant::now() + options.timeout;
+        let arc_rx = Arc::clone(&arc_rx);
+        let watcher = Arc::clone(&watcher);
+        std_task::poll_fn(luau, move |luau| {
+            let _watcher = Arc::clone(&watcher);
+            let rx = lock_receiver(&arc_rx, function_name);
+            let received = match rx.try_recv() {
+                Ok(event) => Ok(event),
+                Err(TryRecvError::Empty) if Instant::now() < give_up_at => {
+                    return Ok(None);
                 },
-                // the channel has somehow gotten disconnected, this means either the sender panicked or smth
-                // or we somehow dropped the watcher
-                // - either case probably means there's a bug in seal or notify or crossbeam
-                // - if we just returned nil here to stop iteration, users would wonder why their for loop stopped iterating
-                // - if we wrap_err! here, users would pcall this and we wouldn't know that users are actually getting this
-                // - so we panic so users may report this and we can investigate
-                Err(RecvTimeoutError::Disconnected) => {
-                    // Ok(LuaMultiValue::from_vec(vec![LuaNil]))
-                    panic!(
-                        "{}: {}\n{}\n{}\n{}\n{}",
-                        "filesystem watcher channel disconnected unexpectedly",
-                        "This closure owns arc_rx and should not lose its sender unless:",
-                        "  - the watcher was dropped prematurely,",
-                        "  - the notify callback panicked",
-                        "  - there's a bug in seal, crossbeam, or notify itself.",
-                        "Please report this with reproduction steps if possible.",
-                    );
-                }
-            }
+                Err(TryRecvError::Empty) => Err(RecvTimeoutError::Timeout),
+                Err(TryRecvError::Disconnected) => Err(RecvTimeoutError::Disconnected),
+            };
+            event_values(luau, received).map(Some)
+        })
+    })?;
+    Ok(LuaValue::Function(std_task::yielding(luau, blocking, start)?))
+}
+
+fn lock_receiver(arc_rx: &Mutex<Receiver<Event>>, function_name: &'static str) -> MutexGuard<'_, Receiver<Event>> {
+    match arc_rx.try_lock() {
+        Ok(rx) => rx,
+        Err(err) => {
+            panic!("{} unexpectedly cannot lock the crossbeam event receiver due to err: {}", function_name, err);
+        }
+    }
+}
+
+/// what the watch iterator returns for each event it receives (or doesn't, by the timeout)
+fn event_values(luau: &Lua, received: Result<Event, RecvTimeoutError>) -> LuaMultiResult {
+    match received {
+        // if an event is received we return its category and an event info table describing
+        // what specific kind of event was received and what paths were accessed/modified/written to/etc.
+        Ok(event) => {
+            let event_category = EventCategory::new(event.kind);
+            let event_table = ok_table(create_event_table(event, event_category, luau))?;
+            let category_str = ok_string(event_category.category(), luau)?;
+            Ok(LuaMultiValue::from_vec(vec![category_str, event_table]))
+        },
+        // if no event recv by timeout we return "Timeout", { kind = "None", paths = {} }
+        // so we don't indefinitely block the luau vm until the next event recv
+        Err(RecvTimeoutError::Timeout) => {
+            Ok(LuaMultiValue::from_vec(vec![
+                ok_string("None", luau)?,
+                ok_table(
+                    TableBuilder::create(luau)?
+                        .with_value("paths", luau.create_table()?)?
+                        .with_value("kind", "None::Timeout")?
+                        .with_value("is_write", false)?
+                        .build()
+                )?
+            ]))
+        },
+        // the channel has somehow gotten disconnected, this means either the sender panicked or smth
+        // or we somehow dropped the watcher
+        // - either case probably means there's a bug in seal or notify or crossbeam
+        // - if we just returned nil here to stop iteration, users would wonder why their for loop stopped iterating
+        // - if we wrap_err! here, users would pcall this and we wouldn't know that users are actually getting this
+        // - so we panic so users may report this and we can investigate
+        Err(RecvTimeoutError::Disconnected) => {
+            // Ok(LuaMultiValue::from_vec(vec![LuaNil]))
+            panic!(
+                "{}: {}\n{}\n{}\n{}\n{}",
+                "filesystem watcher channel disconnected unexpectedly",
+                "This closure owns arc_rx and should not lose its sender unless:",
+                "  - the watcher was dropped prematurely,",
+                "  - the notify callback panicked",
+                "  - there's a bug in seal, crossbeam, or notify itself.",
+                "Please report this with reproduction steps if possible.",
+            );
         }
-    }, luau)
-}
\ No newline at end of file
+    }
+}
@@ -0,0 +1,172 @@
+//! `server.serve` connections: plain TCP or TLS (`ServeConfig.tls`), shared between the request reader and response writers.
+
+use mluau::prelude::*;
+use crate::prelude::*;
+
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+
+use std::cell::RefCell;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::rc::Rc;
+use std::sync::Arc;
+
+enum Transport {
+    Plain(TcpStream),
+    Tls(Box<StreamOwned<ServerConnection, TcpStream>>),
+}
+
+/**
+One client connection. Clones share the same underlying stream, so the request parser's `BufReader`
+and whatever's writing the response (including streamed bodies and WebSockets) can each hold one.
+
+Socket options (timeouts, nonblocking) go through `socket()`, which is the same TCP socket underneath TLS.
+*/
+#[derive(Clone)]
+pub struct ServeConnection {
+    transport: Rc<RefCell<Transport>>,
+    socket: Rc<TcpStream>,
+}
+
+impl ServeConnection {
+    /// the TLS handshake doesn't happen here; it happens on the first read, on whichever thread handles the connection
+    pub fn new(stream: TcpStream, tls: Option<&Arc<ServerConfig>>) -> io::Result<Self> {
+        let socket = Rc::new(stream.try_clone()?);
+        let transport = match tls {
+            Some(config) => {
+                let connection = ServerConnection::new(Arc::clone(config)).map_err(io::Error::other)?;
+                Transport::Tls(Box::new(StreamOwned::new(connection, stream)))
+            },
+            None => Transport::Plain(stream),
+        };
+        Ok(Self {
+            transport: Rc::new(RefCell::new(transport)),
+            socket,
+        })
+    }
+
+    pub fn socket(&self) -> &TcpStream {
+        &self.socket
+    }
+
+    /// tells the client we're done writing; TLS connections get a `close_notify` first so the client knows the response wasn't truncated
+    pub fn shutdown_write(&self) {
+        if let Transport::Tls(tls) = &mut *self.transport.borrow_mut() {
+            tls.conn.send_close_notify();
+            let _ = tls.flush();
+        }
+        let _ = self.socket.shutdown(Shutdown::Write);
+    }
+}
+
+impl Read for ServeConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut *self.transport.borrow_mut() {
+            Transport::Plain(stream) => stream.read(buf),
+            Transport::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for ServeConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut *self.transport.borrow_mut() {
+            Transport::Plain(stream) => stream.write(buf),
+            Transport::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut *self.transport.borrow_mut() {
+            Transport::Plain(stream) => stream.flush(),
+            Transport::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+/// reads a PEM file, or takes the PEM itself if the string's already one (like what `server.self_signed_cert` returns)
+fn read_pem(source: &str, field: &'static str, function_name: &'static str) -> LuaResult<Vec<u8>> {
+    if source.trim_start().starts_with("-----BEGIN") {
+        return Ok(source.as_bytes().to_vec());
+    }
+    match std::fs::read(source) {
+        Ok(pem) => Ok(pem),
+        Err(err) => {
+            wrap_err!("{}: unable to read ServeConfig.tls.{} at '{}' due to err: {}", function_name, field, source, err)
+        }
+    }
+}
+
+/// builds the TLS config for `ServeConfig.tls = { cert = path, key = path }`
+pub fn tls_config_from_table(tls: &LuaTable, function_name: &'static str) -> LuaResult<Arc<ServerConfig>> {
+    let get_source = |field: &'static str| -> LuaResult<String> {
+        match tls.raw_get(field)? {
+            LuaValue::String(source) => Ok(source.to_string_lossy()),
+            other => {
+                wrap_err!("{} expected ServeConfig.tls.{} to be a path to a PEM file (or PEM string), got: {:?}", function_name, field, other)
+            }
+        }
+    };
+    let cert_pem = read_pem(&get_source("cert")?, "cert", function_name)?;
+    let key_pem = read_pem(&get_source("key")?, "key", function_name)?;
+
+    let certs = match CertificateDer::pem_slice_iter(&cert_pem).collect::<Result<Vec<_>, _>>() {
+        Ok(certs) if !certs.is_empty() => certs,
+        Ok(_) => {
+            return wrap_err!("{}: ServeConfig.tls.cert doesn't contain any certificates", function_name);
+        },
+        Err(err) => {
+            return wrap_err!("{}: unable to parse ServeConfig.tls.cert: {}", function_name, err);
+        }
+    };
+    let key = match PrivateKeyDer::from_pem_slice(&key_pem) {
+        Ok(key) => key,
+        Err(err) => {
+            return wrap_err!("{}: unable to parse ServeConfig.tls.key: {}", function_name, err);
+        }
+    };
+
+    // same crypto provider ureq uses, so we don't pull in a second one
+    let builder = match ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider())).with_safe_default_protocol_versions() {
+        Ok(builder) => builder,
+        Err(err) => {
+            return wrap_err!("{}: unable to set up TLS: {}", function_name, err);
+        }
+    };
+    let mut config = match builder.with_no_client_auth().with_single_cert(certs, key) {
+        Ok(config) => config,
+        Err(err) => {
+            return wrap_err!("{}: ServeConfig.tls cert and key don't work together: {}", function_name, err);
+        }
+    };
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+/// `server.self_signed_cert(hosts: { string }?)`: a throwaway cert for local development
+pub fn server_self_signed_cert(luau: &Lua, value: LuaValue) -> LuaValueResult {
+    let function_name = "server.self_signed_cert(hosts: { string }?)";
+    let hosts = match value {
+        LuaValue::Table(hosts) => hosts.sequence_values::<String>().collect::<LuaResult<Vec<String>>>()?,
+        LuaNil => vec![String::from("localhost"), String::from("127.0.0.1"), String::from("::1")],
+        other => {
+            return wrap_err!("{} expected hosts to be an array of hostnames/ip addresses or nil, got: {:?}", function_name, other);
+        }
+    };
+    if hosts.is_empty() {
+        return wrap_err!("{}: hosts can't be empty", function_name);
+    }
+    let certified = match rcgen::generate_simple_self_signed(hosts) {
+        Ok(certified) => certified,
+        Err(err) => {
+            return wrap_err!("{}: unable to generate certificate: {}", function_name, err);
+        }
+    };
+    TableBuilder::create(luau)?
+        .with_value("cert", certified.cert.pem())?
+        .with_value("key", certified.key_pair.serialize_pem())?
+        .build_readonly()
+        .map(LuaValue::Table)
+}
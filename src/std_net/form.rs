@@ -0,0 +1,373 @@
+//! Form bodies: `application/x-www-form-urlencoded` and `multipart/form-data`.
+//! Encoding for outbound `@std/net/http` requests (`http.form`, `http.multipart`), and parsing for `server.serve`.
+
+use mluau::prelude::*;
+use crate::prelude::*;
+use super::url;
+
+use std::fs;
+use std::path::Path;
+
+/// an already-encoded `application/x-www-form-urlencoded` body from `http.form`
+pub struct FormBody {
+    encoded: String,
+}
+
+impl LuaUserData for FormBody {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_meta_field("__type", "FormBody");
+    }
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_meta_method(LuaMetaMethod::ToString, |_luau: &Lua, this: &FormBody, _: LuaValue| -> LuaResult<String> {
+            Ok(this.encoded.clone())
+        });
+    }
+}
+
+pub fn http_form(luau: &Lua, value: LuaValue) -> LuaValueResult {
+    let function_name = "http.form(fields: { [string]: FormValue | { FormValue } })";
+    let fields = match value {
+        LuaValue::Table(fields) => fields,
+        other => {
+            return wrap_err!("{} expected fields to be a table, got: {:?}", function_name, other);
+        }
+    };
+
+    // spaces as `+` like browsers do for forms
+    let encoded = url::encode_query(&url::query_pairs_from_table(&fields, function_name)?, true);
+
+    ok_userdata(FormBody { encoded }, luau)
+}
+
+struct MultipartPart {
+    name: String,
+    filename: Option<String>,
+    content_type: Option<String>,
+    data: Vec<u8>,
+}
+
+/// a `multipart/form-data` body from `http.multipart()`, built up with `:field` and `:file` calls
+pub struct Multipart {
+    boundary: String,
+    parts: Vec<MultipartPart>,
+}
+
+impl Multipart {
+    fn new() -> Self {
+        Self {
+            boundary: format!("----SealFormBoundary{:016x}{:016x}", rand::random::<u64>(), rand::random::<u64>()),
+            parts: Vec::new(),
+        }
+    }
+
+    fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary)
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        // quotes and newlines would break out of the quoted-string, so we escape them the way browsers do
+        let quote = |s: &str| s.replace('"', "%22").replace('\r', "%0D").replace('\n', "%0A");
+        let mut body: Vec<u8> = Vec::new();
+        for part in &self.parts {
+            body.extend_from_slice(format!("--{}\r\n", self.boundary).as_bytes());
+            let mut disposition = format!("Content-Disposition: form-data; name=\"{}\"", quote(&part.name));
+            if let Some(filename) = &part.filename {
+                disposition.push_str(&format!("; filename=\"{}\"", quote(filename)));
+            }
+            body.extend_from_slice(disposition.as_bytes());
+            body.extend_from_slice(b"\r\n");
+            if let Some(content_type) = &part.content_type {
+                body.extend_from_slice(format!("Content-Type: {}\r\n", content_type).as_bytes());
+            }
+            body.extend_from_slice(b"\r\n");
+            body.extend_from_slice(&part.data);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{}--\r\n", self.boundary).as_bytes());
+        body
+    }
+}
+
+fn get_part_name(value: LuaValue, function_name: &'static str) -> LuaResult<String> {
+    match value {
+        LuaValue::String(name) => Ok(name.to_string_lossy()),
+        other => {
+            wrap_err!("{} expected name to be a string, got: {:?}", function_name, other)
+        }
+    }
+}
+
+fn get_optional_string(options: &LuaTable, key: &'static str, function_name: &'static str) -> LuaResult<Option<String>> {
+    match options.raw_get(key)? {
+        LuaValue::String(s) => Ok(Some(s.to_string_lossy())),
+        LuaNil => Ok(None),
+        other => {
+            wrap_err!("{} expected options.{} to be a string or nil, got: {:?}", function_name, key, other)
+        }
+    }
+}
+
+impl LuaUserData for Multipart {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_meta_field("__type", "Multipart");
+        fields.add_field_method_get("boundary", |_luau: &Lua, this: &Multipart| Ok(this.boundary.clone()));
+    }
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_function("field", |_luau: &Lua, (this, name, value): (LuaAnyUserData, LuaValue, LuaValue)| -> LuaResult<LuaAnyUserData> {
+            let function_name = "Multipart:field(name: string, value: string | buffer)";
+            let name = get_part_name(name, function_name)?;
+            let data = match value {
+                LuaValue::String(s) => s.as_bytes().to_vec(),
+                LuaValue::Buffer(buffy) => buffy.to_vec(),
+                LuaValue::Integer(n) => n.to_string().into_bytes(),
+                LuaValue::Number(f) => f.to_string().into_bytes(),
+                other => {
+                    return wrap_err!("{} expected value to be a string or buffer, got: {:?}", function_name, other);
+                }
+            };
+            this.borrow_mut::<Multipart>()?.parts.push(MultipartPart {
+                name,
+                filename: None,
+                content_type: None,
+                data,
+            });
+            Ok(this)
+        });
+        methods.add_function("file", |_luau: &Lua, (this, name, source, options): (LuaAnyUserData, LuaValue, LuaValue, LuaValue)| -> LuaResult<LuaAnyUserData> {
+            let function_name = "Multipart:file(name: string, source: string | buffer, options: { filename: string?, content_type: string? }?)";
+            let name = get_part_name(name, function_name)?;
+            let (filename, content_type) = match options {
+                LuaValue::Table(options) => (
+                    get_optional_string(&options, "filename", function_name)?,
+                    get_optional_string(&options, "content_type", function_name)?,
+                ),
+                LuaNil => (None, None),
+                other => {
+                    return wrap_err!("{} expected options to be a table or nil, got: {:?}", function_name, other);
+                }
+            };
+            let (default_filename, data) = match source {
+                // strings are paths, read now so a missing file errors here instead of halfway through sending
+                LuaValue::String(path) => {
+                    let path = path.to_string_lossy();
+                    let data = match fs::read(&path) {
+                        Ok(data) => data,
+                        Err(err) => {
+                            return wrap_err!("{}: unable to read file at '{}' due to err: {}", function_name, path, err);
+                        }
+                    };
+                    let default_filename = Path::new(&path)
+                        .file_name()
+                        .map(|filename| filename.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| name.clone());
+                    (default_filename, data)
+                },
+                LuaValue::Buffer(buffy) => (name.clone(), buffy.to_vec()),
+                other => {
+                    return wrap_err!("{} expected source to be a path (string) or buffer, got: {:?}", function_name, other);
+                }
+            };
+            this.borrow_mut::<Multipart>()?.parts.push(MultipartPart {
+                name,
+                filename: Some(filename.unwrap_or(default_filename)),
+                content_type: Some(content_type.unwrap_or_else(|| String::from("application/octet-stream"))),
+                data,
+            });
+            Ok(this)
+        });
+    }
+}
+
+pub fn http_multipart(luau: &Lua, _value: LuaValue) -> LuaValueResult {
+    ok_userdata(Multipart::new(), luau)
+}
+
+/// the Content-Type and encoded bytes of a `FormBody` or `Multipart`, or `None` if it's some other kind of userdata
+pub fn encoded_body(userdata: &LuaAnyUserData) -> Option<(String, Vec<u8>)> {
+    if let Ok(form) = userdata.borrow::<FormBody>() {
+        Some((String::from("application/x-www-form-urlencoded"), form.encoded.clone().into_bytes()))
+    } else if let Ok(multipart) = userdata.borrow::<Multipart>() {
+        Some((multipart.content_type(), multipart.encode()))
+    } else {
+        None
+    }
+}
+
+/// one part of a parsed `multipart/form-data` request body
+pub struct ParsedPart {
+    pub name: String,
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub data: Vec<u8>,
+}
+
+/// splits a header value like `form-data; name="a"; filename="b.txt"` into its (lowercase) parameters, unquoting quoted values
+fn header_params(value: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut chars = value.chars().peekable();
+    // skip the value itself (`form-data`, `multipart/form-data`)
+    for c in chars.by_ref() {
+        if c == ';' {
+            break;
+        }
+    }
+    while chars.peek().is_some() {
+        let mut key = String::new();
+        let mut has_value = false;
+        for c in chars.by_ref() {
+            if c == '=' {
+                has_value = true;
+                break;
+            } else if c == ';' {
+                break;
+            }
+            key.push(c);
+        }
+        let key = key.trim().to_ascii_lowercase();
+        let mut param_value = String::new();
+        if has_value {
+            while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                chars.next();
+            }
+            if chars.peek() == Some(&'"') {
+                chars.next();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => {
+                            if let Some(escaped) = chars.next() {
+                                param_value.push(escaped);
+                            }
+                        },
+                        c => param_value.push(c),
+                    }
+                }
+                // anything between the closing quote and the next ';' is junk
+                for c in chars.by_ref() {
+                    if c == ';' {
+                        break;
+                    }
+                }
+            } else {
+                for c in chars.by_ref() {
+                    if c == ';' {
+                        break;
+                    }
+                    param_value.push(c);
+                }
+                param_value = param_value.trim().to_string();
+            }
+        }
+        if !key.is_empty() {
+            params.push((key, param_value));
+        }
+    }
+    params
+}
+
+fn find_bytes(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    if from > haystack.len() {
+        return None;
+    }
+    haystack[from..]
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|position| position + from)
+}
+
+/// the boundary from a `multipart/form-data` Content-Type, or `None` if it's some other content type
+pub fn multipart_boundary(content_type: &str) -> Option<String> {
+    let media_type = content_type.split(';').next().unwrap_or_default().trim();
+    if !media_type.eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    header_params(content_type)
+        .into_iter()
+        .find(|(key, _)| key == "boundary")
+        .map(|(_, boundary)| boundary)
+        .filter(|boundary| !boundary.is_empty())
+}
+
+pub fn is_urlencoded(content_type: &str) -> bool {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .eq_ignore_ascii_case("application/x-www-form-urlencoded")
+}
+
+/// parses a `multipart/form-data` body; the `Err` describes what's malformed about it
+pub fn parse_multipart(body: &[u8], boundary: &str) -> Result<Vec<ParsedPart>, String> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    // every delimiter after the first is preceded by a CRLF that belongs to the delimiter, not the previous part
+    let part_end = format!("\r\n--{}", boundary).into_bytes();
+
+    let Some(first) = find_bytes(body, &delimiter, 0) else {
+        return Err(String::from("missing opening boundary"));
+    };
+    let mut position = first + delimiter.len();
+    let mut parts = Vec::new();
+
+    loop {
+        if body[position..].starts_with(b"--") {
+            // closing delimiter; anything after it is an epilogue we ignore
+            return Ok(parts);
+        }
+        // transport padding (spaces/tabs) is allowed before the CRLF after a delimiter
+        while position < body.len() && (body[position] == b' ' || body[position] == b'\t') {
+            position += 1;
+        }
+        if !body[position..].starts_with(b"\r\n") {
+            return Err(String::from("expected CRLF after boundary"));
+        }
+        position += 2;
+
+        // a part with no headers at all starts with its blank line
+        let (headers, data_start) = if body[position..].starts_with(b"\r\n") {
+            (String::new(), position + 2)
+        } else {
+            match find_bytes(body, b"\r\n\r\n", position) {
+                Some(headers_end) => (String::from_utf8_lossy(&body[position..headers_end]).into_owned(), headers_end + 4),
+                None => {
+                    return Err(String::from("part headers aren't terminated by a blank line"));
+                }
+            }
+        };
+
+        let mut name: Option<String> = None;
+        let mut filename: Option<String> = None;
+        let mut content_type: Option<String> = None;
+        for line in headers.split("\r\n") {
+            let Some((header, value)) = line.split_once(':') else {
+                continue;
+            };
+            let header = header.trim();
+            if header.eq_ignore_ascii_case("content-disposition") {
+                for (key, param_value) in header_params(value) {
+                    match key.as_str() {
+                        "name" => name = Some(param_value),
+                        "filename" => filename = Some(param_value),
+                        _ => {},
+                    }
+                }
+            } else if header.eq_ignore_ascii_case("content-type") {
+                content_type = Some(value.trim().to_string());
+            }
+        }
+        let Some(name) = name else {
+            return Err(String::from("part is missing a Content-Disposition name"));
+        };
+
+        let Some(data_end) = find_bytes(body, &part_end, data_start) else {
+            return Err(String::from("missing closing boundary"));
+        };
+        parts.push(ParsedPart {
+            name,
+            filename,
+            content_type,
+            data: body[data_start..data_end].to_vec(),
+        });
+        position = data_end + part_end.len();
+    }
+}
@@ -1,12 +1,27 @@
 use mluau::prelude::*;
 
+pub mod connection;
+pub mod form;
 pub mod http;
+pub mod request_parser;
+pub mod response_body;
+pub mod response_stream;
+pub mod router;
 pub mod serve;
+pub mod static_files;
+pub mod tcp;
+pub mod udp;
+pub mod url;
+pub mod websocket;
 
 use crate::prelude::*;
 
 pub fn create(luau: &Lua) -> LuaResult<LuaTable> {
     TableBuilder::create(luau)?
         .with_value("http", self::http::create(luau)?)?
+        .with_value("websocket", self::websocket::create(luau)?)?
+        .with_value("tcp", self::tcp::create(luau)?)?
+        .with_value("udp", self::udp::create(luau)?)?
+        .with_value("url", self::url::create(luau)?)?
         .build_readonly()
 }
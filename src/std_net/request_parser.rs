@@ -0,0 +1,321 @@
+//! HTTP/1.1 request parsing for `server.serve` (RFC 9112), working on raw bytes so binary bodies come through intact.
+
+use std::io::{BufRead, Read, Write};
+
+pub const DEFAULT_MAX_HEADER_SIZE: usize = 16 * 1024;
+pub const DEFAULT_MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+/// chunk size lines are just a hex number and maybe some extensions; anything longer is garbage
+const MAX_CHUNK_SIZE_LINE: usize = 1024;
+
+pub const BAD_REQUEST: &str = "400 Bad Request";
+pub const PAYLOAD_TOO_LARGE: &str = "413 Payload Too Large";
+pub const HEADERS_TOO_LARGE: &str = "431 Request Header Fields Too Large";
+
+#[derive(Debug, Clone, Copy)]
+pub struct RequestLimits {
+    /// max size of the request line + headers (and chunked trailers), in bytes
+    pub max_header_size: usize,
+    /// max size of the (decoded) request body, in bytes
+    pub max_body_size: usize,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self {
+            max_header_size: DEFAULT_MAX_HEADER_SIZE,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+        }
+    }
+}
+
+pub struct ParsedRequest {
+    pub method: String,
+    /// the request target, including the query string
+    pub target: String,
+    pub http_1_1: bool,
+    /// lowercased header names in the order they were first seen; repeated headers are combined into one value
+    pub headers: Vec<(String, String)>,
+    /// the request line and header lines as sent, joined by newlines
+    pub head: String,
+    pub body: Vec<u8>,
+    /// set when the request's framing is sketchy enough that we shouldn't reuse the connection
+    pub must_close: bool,
+}
+
+impl ParsedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+}
+
+pub enum ParseError {
+    /// the client hung up (or an idle keep-alive connection timed out) before sending a full request
+    Closed,
+    /// the request is malformed or too big, and should be answered with `status` before closing the connection
+    Rejected { status: &'static str, reason: String },
+}
+
+impl ParseError {
+    fn bad_request(reason: impl Into<String>) -> Self {
+        ParseError::Rejected { status: BAD_REQUEST, reason: reason.into() }
+    }
+    fn headers_too_large(limits: &RequestLimits) -> Self {
+        ParseError::Rejected {
+            status: HEADERS_TOO_LARGE,
+            reason: format!("request headers exceed the server's limit of {} bytes", limits.max_header_size),
+        }
+    }
+    fn payload_too_large(limits: &RequestLimits) -> Self {
+        ParseError::Rejected {
+            status: PAYLOAD_TOO_LARGE,
+            reason: format!("request body exceeds the server's limit of {} bytes", limits.max_body_size),
+        }
+    }
+}
+
+type ParseResult<T> = Result<T, ParseError>;
+
+/// reads and parses one request off the connection; `writer` is only used to send `100 Continue` when the client asks for it
+pub fn parse_request<R: BufRead, W: Write>(reader: &mut R, writer: &mut W, limits: &RequestLimits) -> ParseResult<ParsedRequest> {
+    let mut header_budget = limits.max_header_size;
+    let too_large = || ParseError::headers_too_large(limits);
+
+    // RFC 9112 2.2: servers should ignore empty lines sent before the request line
+    let request_line = loop {
+        match read_line(reader, &mut header_budget, too_large)? {
+            None => return Err(ParseError::Closed),
+            Some(line) if line.is_empty() => continue,
+            Some(line) => break line,
+        }
+    };
+    let Ok(request_line) = String::from_utf8(request_line) else {
+        return Err(ParseError::bad_request("request line isn't valid utf-8"));
+    };
+
+    let mut parts = request_line.split(' ');
+    let (Some(method), Some(target), Some(version), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+        return Err(ParseError::bad_request(format!("malformed request line: {}", request_line)));
+    };
+    if method.is_empty() || !method.bytes().all(is_token_byte) {
+        return Err(ParseError::bad_request(format!("invalid method: {}", method)));
+    }
+    if target.is_empty() {
+        return Err(ParseError::bad_request("missing request target"));
+    }
+    let http_1_1 = match version {
+        "HTTP/1.1" => true,
+        "HTTP/1.0" => false,
+        other => {
+            return Err(ParseError::bad_request(format!("unsupported http version: {}", other)));
+        }
+    };
+
+    let mut head = request_line.clone();
+    let mut headers: Vec<(String, String)> = Vec::new();
+    let mut last_header_name: Option<String> = None;
+    loop {
+        let Some(line) = read_line(reader, &mut header_budget, too_large)? else {
+            return Err(ParseError::Closed);
+        };
+        if line.is_empty() {
+            break;
+        }
+        let line = String::from_utf8_lossy(&line).into_owned();
+        head.push('\n');
+        head.push_str(&line);
+
+        if line.starts_with([' ', '\t']) {
+            // obsolete line folding; RFC 9112 5.2 lets us replace it with a space
+            let Some(name) = &last_header_name else {
+                return Err(ParseError::bad_request("headers can't start with whitespace"));
+            };
+            if let Some((_, value)) = headers.iter_mut().find(|(existing, _)| existing == name) {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+            continue;
+        }
+
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(ParseError::bad_request(format!("malformed header line: {}", line)));
+        };
+        // this also rejects whitespace between the name and colon (RFC 9112 5.1)
+        if name.is_empty() || !name.bytes().all(is_token_byte) {
+            return Err(ParseError::bad_request(format!("invalid header name: '{}'", name)));
+        }
+        let name = name.to_ascii_lowercase();
+        add_header(&mut headers, &name, value.trim());
+        last_header_name = Some(name);
+    }
+
+    let expects_continue = http_1_1
+        && find_header(&headers, "expect").is_some_and(|expect| expect.eq_ignore_ascii_case("100-continue"));
+    let content_length = find_header(&headers, "content-length");
+    let mut must_close = false;
+
+    let body = if let Some(transfer_encoding) = find_header(&headers, "transfer-encoding") {
+        if !transfer_encoding.eq_ignore_ascii_case("chunked") {
+            return Err(ParseError::bad_request(format!("unsupported transfer-encoding: {}", transfer_encoding)));
+        }
+        // RFC 9112 6.1: chunked wins over Content-Length, but a request with both smells like smuggling so we don't reuse the connection
+        if content_length.is_some() {
+            must_close = true;
+        }
+        if expects_continue {
+            send_continue(writer)?;
+        }
+        read_chunked_body(reader, limits, &mut header_budget)?
+    } else if let Some(content_length) = content_length {
+        let length = parse_content_length(content_length, limits)?;
+        if length > limits.max_body_size {
+            return Err(ParseError::payload_too_large(limits));
+        }
+        if expects_continue && length > 0 {
+            send_continue(writer)?;
+        }
+        let mut body = vec![0; length];
+        if reader.read_exact(&mut body).is_err() {
+            return Err(ParseError::Closed);
+        }
+        body
+    } else {
+        Vec::new()
+    };
+
+    Ok(ParsedRequest {
+        method: method.to_string(),
+        target: target.to_string(),
+        http_1_1,
+        headers,
+        head,
+        body,
+        must_close,
+    })
+}
+
+/// reads one line, stripping its CRLF (or bare LF), and counting it against `budget`;
+/// returns `None` if the connection was closed before anything was read
+fn read_line<R: BufRead>(reader: &mut R, budget: &mut usize, too_large: impl Fn() -> ParseError) -> ParseResult<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    // read one byte past the budget so we can tell a line that exactly fits apart from one that doesn't
+    let bytes_read = match reader.by_ref().take(*budget as u64 + 1).read_until(b'\n', &mut line) {
+        Ok(bytes_read) => bytes_read,
+        // resets, timeouts, etc. all mean the same thing to us: there's nobody left to respond to
+        Err(_) => return Err(ParseError::Closed),
+    };
+    if bytes_read == 0 {
+        return Ok(None);
+    }
+    if bytes_read > *budget {
+        return Err(too_large());
+    }
+    if !line.ends_with(b"\n") {
+        return Err(ParseError::Closed);
+    }
+    *budget -= bytes_read;
+
+    line.pop();
+    if line.ends_with(b"\r") {
+        line.pop();
+    }
+    Ok(Some(line))
+}
+
+fn read_chunked_body<R: BufRead>(reader: &mut R, limits: &RequestLimits, header_budget: &mut usize) -> ParseResult<Vec<u8>> {
+    let mut body: Vec<u8> = Vec::new();
+    loop {
+        let mut line_budget = MAX_CHUNK_SIZE_LINE;
+        let Some(size_line) = read_line(reader, &mut line_budget, || ParseError::bad_request("chunk size line too long"))? else {
+            return Err(ParseError::Closed);
+        };
+        // chunk extensions (;name=value) come after the size and we don't care about them
+        let size_str = String::from_utf8_lossy(&size_line);
+        let size_str = size_str.split(';').next().unwrap_or_default().trim();
+        if size_str.is_empty() || !size_str.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(ParseError::bad_request(format!("invalid chunk size: '{}'", size_str)));
+        }
+        let Ok(size) = usize::from_str_radix(size_str, 16) else {
+            return Err(ParseError::payload_too_large(limits));
+        };
+        if size == 0 {
+            break;
+        }
+        if body.len().saturating_add(size) > limits.max_body_size {
+            return Err(ParseError::payload_too_large(limits));
+        }
+
+        let start = body.len();
+        body.resize(start + size, 0);
+        if reader.read_exact(&mut body[start..]).is_err() {
+            return Err(ParseError::Closed);
+        }
+        // chunk data is followed by a CRLF; anything else means the size was a lie
+        let mut line_budget = 2;
+        match read_line(reader, &mut line_budget, || ParseError::bad_request("chunk data longer than its chunk size"))? {
+            Some(line) if line.is_empty() => {},
+            Some(_) => return Err(ParseError::bad_request("chunk data longer than its chunk size")),
+            None => return Err(ParseError::Closed),
+        }
+    }
+
+    // trailer fields count against the header limit; RFC 9110 6.5.1 says not to merge them into the headers so we drop them
+    loop {
+        match read_line(reader, header_budget, || ParseError::headers_too_large(limits))? {
+            Some(line) if line.is_empty() => break,
+            Some(_) => continue,
+            None => return Err(ParseError::Closed),
+        }
+    }
+    Ok(body)
+}
+
+/// Content-Length can be repeated (or sent as a list) as long as every value is the same
+fn parse_content_length(value: &str, limits: &RequestLimits) -> ParseResult<usize> {
+    let mut length: Option<usize> = None;
+    for part in value.split(',').map(str::trim) {
+        if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ParseError::bad_request(format!("invalid Content-Length: {}", value)));
+        }
+        // only digits left, so parsing can only fail by overflowing
+        let Ok(parsed) = part.parse::<usize>() else {
+            return Err(ParseError::payload_too_large(limits));
+        };
+        match length {
+            Some(existing) if existing != parsed => {
+                return Err(ParseError::bad_request(format!("conflicting Content-Length values: {}", value)));
+            },
+            _ => length = Some(parsed),
+        }
+    }
+    length.ok_or_else(|| ParseError::bad_request("empty Content-Length"))
+}
+
+fn send_continue<W: Write>(writer: &mut W) -> ParseResult<()> {
+    match writer.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").and_then(|_| writer.flush()) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(ParseError::Closed),
+    }
+}
+
+fn add_header(headers: &mut Vec<(String, String)>, name: &str, value: &str) {
+    match headers.iter_mut().find(|(existing, _)| existing == name) {
+        Some((_, existing_value)) => {
+            // RFC 9110 5.3: repeated fields combine into a comma separated list, except Cookie which uses semicolons
+            existing_value.push_str(if name == "cookie" { "; " } else { ", " });
+            existing_value.push_str(value);
+        },
+        None => headers.push((name.to_string(), value.to_string())),
+    }
+}
+
+fn find_header<'h>(headers: &'h [(String, String)], name: &str) -> Option<&'h str> {
+    headers
+        .iter()
+        .find(|(existing, _)| existing.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// `tchar` from RFC 9110 5.6.2
+pub(crate) fn is_token_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
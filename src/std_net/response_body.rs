@@ -0,0 +1,146 @@
+//! `ResponseReader`: reads streamed `@std/net/http` response bodies in chunks instead of all at once.
+
+use mluau::prelude::*;
+use crate::prelude::*;
+use ureq::{Body, BodyReader};
+
+use std::cell::RefCell;
+use std::io::Read;
+use std::rc::Rc;
+
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// `None` once the body's been fully read (or closed), so we can drop the connection asap
+type ReaderCell = Rc<RefCell<Option<BodyReader<'static>>>>;
+
+fn with_reader<T>(reader_cell: &ReaderCell, function_name: &'static str, f: impl FnOnce(&mut BodyReader<'static>) -> LuaResult<T>, done: T) -> LuaResult<T> {
+    let mut reader = match reader_cell.try_borrow_mut() {
+        Ok(reader) => reader,
+        Err(_) => {
+            return wrap_err!("{}: reader already borrowed", function_name);
+        }
+    };
+    match reader.as_mut() {
+        Some(body_reader) => f(body_reader),
+        None => Ok(done),
+    }
+}
+
+/// reads until `target` is full or the body ends, so callers get full chunks instead of whatever one socket read returned
+fn read_into(reader: &mut BodyReader<'static>, target: &mut [u8], function_name: &'static str) -> LuaResult<usize> {
+    let mut filled = 0;
+    while filled < target.len() {
+        match reader.read(&mut target[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => {
+                return wrap_err!("{}: unable to read response body: {}", function_name, err);
+            }
+        }
+    }
+    Ok(filled)
+}
+
+pub fn create_reader(luau: &Lua, body: Body) -> LuaResult<LuaTable> {
+    let reader_cell: ReaderCell = Rc::new(RefCell::new(Some(body.into_reader())));
+
+    TableBuilder::create(luau)?
+        .with_function("read", {
+            let reader_cell = Rc::clone(&reader_cell);
+            move | luau: &Lua, mut multivalue: LuaMultiValue | -> LuaValueResult {
+                let function_name = "ResponseReader:read(count: number?)";
+                pop_self(&mut multivalue, function_name)?;
+                let count = match multivalue.pop_front() {
+                    Some(LuaValue::Integer(count)) if count > 0 => int_to_usize(count, function_name, "count")?,
+                    Some(LuaNil) | None => DEFAULT_CHUNK_SIZE,
+                    Some(other) => {
+                        return wrap_err!("{} expected count to be a positive integer or nil, got: {:?}", function_name, other);
+                    }
+                };
+                let chunk = with_reader(&reader_cell, function_name, |reader| {
+                    let mut chunk = vec![0; count];
+                    let read = read_into(reader, &mut chunk, function_name)?;
+                    chunk.truncate(read);
+                    Ok(Some(chunk))
+                }, None)?;
+                match chunk {
+                    Some(chunk) if !chunk.is_empty() => ok_buffy(chunk, luau),
+                    _ => {
+                        // body's done, let go of the connection
+                        reader_cell.borrow_mut().take();
+                        Ok(LuaNil)
+                    }
+                }
+            }
+        })?
+        .with_function("fill", {
+            let reader_cell = Rc::clone(&reader_cell);
+            move | _luau: &Lua, mut multivalue: LuaMultiValue | -> LuaResult<usize> {
+                let function_name = "ResponseReader:fill(target: buffer, target_offset: number?)";
+                pop_self(&mut multivalue, function_name)?;
+                let target = match multivalue.pop_front() {
+                    Some(LuaValue::Buffer(target)) => target,
+                    other => {
+                        return wrap_err!("{} expected target to be a buffer, got: {:?}", function_name, other);
+                    }
+                };
+                let offset = match multivalue.pop_front() {
+                    Some(LuaValue::Integer(offset)) => int_to_usize(offset, function_name, "target_offset")?,
+                    Some(LuaNil) | None => 0,
+                    Some(other) => {
+                        return wrap_err!("{} expected target_offset to be an integer or nil, got: {:?}", function_name, other);
+                    }
+                };
+                if offset > target.len() {
+                    return wrap_err!("{}: target_offset {} is out of bounds for buffer of length {}", function_name, offset, target.len());
+                }
+                let mut chunk = vec![0; target.len() - offset];
+                let read = with_reader(&reader_cell, function_name, |reader| read_into(reader, &mut chunk, function_name), 0)?;
+                if read == 0 {
+                    reader_cell.borrow_mut().take();
+                } else {
+                    target.write_bytes(offset, &chunk[..read]);
+                }
+                Ok(read)
+            }
+        })?
+        .with_function("chunks", {
+            let reader_cell = Rc::clone(&reader_cell);
+            move | luau: &Lua, mut multivalue: LuaMultiValue | -> LuaResult<LuaFunction> {
+                let function_name = "ResponseReader:chunks(size: number?)";
+                pop_self(&mut multivalue, function_name)?;
+                let size = match multivalue.pop_front() {
+                    Some(LuaValue::Integer(size)) if size > 0 => int_to_usize(size, function_name, "size")?,
+                    Some(LuaNil) | None => DEFAULT_CHUNK_SIZE,
+                    Some(other) => {
+                        return wrap_err!("{} expected size to be a positive integer or nil, got: {:?}", function_name, other);
+                    }
+                };
+                let reader_cell = Rc::clone(&reader_cell);
+                luau.create_function(move | luau: &Lua, _value: LuaMultiValue | -> LuaValueResult {
+                    let function_name = "ResponseReader:chunks() iterator function";
+                    let chunk = with_reader(&reader_cell, function_name, |reader| {
+                        let mut chunk = vec![0; size];
+                        let read = read_into(reader, &mut chunk, function_name)?;
+                        chunk.truncate(read);
+                        Ok(chunk)
+                    }, Vec::new())?;
+                    if chunk.is_empty() {
+                        reader_cell.borrow_mut().take();
+                        Ok(LuaNil)
+                    } else {
+                        ok_buffy(chunk, luau)
+                    }
+                })
+            }
+        })?
+        .with_function("close", {
+            let reader_cell = Rc::clone(&reader_cell);
+            move | _luau: &Lua, _value: LuaMultiValue | -> LuaEmptyResult {
+                reader_cell.borrow_mut().take();
+                Ok(())
+            }
+        })?
+        .build_readonly()
+}
@@ -0,0 +1,190 @@
+//! Streamed `server.serve` responses: chunked transfer encoding and Server-Sent Events.
+
+use mluau::prelude::*;
+use crate::prelude::*;
+use crate::std_json;
+use super::connection::ServeConnection;
+
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+struct ChunkedWriter {
+    stream: ServeConnection,
+    /// HTTP/1.0 clients don't understand chunked encoding, so we write raw bytes and end the response by closing the connection
+    chunked: bool,
+    /// format chunks as `text/event-stream` events
+    sse: bool,
+    /// set once a write fails; the client's gone so there's no point writing anything else
+    disconnected: bool,
+}
+
+impl ChunkedWriter {
+    /// returns whether the client is still connected
+    fn write_chunk(&mut self, bytes: &[u8]) -> bool {
+        // a zero length chunk would end the response early
+        if self.disconnected || bytes.is_empty() {
+            return !self.disconnected;
+        }
+        let result = if self.chunked {
+            write!(self.stream, "{:x}\r\n", bytes.len())
+                .and_then(|_| self.stream.write_all(bytes))
+                .and_then(|_| self.stream.write_all(b"\r\n"))
+        } else {
+            self.stream.write_all(bytes)
+        };
+        // flush every chunk; the whole point of streaming is that the client sees it now
+        if result.and_then(|_| self.stream.flush()).is_err() {
+            self.disconnected = true;
+        }
+        !self.disconnected
+    }
+
+    fn finish(&mut self) -> bool {
+        if self.chunked && !self.disconnected && self.stream.write_all(b"0\r\n\r\n").and_then(|_| self.stream.flush()).is_err() {
+            self.disconnected = true;
+        }
+        !self.disconnected
+    }
+
+    fn chunk_to_bytes(&self, luau: &Lua, chunk: LuaValue, function_name: &'static str) -> LuaResult<Vec<u8>> {
+        match chunk {
+            LuaValue::Buffer(buffy) => Ok(buffy.to_vec()),
+            LuaValue::String(s) if self.sse => Ok(format_sse_data(&s.to_string_lossy(), None)),
+            LuaValue::String(s) => Ok(s.as_bytes().to_vec()),
+            LuaValue::Table(event) if self.sse => format_sse_event(luau, event, function_name),
+            other => {
+                let expected = if self.sse { "string, buffer, or ServerSentEvent table" } else { "string or buffer" };
+                wrap_err!("{} expected chunk to be a {}, got: {:?}", function_name, expected, other)
+            }
+        }
+    }
+}
+
+/// `data:` lines for each line of `data`, optionally preceded by other fields, terminated by a blank line
+fn format_sse_data(data: &str, fields: Option<String>) -> Vec<u8> {
+    let mut event = fields.unwrap_or_default();
+    for line in data.split('\n') {
+        event.push_str("data: ");
+        event.push_str(line.strip_suffix('\r').unwrap_or(line));
+        event.push('\n');
+    }
+    event.push('\n');
+    event.into_bytes()
+}
+
+fn format_sse_event(luau: &Lua, event: LuaTable, function_name: &'static str) -> LuaResult<Vec<u8>> {
+    let mut fields = String::new();
+    if let LuaValue::String(comment) = event.raw_get("comment")? {
+        for line in comment.to_string_lossy().lines() {
+            fields.push_str(&format!(": {}\n", line));
+        }
+    }
+    for field in ["event", "id"] {
+        match event.raw_get(field)? {
+            LuaValue::String(value) => {
+                let value = value.to_string_lossy();
+                // a newline would start a new field and let the value smuggle its own
+                if value.contains(['\n', '\r']) {
+                    return wrap_err!("{}: ServerSentEvent.{} can't contain newlines, got: {:?}", function_name, field, value);
+                }
+                fields.push_str(&format!("{}: {}\n", field, value));
+            },
+            LuaNil => {},
+            other => {
+                return wrap_err!("{} expected ServerSentEvent.{} to be a string or nil, got: {:?}", function_name, field, other);
+            }
+        }
+    }
+    match event.raw_get("retry")? {
+        LuaValue::Integer(retry) => fields.push_str(&format!("retry: {}\n", int_to_u64(retry, function_name, "ServerSentEvent.retry")?)),
+        LuaNil => {},
+        other => {
+            return wrap_err!("{} expected ServerSentEvent.retry (reconnection time in milliseconds) to be an integer or nil, got: {:?}", function_name, other);
+        }
+    }
+
+    let data = match event.raw_get("data")? {
+        LuaValue::String(data) => Some(data.to_string_lossy()),
+        LuaValue::Table(data) => Some(std_json::json_raw_encode(luau, LuaValue::Table(data))?),
+        LuaNil => None,
+        other => {
+            return wrap_err!("{} expected ServerSentEvent.data to be a string, table (to serialize as json), or nil, got: {:?}", function_name, other);
+        }
+    };
+    match data {
+        Some(data) => Ok(format_sse_data(&data, Some(fields))),
+        None if fields.is_empty() => {
+            wrap_err!("{}: ServerSentEvent must have at least one of fields 'data', 'event', 'id', 'retry', or 'comment'", function_name)
+        },
+        None => {
+            fields.push('\n');
+            Ok(fields.into_bytes())
+        }
+    }
+}
+
+/**
+Streams a response body from a `ServeResponse.body` function.
+
+The function's called over and over with a `ResponseWriter` until it returns nil; each call can return a chunk
+to send and/or send chunks with `writer:write(chunk)`. This means plain iterators (like `ChildProcessStream:lines()`)
+and writer-style callbacks both work.
+
+Returns whether the client's still connected after the response.
+*/
+pub fn stream_body(luau: &Lua, stream: &ServeConnection, body: LuaFunction, chunked: bool, sse: bool) -> LuaResult<bool> {
+    let stream = stream.clone();
+    let writer_cell = Rc::new(RefCell::new(ChunkedWriter { stream, chunked, sse, disconnected: false }));
+
+    let writer_handle = TableBuilder::create(luau)?
+        .with_function("write", {
+            let writer_cell = Rc::clone(&writer_cell);
+            move | luau: &Lua, mut multivalue: LuaMultiValue | -> LuaResult<bool> {
+                let function_name = "ResponseWriter:write(chunk: string | buffer | ServerSentEvent)";
+                pop_self(&mut multivalue, function_name)?;
+                let chunk = multivalue.pop_front().unwrap_or(LuaNil);
+                let mut writer = match writer_cell.try_borrow_mut() {
+                    Ok(writer) => writer,
+                    Err(_) => {
+                        return wrap_err!("{}: writer already borrowed", function_name);
+                    }
+                };
+                let bytes = writer.chunk_to_bytes(luau, chunk, function_name)?;
+                Ok(writer.write_chunk(&bytes))
+            }
+        })?
+        .with_function("connected", {
+            let writer_cell = Rc::clone(&writer_cell);
+            move | _luau: &Lua, _value: LuaValue | -> LuaResult<bool> {
+                match writer_cell.try_borrow() {
+                    Ok(writer) => Ok(!writer.disconnected),
+                    Err(_) => wrap_err!("ResponseWriter:connected(): writer already borrowed"),
+                }
+            }
+        })?
+        .build_readonly()?;
+
+    let function_name = "ServeResponse.body streaming function";
+    loop {
+        let chunk = match body.call::<LuaValue>(writer_handle.clone()) {
+            Ok(LuaNil) => break,
+            Ok(chunk) => chunk,
+            Err(err) => {
+                // we can't send an error status anymore; dropping the connection without a final chunk tells the client the response is incomplete
+                return wrap_err!("{} errored while streaming the response: {}", function_name, err);
+            }
+        };
+        let mut writer = writer_cell.borrow_mut();
+        let bytes = writer.chunk_to_bytes(luau, chunk, function_name)?;
+        if !writer.write_chunk(&bytes) {
+            return Ok(false);
+        }
+    }
+
+    let mut writer = writer_cell.borrow_mut();
+    if writer.disconnected {
+        return Ok(false);
+    }
+    Ok(writer.finish())
+}
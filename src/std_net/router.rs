@@ -0,0 +1,169 @@
+use mluau::prelude::*;
+use crate::prelude::*;
+use super::url::percent_decode;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    /// `:name`, matches exactly one path segment
+    Param(String),
+    /// `*`, matches zero or more trailing path segments
+    Wildcard,
+}
+
+impl Segment {
+    fn rank(&self) -> u8 {
+        match self {
+            Segment::Literal(_) => 0,
+            Segment::Param(_) => 1,
+            Segment::Wildcard => 2,
+        }
+    }
+}
+
+struct Route {
+    /// `None` when the route accepts any method (`"/health"` or `"* /health"`)
+    method: Option<String>,
+    segments: Vec<Segment>,
+    handler: LuaFunction,
+}
+
+impl Route {
+    fn parse(pattern: &str, handler: LuaFunction, function_name: &'static str) -> LuaResult<Self> {
+        let trimmed = pattern.trim();
+        let (method, path) = match trimmed.split_once(char::is_whitespace) {
+            Some(("*", path)) => (None, path.trim()),
+            Some((method, path)) => (Some(method.to_ascii_uppercase()), path.trim()),
+            None => (None, trimmed),
+        };
+
+        if !path.starts_with('/') {
+            return wrap_err!("{}: route '{}' should look like \"GET /users/:id\" or \"/users/:id\" (path must start with /)", function_name, pattern);
+        }
+
+        let raw_segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let mut segments = Vec::with_capacity(raw_segments.len());
+        for (index, segment) in raw_segments.iter().enumerate() {
+            if *segment == "*" {
+                if index != raw_segments.len() - 1 {
+                    return wrap_err!("{}: route '{}' can only have a wildcard (*) as its last segment", function_name, pattern);
+                }
+                segments.push(Segment::Wildcard);
+            } else if let Some(name) = segment.strip_prefix(':') {
+                if name.is_empty() {
+                    return wrap_err!("{}: route '{}' has a path param (:) without a name", function_name, pattern);
+                }
+                segments.push(Segment::Param(name.to_string()));
+            } else {
+                segments.push(Segment::Literal(segment.to_string()));
+            }
+        }
+
+        Ok(Self { method, segments, handler })
+    }
+
+    /// literal segments beat params, which beat wildcards; routes with a method beat catch-all methods
+    fn specificity(&self) -> (Vec<u8>, bool) {
+        (self.segments.iter().map(Segment::rank).collect(), self.method.is_none())
+    }
+
+    fn match_path(&self, path_segments: &[String]) -> Option<Vec<(String, String)>> {
+        let mut params = Vec::new();
+        for (index, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Wildcard => {
+                    params.push((String::from("*"), path_segments[index.min(path_segments.len())..].join("/")));
+                    return Some(params);
+                },
+                Segment::Literal(literal) => {
+                    if path_segments.get(index)? != literal {
+                        return None;
+                    }
+                },
+                Segment::Param(name) => {
+                    params.push((name.clone(), path_segments.get(index)?.clone()));
+                },
+            }
+        }
+        if path_segments.len() == self.segments.len() {
+            Some(params)
+        } else {
+            None
+        }
+    }
+}
+
+pub enum RouteMatch<'router> {
+    Found { handler: &'router LuaFunction, params: Vec<(String, String)> },
+    /// the path matched at least one route, but none of them accept this method
+    MethodNotAllowed { allowed: Vec<String> },
+    NotFound,
+}
+
+/// Dispatches requests to handler functions by `"METHOD /path/:param/*"` patterns,
+/// used when `ServeConfig.handler` is a table instead of a function.
+pub struct Router {
+    routes: Vec<Route>,
+    pub not_found: Option<LuaFunction>,
+    pub method_not_allowed: Option<LuaFunction>,
+}
+
+impl Router {
+    pub fn from_table(
+        routes_table: LuaTable,
+        not_found: Option<LuaFunction>,
+        method_not_allowed: Option<LuaFunction>,
+        function_name: &'static str,
+    ) -> LuaResult<Self> {
+        let mut routes = Vec::new();
+        for pair in routes_table.pairs::<LuaValue, LuaValue>() {
+            let (pattern, handler) = pair?;
+            let pattern = match pattern {
+                LuaValue::String(pattern) => pattern.to_string_lossy(),
+                other => {
+                    return wrap_err!("{}: expected route keys to be strings like \"GET /users/:id\", got: {:?}", function_name, other);
+                }
+            };
+            let handler = match handler {
+                LuaValue::Function(f) => f,
+                other => {
+                    return wrap_err!("{}: expected route '{}' to map to a handler function, got: {:?}", function_name, pattern, other);
+                }
+            };
+            routes.push(Route::parse(&pattern, handler, function_name)?);
+        }
+        // luau table iteration order isn't stable, so we sort routes to make sure the most specific one always wins
+        routes.sort_by_key(Route::specificity);
+        Ok(Self { routes, not_found, method_not_allowed })
+    }
+
+    pub fn find(&self, method: &str, path: &str) -> RouteMatch<'_> {
+        let path_segments: Vec<String> = path
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(percent_decode)
+            .collect();
+
+        let mut allowed: Vec<String> = Vec::new();
+        for route in &self.routes {
+            let Some(params) = route.match_path(&path_segments) else {
+                continue;
+            };
+            match &route.method {
+                Some(route_method) if route_method != method => {
+                    if !allowed.contains(route_method) {
+                        allowed.push(route_method.clone());
+                    }
+                },
+                _ => return RouteMatch::Found { handler: &route.handler, params },
+            }
+        }
+
+        if allowed.is_empty() {
+            RouteMatch::NotFound
+        } else {
+            RouteMatch::MethodNotAllowed { allowed }
+        }
+    }
+}
+
@@ -1,9 +1,123 @@
-#[allow(unused_imports)]
-use crate::{colors, std_json, table_helpers::TableBuilder, LuaValueResult};
+use crate::prelude::*;
 use mluau::prelude::*;
-use regex::Regex;
 use std::net::{TcpListener, TcpStream};
-use std::io::{prelude::*, BufReader, Write};
+use std::io::{self, prelude::*, BufReader, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use super::connection::{self, ServeConnection};
+use super::request_parser::{self, ParseError, ParsedRequest, RequestLimits};
+use super::{form, response_stream, static_files, url, websocket};
+use super::router::{RouteMatch, Router};
+use crate::err::{display_error, display_error_and_exit};
+use crate::globals;
+use crate::std_thread::thread_spawn_options::ThreadSpawnOptions;
+
+/// set by `server.shutdown()`; every running `server.serve` stops accepting connections once it's set
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+/// how long the accept loop sleeps between polls when there's no incoming connection
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(5);
+const DEFAULT_KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(5);
+/// how long we keep reading (and discarding) a rejected request's leftovers so the client actually sees our error response
+const REJECTED_REQUEST_DRAIN_TIMEOUT: Duration = Duration::from_millis(500);
+const REJECTED_REQUEST_DRAIN_LIMIT: u64 = 1024 * 1024;
+
+fn shutdown_requested() -> bool {
+    SHUTDOWN_REQUESTED.load(Ordering::Acquire)
+}
+
+enum ServeHandler {
+    Function(LuaFunction),
+    Router(Router),
+}
+
+impl ServeHandler {
+    /// reads `handler`, `not_found`, and `method_not_allowed` from a ServeConfig (or a worker module's returned table)
+    fn from_config(config: &LuaTable, function_name: &'static str) -> LuaResult<Self> {
+        match config.raw_get("handler")? {
+            LuaValue::Function(f) => Ok(ServeHandler::Function(f)),
+            LuaValue::Table(routes) => {
+                let not_found = get_optional_handler(config, "not_found")?;
+                let method_not_allowed = get_optional_handler(config, "method_not_allowed")?;
+                Ok(ServeHandler::Router(Router::from_table(routes, not_found, method_not_allowed, function_name)?))
+            },
+            other => {
+                wrap_err!("{} expected handler to be a function or table of routes, got: {:#?}", function_name, other)
+            }
+        }
+    }
+
+    /// worker modules can return a handler function, a table of routes, or a table with `handler`, `not_found`, etc. fields
+    fn from_worker_module(value: LuaValue, function_name: &'static str) -> LuaResult<Self> {
+        match value {
+            LuaValue::Function(f) => Ok(ServeHandler::Function(f)),
+            LuaValue::Table(t) if t.contains_key("handler")? => Self::from_config(&t, function_name),
+            LuaValue::Table(routes) => Ok(ServeHandler::Router(Router::from_table(routes, None, None, function_name)?)),
+            other => {
+                wrap_err!("{} expected the worker module to return a handler function, a table of routes, or a table with field 'handler', got: {:#?}", function_name, other)
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct ConnectionOptions {
+    /// how long an idle keep-alive connection stays open; `None` closes every connection after one response
+    keep_alive: Option<Duration>,
+    limits: RequestLimits,
+    /// set by `ServeConfig.tls`
+    tls: Option<Arc<rustls::ServerConfig>>,
+}
+
+enum ResponseBody {
+    Bytes(Vec<u8>),
+    /// called repeatedly to produce chunks, see `response_stream::stream_body`
+    Stream(LuaFunction),
+}
+
+/// what we should do with a request once we've figured out which handler (if any) it goes to
+enum HandlerCall {
+    Call(LuaFunction),
+    /// custom `ServeConfig.method_not_allowed` handler, called with the request and a list of allowed methods
+    CallMethodNotAllowed(LuaFunction, Vec<String>),
+    Respond(LuaTable),
+}
+
+fn get_optional_handler(config: &LuaTable, key: &'static str) -> LuaResult<Option<LuaFunction>> {
+    match config.raw_get(key)? {
+        LuaValue::Function(f) => Ok(Some(f)),
+        LuaNil => Ok(None),
+        other => {
+            wrap_err!("server.serve expected ServeConfig.{} to be a handler function or nil, got: {:#?}", key, other)
+        }
+    }
+}
+
+fn get_size_limit(config: &LuaTable, key: &'static str, default: usize) -> LuaResult<usize> {
+    match config.raw_get(key)? {
+        LuaValue::Integer(size) if size > 0 => int_to_usize(size, "server.serve", key),
+        LuaNil => Ok(default),
+        other => {
+            wrap_err!("server.serve expected ServeConfig.{} to be a positive integer (in bytes) or nil, got: {:#?}", key, other)
+        }
+    }
+}
+
+fn default_response(luau: &Lua, status_code: &str, allowed: Option<&[String]>) -> LuaResult<LuaTable> {
+    let headers = TableBuilder::create(luau)?;
+    let headers = match allowed {
+        Some(allowed) => headers.with_value("Allow", allowed.join(", "))?,
+        None => headers,
+    };
+    TableBuilder::create(luau)?
+        .with_value("status_code", status_code)?
+        .with_value("content_type", "text")?
+        .with_value("body", status_code)?
+        .with_value("headers", headers.build()?)?
+        .build()
+}
 
 fn server_serve(luau: &Lua, serve_config: LuaValue) -> LuaValueResult {
     let config = match serve_config {
@@ -33,16 +147,36 @@ fn server_serve(luau: &Lua, serve_config: LuaValue) -> LuaValueResult {
         }
     };
 
-    let handler_function = match config.raw_get("handler") {
-        Ok(LuaValue::Function(f)) => f,
-        Ok(LuaValue::Table(_handles)) => todo!(),
-        Ok(other) => {
-            return wrap_err!("server.serve expected handler to be a function, got: {:#?}", other);
+    let keep_alive = match config.raw_get("keep_alive")? {
+        LuaNil | LuaValue::Boolean(false) => None,
+        LuaValue::Boolean(true) => Some(DEFAULT_KEEP_ALIVE_TIMEOUT),
+        LuaValue::Integer(seconds) if seconds > 0 => Some(Duration::from_secs(seconds as u64)),
+        // read timeouts can't be zero, which is what tiny ones round down to
+        LuaValue::Number(seconds) if seconds.is_finite() && seconds > 0.0 => match Duration::try_from_secs_f64(seconds) {
+            Ok(timeout) if !timeout.is_zero() => Some(timeout),
+            Ok(_) => {
+                return wrap_err!("server.serve: ServeConfig.keep_alive idle timeout of {} seconds is too small", seconds);
+            },
+            Err(err) => {
+                return wrap_err!("server.serve: error creating Duration from ServeConfig.keep_alive of {} seconds: {}", seconds, err);
+            }
+        },
+        other => {
+            return wrap_err!("server.serve expected ServeConfig.keep_alive to be a boolean or idle timeout in seconds (finite number greater than 0), got: {:#?}", other);
         }
-        Err(err) => {
-            return wrap_err!("server.serve expected some handler, got an error: {}", err);
+    };
+    let limits = RequestLimits {
+        max_header_size: get_size_limit(&config, "max_header_size", request_parser::DEFAULT_MAX_HEADER_SIZE)?,
+        max_body_size: get_size_limit(&config, "max_body_size", request_parser::DEFAULT_MAX_BODY_SIZE)?,
+    };
+    let tls = match config.raw_get("tls")? {
+        LuaValue::Table(tls) => Some(connection::tls_config_from_table(&tls, "server.serve")?),
+        LuaNil => None,
+        other => {
+            return wrap_err!("server.serve expected ServeConfig.tls to be a table with fields cert and key or nil, got: {:#?}", other);
         }
     };
+    let connection_options = ConnectionOptions { keep_alive, limits, tls };
 
     let address_port = format!("{}:{}", address, port);
     let listener = match TcpListener::bind(&address_port) {
@@ -51,148 +185,366 @@ fn server_serve(luau: &Lua, serve_config: LuaValue) -> LuaValueResult {
             return wrap_err!("server.serve: failed to bind to {} with error: {}", address_port, err);
         }
     };
+    // we poll a nonblocking listener so server.shutdown() can stop the accept loop
+    if let Err(err) = listener.set_nonblocking(true) {
+        return wrap_err!("server.serve: unable to set listener at {} to nonblocking due to err: {}", address_port, err);
+    }
+    SHUTDOWN_REQUESTED.store(false, Ordering::Release);
 
-    for stream in listener.incoming() {
-        match stream {
-            Ok(stream) => {
-                match handle_client(stream, handler_function.clone(), luau) {
-                    Ok(_client) => {}
+    match config.raw_get("workers")? {
+        LuaValue::Table(workers) => {
+            if !config.raw_get::<LuaValue>("handler")?.is_nil() {
+                return wrap_err!("server.serve: ServeConfig.handler can't be used with ServeConfig.workers because Luau functions can't be sent across threads; \
+                    return your handler from the module at workers.path instead");
+            }
+            serve_with_workers(luau, listener, workers, connection_options)
+        },
+        LuaNil => {
+            let handler = ServeHandler::from_config(&config, "server.serve")?;
+            // with just the one accept loop, an idle keep-alive client would keep everyone else waiting
+            let connection_options = ConnectionOptions { keep_alive: None, ..connection_options };
+            accept_connections(&listener, |stream| {
+                match handle_connection(stream, &handler, luau, &connection_options) {
+                    Ok(_) => Ok(()),
                     Err(err) => {
-                        return wrap_err!("server.serve: failed to handle client: {}", err);
+                        wrap_err!("server.serve: failed to handle client: {}", err)
                     }
                 }
+            })?;
+            Ok(LuaNil)
+        },
+        other => {
+            wrap_err!("server.serve expected ServeConfig.workers to be a ServeWorkers table (with fields count and path or src) or nil, got: {:#?}", other)
+        }
+    }
+}
+
+/// calls `on_connection` for every incoming connection until `server.shutdown()` is called
+fn accept_connections<F>(listener: &TcpListener, mut on_connection: F) -> LuaEmptyResult
+where
+    F: FnMut(TcpStream) -> LuaEmptyResult,
+{
+    while !shutdown_requested() {
+        match listener.accept() {
+            Ok((stream, _address)) => {
+                // accepted streams inherit nonblocking from the listener on some platforms
+                if let Err(err) = stream.set_nonblocking(false) {
+                    eprintln!("server.serve: unable to set connection to blocking: {}", err);
+                    continue;
+                }
+                on_connection(stream)?;
+            },
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(ACCEPT_POLL_INTERVAL);
+            },
+            Err(err) => {
+                println!("Connection failed: {}", err);
             }
-            Err(e) => {
-                println!("Connection failed: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn serve_with_workers(luau: &Lua, listener: TcpListener, workers: LuaTable, connection_options: ConnectionOptions) -> LuaValueResult {
+    let function_name = "server.serve";
+    let count = match workers.raw_get("count")? {
+        LuaValue::Integer(count) if count > 0 => int_to_usize(count, function_name, "workers.count")?,
+        LuaNil => thread::available_parallelism().map(|n| n.get()).unwrap_or(4),
+        other => {
+            return wrap_err!("{} expected ServeConfig.workers.count to be a positive integer or nil (defaults to number of cpus), got: {:#?}", function_name, other);
+        }
+    };
+    let options = ThreadSpawnOptions::from_table(workers, luau, function_name)?;
+    let src = options.get_src(function_name)?;
+
+    // bounded so a flood of connections waits in the os backlog instead of piling up in memory
+    let (sender, receiver) = crossbeam_channel::bounded::<TcpStream>(count * 4);
+
+    let mut handles = Vec::with_capacity(count);
+    for index in 0..count {
+        let receiver = receiver.clone();
+        let src = src.clone();
+        let chunk_name = options.chunk_name.clone();
+        let worker_name = format!("{}-{}", options.name, index + 1);
+        let spawned_at = options.spawned_at.clone();
+        let connection_options = connection_options.clone();
+        let handle_result = thread::Builder::new().name(worker_name.clone()).spawn(move || -> LuaEmptyResult {
+            let worker_luau = Lua::default();
+            worker_luau.sandbox(true)?;
+            globals::set_globals(&worker_luau, &chunk_name)?;
+            let handler = match worker_luau
+                .load(src)
+                .set_name(&chunk_name)
+                .eval::<LuaValue>()
+                .and_then(|value| ServeHandler::from_worker_module(value, "server.serve worker"))
+            {
+                Ok(handler) => handler,
+                Err(err) => {
+                    let formatted_err = LuaError::external(format!("{}{}{}\n Error occurred in server worker '{}', which was spawned at {}",
+                        colors::RED, err, colors::RESET, worker_name, spawned_at));
+                    display_error_and_exit(formatted_err);
+                }
+            };
+            // recv errs once the accept loop drops its sender (server.shutdown()), so workers wind down after their current connection
+            while let Ok(stream) = receiver.recv() {
+                if let Err(err) = handle_connection(stream, &handler, &worker_luau, &connection_options) {
+                    // one bad request shouldn't take down the whole pool
+                    display_error(LuaError::external(format!("server.serve worker '{}' failed to handle client: {}", worker_name, err)));
+                }
+            }
+            Ok(())
+        });
+        match handle_result {
+            Ok(handle) => handles.push(handle),
+            Err(err) => {
+                return wrap_err!("{}: can't spawn server worker thread due to io error: {}", function_name, err);
             }
         }
     }
+    drop(receiver);
+
+    accept_connections(&listener, |stream| {
+        match sender.send(stream) {
+            Ok(_) => Ok(()),
+            Err(_) => {
+                wrap_err!("{}: all server workers unexpectedly exited", function_name)
+            }
+        }
+    })?;
+
+    drop(sender);
+    for handle in handles {
+        match handle.join() {
+            Ok(result) => result?,
+            Err(err) => {
+                return wrap_err!("{}: unable to join server worker thread due to err: {:?}", function_name, err);
+            }
+        }
+    }
+
+    Ok(LuaNil)
+}
+
+fn server_shutdown(_luau: &Lua, _value: LuaValue) -> LuaEmptyResult {
+    SHUTDOWN_REQUESTED.store(true, Ordering::Release);
+    Ok(())
+}
+
+/// handles requests on one connection, looping while the client keeps the connection alive
+fn handle_connection(stream: TcpStream, handler: &ServeHandler, luau: &Lua, options: &ConnectionOptions) -> LuaEmptyResult {
+    if let Some(timeout) = options.keep_alive
+        && let Err(err) = stream.set_read_timeout(Some(timeout))
+    {
+        return wrap_err!("unable to set keep-alive timeout on connection due to err: {}", err);
+    }
+    let mut stream = match ServeConnection::new(stream, options.tls.as_ref()) {
+        Ok(stream) => stream,
+        Err(err) => {
+            return wrap_err!("unable to set up connection due to err: {}", err);
+        }
+    };
+    let mut buf_reader = BufReader::new(stream.clone());
+    while handle_client(&mut buf_reader, &mut stream, handler, luau, options)? {
+        if shutdown_requested() {
+            break;
+        }
+    }
+    Ok(())
+}
 
-    Ok(LuaValue::Nil)
+/// responds to a malformed or oversized request; we can't trust the framing of whatever comes after it, so the connection gets closed
+fn reject_request(stream: &mut ServeConnection, status: &str, reason: &str) {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, reason.len(), reason
+    );
+    // the client might've hung up already, in which case there's nobody left to tell
+    if stream.write_all(response.as_bytes()).and_then(|_| stream.flush()).is_err() {
+        return;
+    }
+    // closing a socket with unread data sends a RST that can clobber our response before the client reads it,
+    // so we finish our side and swallow (some of) whatever the client was still sending
+    stream.shutdown_write();
+    let _ = stream.socket().set_read_timeout(Some(REJECTED_REQUEST_DRAIN_TIMEOUT));
+    let _ = io::copy(&mut stream.by_ref().take(REJECTED_REQUEST_DRAIN_LIMIT), &mut io::sink());
 }
 
-fn handle_client(mut stream: TcpStream, handler_function: LuaFunction, luau: &Lua) -> LuaValueResult {
-    let mut invalid_request = false;
+/// `ServeResponse.headers` and `.cookies` as header lines
+fn custom_headers(serve_response: &LuaTable) -> LuaResult<String> {
+    let headers: Option<LuaTable> = serve_response.raw_get("headers").ok();
+    let cookies: Option<LuaTable> = serve_response.raw_get("cookies").ok();
+
+    let mut additional_headers = String::new();
+    if let Some(headers_table) = headers {
+        for pair in headers_table.pairs::<LuaString, LuaString>().flatten() {
+            let (key, value) = pair;
+            additional_headers.push_str(&format!("{}: {}\r\n", key.to_str()?, value.to_str()?));
+        }
+    }
+    if let Some(cookies_table) = cookies {
+        for pair in cookies_table.pairs::<LuaString, LuaString>().flatten() {
+            let (key, value) = pair;
+            additional_headers.push_str(&format!("Set-Cookie: {}={}\r\n", key.to_str()?, value.to_str()?));
+        }
+    }
+    Ok(additional_headers)
+}
 
-    let peer_address = match stream.peer_addr() {
+/// the full url the client asked for, pieced together from the request target and Host header
+fn request_url(request: &ParsedRequest, secure: bool, stream: &ServeConnection) -> Option<url::Url> {
+    let target = request.target.as_str();
+    // proxies send the absolute url as the target; `OPTIONS *` doesn't have a url at all
+    if !target.starts_with('/') {
+        return url::Url::parse(target).ok();
+    }
+    let host = match request.header("host") {
+        Some(host) => host.to_string(),
+        // HTTP/1.0 clients don't have to send a Host header
+        None => stream.socket().local_addr().ok()?.to_string(),
+    };
+    let scheme = if secure { "https" } else { "http" };
+    url::Url::parse(&format!("{}://{}{}", scheme, host, target)).ok()
+}
+
+/// handles one request, returning whether the connection should be kept alive for another
+fn handle_client(
+    buf_reader: &mut BufReader<ServeConnection>,
+    stream: &mut ServeConnection,
+    handler: &ServeHandler,
+    luau: &Lua,
+    options: &ConnectionOptions,
+) -> LuaResult<bool> {
+    let peer_address = match stream.socket().peer_addr() {
         Ok(address) => address.to_string(),
         Err(err) => format!("Unknown ({})", err),
     };
 
-    let mut body: String = String::from("");
-
-    let lines: Vec<String> = {
-        let mut buf_reader = BufReader::new(&stream);
-        let mut content_length: Option<usize> = None;
-        let mut lines: Vec<String> = Vec::new();
-        
-        // handle header and break upon hitting \r\n\r\n aka time to start body
-        for header in buf_reader.by_ref().lines() {
-            match header {
-                Ok(header_line) => {
-                    if header_line.to_lowercase().contains("content-length") {
-                        if let Some(content_length_str) = header_line.split_once(':').map(|x| x.1) {
-                            if let Ok(length) = content_length_str.trim().parse::<usize>() {
-                                content_length = Some(length);
-                            } else {
-                                eprintln!("Failed to parse Content-Length");
-                            }
-                        } else {
-                            eprintln!("Content-Length header is malformed");
-                        }
-                    }
-                    // handle body here by reading exact content length and append it to lines vec
-                    if header_line.is_empty() {
-                        if let Some(length) = content_length {
-                            let mut body_buff = vec![0; length];
-                            let body_string = match buf_reader.read_exact(&mut body_buff) {
-                                Ok(_) => String::from_utf8_lossy(&body_buff).to_string(),
-                                Err(err) => err.to_string(),
-                            };
-                            body = body_string.clone();
-                            lines.push(body_string);
-                        }
-                        break;
-                    }
-                    lines.push(header_line);
-                },
-                Err(err) => {
-                    invalid_request = true;
-                    lines.push(format!("Error reading line: {}", err));
-                    break;
-                }
-            }
+    let request = match request_parser::parse_request(buf_reader, stream, &options.limits) {
+        Ok(request) => request,
+        // client closed the connection (or an idle keep-alive connection had nothing more to say)
+        Err(ParseError::Closed) => return Ok(false),
+        Err(ParseError::Rejected { status, reason }) => {
+            reject_request(stream, status, &reason);
+            return Ok(false);
         }
-        lines
     };
+    let method = request.method.as_str();
+    let path = request.target.as_str();
 
-    if invalid_request {
-        // commented all of this out because it gets garbled when returned through https
-        
-        // let response = r#"{"ok": false, "err": "invalid utf-8 request"}"#;
-        // let response_headers = format!(
-        //     "HTTP/1.1 400 Bad Request\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n",
-        //     response.len()
-        // );
-
-        // if stream.write_all(response_headers.as_bytes()).is_err() {
-        //     return Ok(LuaNil); // prevent server from crashing if pipe break (ctrl c) in mid invalid request
-        // }
-        // if stream.write_all(response.as_bytes()).is_err() {
-        //     return Ok(LuaNil);
-        // }
-        return Ok(LuaNil);
-    }  
-
-    let request_text = lines.join("\n");
-    
-    let first_line_re = Regex::new(r"^(?P<method>\w+)\s+(?P<path>\S+)\s+HTTP/1\.[01]$").unwrap();
-    let captures = match first_line_re.captures(&lines[0]) {
-        Some(captures) => captures,
-        None => return wrap_err!("server.serve: Failed to parse request line: {}", lines[0]),
+    // HTTP/1.1 connections are persistent unless the client says otherwise; HTTP/1.0 ones have to opt in
+    let keep_alive = options.keep_alive.is_some() && !shutdown_requested() && !request.must_close && {
+        let has_connection_option = |option: &str| request
+            .header("connection")
+            .is_some_and(|connection| connection.split(',').any(|o| o.trim().eq_ignore_ascii_case(option)));
+        if has_connection_option("close") {
+            false
+        } else {
+            request.http_1_1 || has_connection_option("keep-alive")
+        }
     };
 
-    let method = match captures.name("method") {
-        Some(method) => method.as_str(),
-        None => return wrap_err!("server.serve: Failed to extract HTTP method: {}", lines[0]),
-    };
-    let path = match captures.name("path") {
-        Some(path) => path.as_str(),
-        None => return wrap_err!("server.serve: Failed to extract HTTP request path: {}", lines[0]),
+    let request_text = if request.body.is_empty() {
+        request.head.clone()
+    } else {
+        format!("{}\n{}", request.head, String::from_utf8_lossy(&request.body))
     };
 
-    // Collect headers
+    // header names are stored lowercase, but can be looked up with any casing (headers["Content-Type"])
     let headers_table = luau.create_table()?;
-    let mut i = 1;
-    while i < lines.len() && !lines[i].is_empty() {
-        let mut header_line = lines[i].to_string();
-        while i + 1 < lines.len() && lines[i + 1].starts_with(' ') {
-            i += 1;
-            header_line.push_str(&format!("\r\n{}", lines[i]));
-        }
+    for (name, value) in &request.headers {
+        headers_table.raw_set(name.as_str(), value.as_str())?;
+    }
+    let headers_metatable = TableBuilder::create(luau)?
+        .with_function("__index", |_luau: &Lua, (headers, key): (LuaTable, LuaValue)| -> LuaValueResult {
+            match key {
+                LuaValue::String(key) => headers.raw_get(key.to_string_lossy().to_ascii_lowercase()),
+                _ => Ok(LuaNil),
+            }
+        })?
+        .build_readonly()?;
+    headers_table.set_metatable(Some(headers_metatable))?;
 
-        if let Some((key, value)) = header_line.split_once(": ") {
-            headers_table.raw_set(key, value)?;
-        } else {
-            // sometimes we get fails to parse header line and IDK they look like half cut off words and stuff
-            // by that time we usually got some headers so let's just break out of it
-            // if we continue instead of break we basically hit a spinlock of Failed to parse header line stuff
-            break;
-            // return wrap_err!("server.serve: Failed to parse header line: {}", header_line);
+    let (route_path, query_string) = path.split_once('?').unwrap_or((path, ""));
+    let query_table = luau.create_table()?;
+    for (key, value) in url::parse_query(query_string) {
+        query_table.raw_set(key, value)?;
+    }
+
+    // urlencoded and multipart bodies get parsed into `form` (text fields) and `files` (file parts)
+    let form_table = luau.create_table()?;
+    let files_table = luau.create_table()?;
+    let content_type = request.header("content-type").unwrap_or_default();
+    if form::is_urlencoded(content_type) {
+        for (key, value) in url::parse_query(&String::from_utf8_lossy(&request.body)) {
+            form_table.raw_set(key, value)?;
+        }
+    } else if let Some(boundary) = form::multipart_boundary(content_type) {
+        let parts = match form::parse_multipart(&request.body, &boundary) {
+            Ok(parts) => parts,
+            Err(reason) => {
+                reject_request(stream, request_parser::BAD_REQUEST, &format!("malformed multipart/form-data body: {}", reason));
+                return Ok(false);
+            }
+        };
+        for part in parts {
+            match part.filename {
+                Some(filename) => {
+                    let file = TableBuilder::create(luau)?
+                        .with_value("filename", filename)?
+                        .with_value("content_type", part.content_type.unwrap_or_else(|| String::from("application/octet-stream")))?
+                        .with_value("data", luau.create_buffer(&part.data)?)?
+                        .build_readonly()?;
+                    files_table.raw_set(part.name, file)?;
+                },
+                None => form_table.raw_set(part.name, luau.create_string(&part.data)?)?,
+            }
         }
-        i += 1;
     }
 
+    let params_table = luau.create_table()?;
+    let handler_call = match handler {
+        ServeHandler::Function(f) => HandlerCall::Call(f.clone()),
+        ServeHandler::Router(router) => match router.find(method, route_path) {
+            RouteMatch::Found { handler, params } => {
+                for (key, value) in params {
+                    params_table.raw_set(key, value)?;
+                }
+                HandlerCall::Call(handler.clone())
+            },
+            RouteMatch::MethodNotAllowed { allowed } => match &router.method_not_allowed {
+                Some(f) => HandlerCall::CallMethodNotAllowed(f.clone(), allowed),
+                None => HandlerCall::Respond(default_response(luau, "405 Method Not Allowed", Some(&allowed))?),
+            },
+            RouteMatch::NotFound => match &router.not_found {
+                Some(f) => HandlerCall::Call(f.clone()),
+                None => HandlerCall::Respond(default_response(luau, "404 Not Found", None)?),
+            },
+        },
+    };
+
     let serve_request_info = TableBuilder::create(luau)?
         .with_value("peer_address", peer_address)?
         .with_value("method", method)?
         .with_value("path", path)?
+        .with_value("url", request_url(&request, options.tls.is_some(), stream).map(|url| url.to_table(luau)).transpose()?)?
+        .with_value("query", query_table)?
+        .with_value("params", params_table)?
         .with_value("headers", headers_table)?
-        .with_value("body", body)?
-        .with_value("raw_text", request_text.clone())?
+        .with_value("body", luau.create_string(&request.body)?)?
+        .with_value("raw_body", luau.create_buffer(&request.body)?)?
+        .with_value("raw_text", request_text)?
+        .with_value("form", form_table)?
+        .with_value("files", files_table)?
+        .with_value("websocket", websocket::is_upgrade_request(&request))?
         .build_readonly()?;
 
-    let serve_response: LuaTable = match handler_function.call(serve_request_info) {
+    let handler_result = match handler_call {
+        HandlerCall::Call(f) => f.call::<LuaValue>(serve_request_info),
+        HandlerCall::CallMethodNotAllowed(f, allowed) => f.call::<LuaValue>((serve_request_info, allowed)),
+        HandlerCall::Respond(response) => Ok(LuaValue::Table(response)),
+    };
+
+    let serve_response: LuaTable = match handler_result {
         Ok(res) => match res {
             LuaValue::Table(table) => table,
             other => return wrap_err!("server.serve: handler_function should return a table, got: {:#?}", other),
@@ -200,6 +552,15 @@ fn handle_client(mut stream: TcpStream, handler_function: LuaFunction, luau: &Lu
         Err(err) => return wrap_err!("server.serve: handler_function call failed with error: {}", err),
     };
 
+    if let LuaValue::Function(on_connect) = serve_response.raw_get("websocket")? {
+        if !websocket::is_upgrade_request(&request) {
+            reject_request(stream, "426 Upgrade Required", "this endpoint only accepts WebSocket connections");
+            return Ok(false);
+        }
+        websocket::accept(luau, buf_reader, stream, &request, &custom_headers(&serve_response)?, on_connect)?;
+        return Ok(false);
+    }
+
     let status_code: String = match serve_response.raw_get("status_code") {
         Ok(status) => status,
         Err(err) => return wrap_err!("ServeResponse table missing 'status_code': {}", err),
@@ -215,8 +576,11 @@ fn handle_client(mut stream: TcpStream, handler_function: LuaFunction, luau: &Lu
                     "json" => "application/json".to_string(),
                     "xml"  => "application/xml".to_string(),
                     "css"  => "text/css".to_string(),
+                    "javascript" => "text/javascript; charset=utf-8".to_string(),
                     "binary" => "application/octet-stream".to_string(),
-                    other => other.to_string()
+                    "event-stream" | "sse" => "text/event-stream".to_string(),
+                    // file extensions like "png" or "js" work too
+                    other => static_files::mime_type(other).unwrap_or(other).to_string()
                 }
             } else {
                 return wrap_err!("ServeResponse expected content_type to be a string, got: {:#?}", content_type);
@@ -225,22 +589,17 @@ fn handle_client(mut stream: TcpStream, handler_function: LuaFunction, luau: &Lu
         Err(err) => return wrap_err!("ServeResponse table missing 'content_type': {}", err),
     };
 
-    let mut buffer_body: Option<Vec<u8>> = None;
-    let body: String = match serve_response.raw_get("body") {
-        Ok(LuaValue::String(body)) => body.to_string_lossy(),
-        Ok(LuaValue::Buffer(buff)) => {
-            let bytes = buff.to_vec();
-            buffer_body = Some(bytes);
-            String::from("")
-        },
+    // luau strings can hold arbitrary bytes, so we don't go through utf-8 for either kind of body
+    let body = match serve_response.raw_get("body") {
+        Ok(LuaValue::String(body)) => ResponseBody::Bytes(body.as_bytes().to_vec()),
+        Ok(LuaValue::Buffer(buff)) => ResponseBody::Bytes(buff.to_vec()),
+        Ok(LuaValue::Function(f)) => ResponseBody::Stream(f),
         Ok(other) => {
-            return wrap_err!("Expected body to be a string (or buffer), got: {:#?}", other);
+            return wrap_err!("Expected body to be a string, buffer, or function (to stream the response), got: {:#?}", other);
         }
         Err(err) => return wrap_err!("ServeResponse table missing 'body': {}", err),
     };
 
-    let headers: Option<LuaTable> = serve_response.raw_get("headers").ok();
-    let cookies: Option<LuaTable> = serve_response.raw_get("cookies").ok();
     let http_version: Option<String> = serve_response.raw_get("http_version").ok();
     let reason_phrase: Option<String> = serve_response.raw_get("reason_phrase").ok();
     let redirect_url: Option<String> = serve_response.raw_get("redirect_url").ok();
@@ -248,54 +607,55 @@ fn handle_client(mut stream: TcpStream, handler_function: LuaFunction, luau: &Lu
     let http_version = http_version.unwrap_or_else(|| "HTTP/1.1".to_string());
     let reason_phrase = reason_phrase.unwrap_or_else(|| "OK".to_string());
 
-    // Construct headers and cookies
-    let mut additional_headers = String::new();
-    if let Some(headers_table) = headers {
-        for pair in headers_table.pairs::<LuaString, LuaString>().flatten() {
-            let (key, value) = pair;
-            additional_headers.push_str(&format!("{}: {}\r\n", key.to_str()?, value.to_str()?));
-        }
-    }
-    if let Some(cookies_table) = cookies {
-        for pair in cookies_table.pairs::<LuaString, LuaString>().flatten() {
-            let (key, value) = pair;
-            additional_headers.push_str(&format!("Set-Cookie: {}={}\r\n", key.to_str()?, value.to_str()?));
-        }
-    }
+    let mut additional_headers = custom_headers(&serve_response)?;
     if let Some(url) = redirect_url {
         additional_headers.push_str(&format!("Location: {}\r\n", url));
     }
 
+    let sse = content_type.starts_with("text/event-stream");
+    if sse {
+        // keep proxies from caching or buffering the event stream
+        additional_headers.push_str("Cache-Control: no-cache\r\n");
+    }
+
+    // HTTP/1.0 clients can't read chunked responses, so a streamed response to them ends when we close the connection
+    let keep_alive = keep_alive && (request.http_1_1 || matches!(body, ResponseBody::Bytes(_)));
+    additional_headers.push_str(if keep_alive { "Connection: keep-alive\r\n" } else { "Connection: close\r\n" });
+
+    let framing_header = match &body {
+        ResponseBody::Bytes(bytes) => format!("Content-Length: {}\r\n", bytes.len()),
+        ResponseBody::Stream(_) if request.http_1_1 => String::from("Transfer-Encoding: chunked\r\n"),
+        ResponseBody::Stream(_) => String::new(),
+    };
+
     // Respond with the specified content
-    let response = format!("{} {} {}\r\nContent-Type: {}\r\n{}Content-Length: {}\r\n\r\n{}", 
-        http_version, status_code, reason_phrase, content_type, additional_headers, body.len(), body);
-    
-    // now we actually send and write to stream
-    // if someone passed in body = somebuffer then we have to write the buffer to the stream separately
-    match buffer_body {
-        Some(bytes) => {
-            match stream.write_all(response.as_bytes()).and_then(|_| stream.write_all(&bytes) ) {
-                Ok(_) => match stream.flush() {
-                    Ok(_) => Ok(LuaValue::Nil),
-                    Err(err) => wrap_err!("Failed to flush stream: {}", err),
-                },
-                Err(err) => wrap_err!("Failed to write response: {}", err),
-            }
-        }
-        None => {
-            match stream.write_all(response.as_bytes()) {
-                Ok(_) => match stream.flush() {
-                    Ok(_) => Ok(LuaValue::Nil),
-                    Err(err) => wrap_err!("Failed to flush stream: {}", err),
-                },
-                Err(err) => wrap_err!("Failed to write response: {}", err),
-            }
-        }
+    let response_head = format!("{} {} {}\r\nContent-Type: {}\r\n{}{}\r\n",
+        http_version, status_code, reason_phrase, content_type, additional_headers, framing_header);
+
+    // responses to HEAD requests carry the headers a GET would've gotten, but never a body
+    let head_only = method.eq_ignore_ascii_case("HEAD");
+    let write_result = match &body {
+        ResponseBody::Bytes(bytes) if !head_only => stream.write_all(response_head.as_bytes()).and_then(|_| stream.write_all(bytes)),
+        _ => stream.write_all(response_head.as_bytes()),
+    };
+    if let Err(err) = write_result.and_then(|_| stream.flush()) {
+        return wrap_err!("Failed to write response: {}", err);
+    }
+
+    match body {
+        ResponseBody::Stream(f) if !head_only => {
+            let still_connected = response_stream::stream_body(luau, stream, f, request.http_1_1, sse)?;
+            Ok(keep_alive && still_connected)
+        },
+        _ => Ok(keep_alive),
     }
 }
 
 pub fn create(luau: &Lua) -> LuaResult<LuaTable> {
     TableBuilder::create(luau)?
         .with_function("serve", server_serve)?
+        .with_function("shutdown", server_shutdown)?
+        .with_function("static", static_files::server_static)?
+        .with_function("self_signed_cert", connection::server_self_signed_cert)?
         .build_readonly()
 }
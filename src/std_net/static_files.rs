@@ -0,0 +1,431 @@
+//! `server.static`: a `ServeHandler` that serves files out of a directory.
+//! Handles MIME types, conditional requests (ETag/Last-Modified), byte ranges, index files, and gzip.
+
+use mluau::prelude::*;
+use crate::prelude::*;
+use super::url;
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use jiff::Timestamp;
+use jiff::fmt::rfc2822::{DateTimeParser, DateTimePrinter};
+
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// compressing tiny files isn't worth the cpu or the gzip header
+const MIN_GZIP_SIZE: u64 = 1024;
+
+/// MIME types for common file extensions; also used for `ServeResponse.content_type` shorthands like `"png"`
+pub fn mime_type(extension: &str) -> Option<&'static str> {
+    Some(match extension.to_ascii_lowercase().as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" | "map" => "application/json",
+        "xml" => "application/xml",
+        "txt" | "text" => "text/plain; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "luau" | "lua" => "text/plain; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "mp3" => "audio/mpeg",
+        "ogg" => "audio/ogg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => return None,
+    })
+}
+
+fn is_compressible(mime: &str) -> bool {
+    mime.starts_with("text/")
+        || mime.contains("json")
+        || mime.contains("javascript")
+        || mime.contains("xml")
+        || mime == "application/wasm"
+}
+
+struct StaticOptions {
+    /// canonicalized so symlinks can't lead requests outside of it
+    root: PathBuf,
+    /// stripped from request paths before looking up files (`"/docs"` serves `/docs/intro.html` from `<root>/intro.html`)
+    prefix: String,
+    index: Vec<String>,
+    gzip: bool,
+    /// `Cache-Control: max-age` in seconds
+    max_age: Option<u64>,
+    /// serve files and directories starting with `.`
+    dotfiles: bool,
+}
+
+impl StaticOptions {
+    fn from_value(dir: String, value: LuaValue, function_name: &'static str) -> LuaResult<Self> {
+        let root = match fs::canonicalize(&dir) {
+            Ok(root) if root.is_dir() => root,
+            Ok(_) => {
+                return wrap_err!("{}: '{}' isn't a directory", function_name, dir);
+            },
+            Err(err) => {
+                return wrap_err!("{}: unable to serve directory '{}' due to err: {}", function_name, dir, err);
+            }
+        };
+        let mut options = Self {
+            root,
+            prefix: String::new(),
+            index: vec![String::from("index.html")],
+            gzip: true,
+            max_age: None,
+            dotfiles: false,
+        };
+        let config = match value {
+            LuaValue::Table(config) => config,
+            LuaNil => return Ok(options),
+            other => {
+                return wrap_err!("{} expected options to be a StaticOptions table or nil, got: {:?}", function_name, other);
+            }
+        };
+
+        match config.raw_get("prefix")? {
+            LuaValue::String(prefix) => options.prefix = prefix.to_string_lossy().trim_end_matches('/').to_string(),
+            LuaNil => {},
+            other => {
+                return wrap_err!("{} expected StaticOptions.prefix to be a string or nil, got: {:?}", function_name, other);
+            }
+        }
+        match config.raw_get("index")? {
+            LuaValue::String(index) => options.index = vec![index.to_string_lossy()],
+            LuaValue::Table(index) => options.index = index.sequence_values::<String>().collect::<LuaResult<Vec<String>>>()?,
+            // `index = false` turns index files off
+            LuaValue::Boolean(false) => options.index = Vec::new(),
+            LuaNil => {},
+            other => {
+                return wrap_err!("{} expected StaticOptions.index to be a string, array of strings, false, or nil, got: {:?}", function_name, other);
+            }
+        }
+        match config.raw_get("gzip")? {
+            LuaValue::Boolean(gzip) => options.gzip = gzip,
+            LuaNil => {},
+            other => {
+                return wrap_err!("{} expected StaticOptions.gzip to be a boolean or nil, got: {:?}", function_name, other);
+            }
+        }
+        match config.raw_get("max_age")? {
+            LuaValue::Integer(max_age) => options.max_age = Some(int_to_u64(max_age, function_name, "max_age")?),
+            LuaValue::Number(max_age) if max_age >= 0.0 => options.max_age = Some(max_age as u64),
+            LuaNil => {},
+            other => {
+                return wrap_err!("{} expected StaticOptions.max_age (in seconds) to be a positive number or nil, got: {:?}", function_name, other);
+            }
+        }
+        match config.raw_get("dotfiles")? {
+            LuaValue::Boolean(dotfiles) => options.dotfiles = dotfiles,
+            LuaNil => {},
+            other => {
+                return wrap_err!("{} expected StaticOptions.dotfiles to be a boolean or nil, got: {:?}", function_name, other);
+            }
+        }
+        Ok(options)
+    }
+
+    /// maps a (percent-decoded) request path to a path under the root, or `None` if it tries to escape it
+    fn resolve(&self, request_path: &str) -> Option<PathBuf> {
+        if request_path.contains('\0') {
+            return None;
+        }
+        let mut path = self.root.clone();
+        for component in request_path.split(['/', '\\']) {
+            match component {
+                "" | "." => {},
+                ".." => return None,
+                hidden if !self.dotfiles && hidden.starts_with('.') => return None,
+                component => path.push(component),
+            }
+        }
+        // a symlink inside the root could still point outside of it
+        match fs::canonicalize(&path) {
+            Ok(canonical) if canonical.starts_with(&self.root) => Some(canonical),
+            _ => None,
+        }
+    }
+}
+
+fn get_header(headers: &LuaTable, name: &str) -> LuaResult<Option<String>> {
+    match headers.raw_get(name)? {
+        LuaValue::String(value) => Ok(Some(value.to_string_lossy())),
+        _ => Ok(None),
+    }
+}
+
+/// whether `Accept-Encoding` allows gzip (and doesn't opt out of it with `q=0`)
+fn accepts_gzip(accept_encoding: &str) -> bool {
+    accept_encoding.split(',').any(|coding| {
+        let mut params = coding.split(';');
+        let name = params.next().unwrap_or_default().trim();
+        let refused = params.any(|param| {
+            param.trim().strip_prefix("q=").is_some_and(|q| q.trim().parse::<f64>().is_ok_and(|q| q == 0.0))
+        });
+        (name.eq_ignore_ascii_case("gzip") || name == "*") && !refused
+    })
+}
+
+/// whether an `If-None-Match` or `If-Range` header matches our ETag (gzipped responses get a suffixed version of the same tag)
+fn etag_matches(header: &str, etag: &str) -> bool {
+    header.split(',').map(str::trim).any(|tag| {
+        let tag = tag.strip_prefix("W/").unwrap_or(tag);
+        tag == "*" || tag == etag || tag.strip_suffix("-gzip\"").is_some_and(|tag| format!("{}\"", tag) == etag)
+    })
+}
+
+enum ByteRange {
+    /// inclusive start and end
+    Partial(u64, u64),
+    Unsatisfiable,
+    /// no range, multiple ranges, or a header we don't understand; any of these get the whole file
+    Full,
+}
+
+fn parse_range(header: &str, len: u64) -> ByteRange {
+    let Some(spec) = header.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.split_once('-') else {
+        return ByteRange::Full;
+    };
+    let (start, end) = (start.trim(), end.trim());
+    let range = match (start.parse::<u64>(), end.parse::<u64>()) {
+        // bytes=-500 is the last 500 bytes
+        (Err(_), Ok(suffix)) if start.is_empty() => {
+            if suffix == 0 {
+                return ByteRange::Unsatisfiable;
+            }
+            (len.saturating_sub(suffix), len.saturating_sub(1))
+        },
+        (Ok(start), Err(_)) if end.is_empty() => (start, len.saturating_sub(1)),
+        (Ok(start), Ok(end)) if start <= end => (start, end.min(len.saturating_sub(1))),
+        _ => return ByteRange::Full,
+    };
+    if range.0 >= len {
+        ByteRange::Unsatisfiable
+    } else {
+        ByteRange::Partial(range.0, range.1)
+    }
+}
+
+fn read_range(path: &Path, start: u64, end: u64) -> std::io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut bytes = Vec::with_capacity((end - start + 1) as usize);
+    file.take(end - start + 1).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn gzip(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes)?;
+    encoder.finish()
+}
+
+fn simple_response(luau: &Lua, status_code: &str, headers: LuaTable) -> LuaValueResult {
+    TableBuilder::create(luau)?
+        .with_value("status_code", status_code)?
+        .with_value("content_type", "text")?
+        .with_value("headers", headers)?
+        .with_value("body", if status_code.starts_with("304") { "" } else { status_code })?
+        .build()
+        .map(LuaValue::Table)
+}
+
+fn serve_file(luau: &Lua, options: &StaticOptions, request: LuaTable) -> LuaValueResult {
+    let function_name = "server.static handler";
+    let headers_table = luau.create_table()?;
+
+    let method: String = request.raw_get("method")?;
+    if !method.eq_ignore_ascii_case("GET") && !method.eq_ignore_ascii_case("HEAD") {
+        headers_table.raw_set("Allow", "GET, HEAD")?;
+        return simple_response(luau, "405 Method Not Allowed", headers_table);
+    }
+
+    let full_path: String = request.raw_get("path")?;
+    let url_path = full_path.split_once('?').map(|(path, _)| path).unwrap_or(&full_path);
+    // when mounted on a route like "GET /docs/*", the wildcard's already the path we want
+    let wildcard: Option<String> = match request.raw_get("params")? {
+        LuaValue::Table(params) => params.raw_get("*")?,
+        _ => None,
+    };
+    let request_path = match wildcard {
+        Some(wildcard) => wildcard,
+        None => match url_path.strip_prefix(options.prefix.as_str()) {
+            Some(rest) if options.prefix.is_empty() || rest.is_empty() || rest.starts_with('/') => url::percent_decode(rest),
+            _ => return simple_response(luau, "404 Not Found", headers_table),
+        },
+    };
+
+    let Some(mut file_path) = options.resolve(&request_path) else {
+        return simple_response(luau, "404 Not Found", headers_table);
+    };
+
+    if file_path.is_dir() {
+        // relative links in an index page only work if the url ends in a slash
+        if !url_path.ends_with('/') {
+            let location = match full_path.split_once('?') {
+                Some((path, query)) => format!("{}/?{}", path, query),
+                None => format!("{}/", full_path),
+            };
+            headers_table.raw_set("Location", location)?;
+            return simple_response(luau, "301 Moved Permanently", headers_table);
+        }
+        match options.index.iter().map(|index| file_path.join(index)).find(|index| index.is_file()) {
+            Some(index) => file_path = index,
+            None => return simple_response(luau, "404 Not Found", headers_table),
+        }
+    }
+
+    let metadata = match fs::metadata(&file_path) {
+        Ok(metadata) if metadata.is_file() => metadata,
+        _ => return simple_response(luau, "404 Not Found", headers_table),
+    };
+    let len = metadata.len();
+    let modified = metadata.modified().ok().and_then(|modified| Timestamp::try_from(modified).ok());
+
+    let content_type = file_path
+        .extension()
+        .and_then(|extension| mime_type(&extension.to_string_lossy()))
+        .unwrap_or("application/octet-stream");
+    let etag = format!("\"{:x}-{:x}\"", len, modified.map(|modified| modified.as_nanosecond()).unwrap_or_default());
+    let last_modified = modified.and_then(|modified| DateTimePrinter::new().timestamp_to_rfc9110_string(&modified).ok());
+
+    headers_table.raw_set("Accept-Ranges", "bytes")?;
+    if let Some(last_modified) = &last_modified {
+        headers_table.raw_set("Last-Modified", last_modified.as_str())?;
+    }
+    if let Some(max_age) = options.max_age {
+        headers_table.raw_set("Cache-Control", format!("public, max-age={}", max_age))?;
+    }
+    let compressible = options.gzip && is_compressible(content_type);
+    if compressible {
+        headers_table.raw_set("Vary", "Accept-Encoding")?;
+    }
+
+    let request_headers: LuaTable = request.raw_get("headers")?;
+
+    // conditional requests: If-None-Match wins over If-Modified-Since when both are sent
+    let not_modified = match get_header(&request_headers, "if-none-match")? {
+        Some(if_none_match) => etag_matches(&if_none_match, &etag),
+        None => match (get_header(&request_headers, "if-modified-since")?, modified) {
+            (Some(since), Some(modified)) => DateTimeParser::new()
+                .parse_timestamp(since.as_str())
+                .is_ok_and(|since| modified.as_second() <= since.as_second()),
+            _ => false,
+        },
+    };
+    if not_modified {
+        headers_table.raw_set("ETag", etag)?;
+        return simple_response(luau, "304 Not Modified", headers_table);
+    }
+
+    // If-Range means "only send a range if the file hasn't changed since I got the first part"
+    let range_allowed = match get_header(&request_headers, "if-range")? {
+        Some(if_range) if if_range.trim_start().starts_with('"') || if_range.trim_start().starts_with("W/") => etag_matches(&if_range, &etag),
+        Some(if_range) => last_modified.as_deref() == Some(if_range.trim()),
+        None => true,
+    };
+    let range = match get_header(&request_headers, "range")? {
+        Some(range) if range_allowed => parse_range(&range, len),
+        _ => ByteRange::Full,
+    };
+
+    let (status_code, body) = match range {
+        ByteRange::Unsatisfiable => {
+            headers_table.raw_set("Content-Range", format!("bytes */{}", len))?;
+            return simple_response(luau, "416 Range Not Satisfiable", headers_table);
+        },
+        // ranges are byte offsets into the file as-is, so partial responses are never gzipped
+        ByteRange::Partial(start, end) => {
+            headers_table.raw_set("ETag", etag)?;
+            headers_table.raw_set("Content-Range", format!("bytes {}-{}/{}", start, end, len))?;
+            match read_range(&file_path, start, end) {
+                Ok(bytes) => ("206 Partial Content", bytes),
+                Err(err) => {
+                    return wrap_err!("{}: unable to read '{}' due to err: {}", function_name, file_path.display(), err);
+                }
+            }
+        },
+        ByteRange::Full => {
+            let wants_gzip = compressible
+                && len >= MIN_GZIP_SIZE
+                && get_header(&request_headers, "accept-encoding")?.is_some_and(|accept_encoding| accepts_gzip(&accept_encoding));
+            // a precompressed `file.gz` next to the file saves us compressing it on every request
+            let precompressed = PathBuf::from(format!("{}.gz", file_path.display()));
+            let body = if wants_gzip && precompressed.is_file() {
+                fs::read(&precompressed).map(Some)
+            } else if wants_gzip {
+                fs::read(&file_path).and_then(|bytes| gzip(&bytes)).map(Some)
+            } else {
+                Ok(None)
+            };
+            let body = match body {
+                Ok(Some(gzipped)) => {
+                    headers_table.raw_set("Content-Encoding", "gzip")?;
+                    headers_table.raw_set("ETag", format!("{}-gzip\"", etag.trim_end_matches('"')))?;
+                    gzipped
+                },
+                Ok(None) => {
+                    headers_table.raw_set("ETag", etag)?;
+                    match fs::read(&file_path) {
+                        Ok(bytes) => bytes,
+                        Err(err) => {
+                            return wrap_err!("{}: unable to read '{}' due to err: {}", function_name, file_path.display(), err);
+                        }
+                    }
+                },
+                Err(err) => {
+                    return wrap_err!("{}: unable to read '{}' due to err: {}", function_name, file_path.display(), err);
+                }
+            };
+            ("200 OK", body)
+        },
+    };
+
+    TableBuilder::create(luau)?
+        .with_value("status_code", status_code)?
+        .with_value("content_type", content_type)?
+        .with_value("headers", headers_table)?
+        .with_value("body", luau.create_buffer(body)?)?
+        .build()
+        .map(LuaValue::Table)
+}
+
+pub fn server_static(luau: &Lua, (dir, options): (LuaValue, LuaValue)) -> LuaResult<LuaFunction> {
+    let function_name = "server.static(dir: string, options: StaticOptions?)";
+    let dir = match dir {
+        LuaValue::String(dir) => dir.to_string_lossy(),
+        other => {
+            return wrap_err!("{} expected dir to be a string, got: {:?}", function_name, other);
+        }
+    };
+    let options = Rc::new(StaticOptions::from_value(dir, options, function_name)?);
+    luau.create_function(move | luau: &Lua, request: LuaTable | -> LuaValueResult {
+        serve_file(luau, &options, request)
+    })
+}
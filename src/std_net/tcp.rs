@@ -0,0 +1,649 @@
+//! Raw TCP sockets: `net.tcp.connect` and `net.tcp.listen`.
+//! Reads follow the same conventions as `ChildProcess.stdout` (`read`, `read_exact`, `read_to`, `fill`, `lines`), except
+//! they read straight off the socket instead of from a reader thread.
+
+use mluau::prelude::*;
+use crate::prelude::*;
+
+use std::cell::RefCell;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// how many bytes `TcpSocket:read()` returns at most if the user doesn't pass a count, and how much we read off the socket at a time
+const DEFAULT_READ_SIZE: usize = 8192;
+
+/// `timeout: number?`: `nil` blocks, `0` doesn't block at all, anything else is seconds
+pub fn pop_timeout(multivalue: &mut LuaMultiValue, function_name: &'static str) -> LuaResult<Option<Duration>> {
+    let f = match multivalue.pop_front() {
+        Some(LuaValue::Number(f)) => f,
+        Some(LuaValue::Integer(i)) => i as f64,
+        Some(LuaNil) | None => {
+            return Ok(None);
+        },
+        Some(other) => {
+            return wrap_err!("{} expected timeout to be a number or nil, got: {:?}", function_name, other);
+        }
+    };
+
+    if f.is_nan() || f.is_infinite() {
+        wrap_err!("{}: timeout can't be NaN nor infinite!", function_name)
+    } else if f < 0.0 {
+        wrap_err!("{}: timeout can't be negative! got: {:?}", function_name, f)
+    } else {
+        match Duration::try_from_secs_f64(f) {
+            Ok(duration) => Ok(Some(duration)),
+            Err(err) => wrap_err!("{}: error creating Duration from timeout: {}", function_name, err),
+        }
+    }
+}
+
+/// data to send, either a string or a buffer
+pub fn pop_data(multivalue: &mut LuaMultiValue, function_name: &'static str) -> LuaResult<Vec<u8>> {
+    match multivalue.pop_front() {
+        Some(LuaValue::String(data)) => Ok(data.as_bytes().to_vec()),
+        Some(LuaValue::Buffer(buffy)) => Ok(buffy.to_vec()),
+        Some(other) => {
+            wrap_err!("{} expected data to be a string or buffer, got: {:?}", function_name, other)
+        },
+        None => {
+            wrap_err!("{} called without required argument 'data'", function_name)
+        }
+    }
+}
+
+pub fn pop_host_port(multivalue: &mut LuaMultiValue, function_name: &'static str, port_required: bool) -> LuaResult<(String, u16)> {
+    let host = match multivalue.pop_front() {
+        Some(LuaValue::String(host)) => host.to_string_lossy(),
+        Some(other) => {
+            return wrap_err!("{} expected host to be a string (hostname or ip address), got: {:?}", function_name, other);
+        },
+        None => {
+            return wrap_err!("{} called without required argument 'host'", function_name);
+        }
+    };
+    let port = match multivalue.pop_front() {
+        Some(LuaValue::Integer(port)) => port,
+        Some(LuaValue::Number(port)) if port.fract() == 0.0 => port as i64,
+        Some(LuaNil) | None if !port_required => 0,
+        Some(LuaNil) | None => {
+            return wrap_err!("{} called without required argument 'port'", function_name);
+        },
+        Some(other) => {
+            return wrap_err!("{} expected port to be an integer, got: {:?}", function_name, other);
+        }
+    };
+    match u16::try_from(port) {
+        Ok(port) => Ok((host, port)),
+        Err(_) => wrap_err!("{}: port {} is out of range (0-65535)", function_name, port),
+    }
+}
+
+pub fn resolve(host: &str, port: u16, function_name: &'static str) -> LuaResult<Vec<SocketAddr>> {
+    match (host, port).to_socket_addrs() {
+        Ok(addresses) => {
+            let addresses: Vec<SocketAddr> = addresses.collect();
+            if addresses.is_empty() {
+                wrap_err!("{}: host '{}' didn't resolve to any addresses", function_name, host)
+            } else {
+                Ok(addresses)
+            }
+        },
+        Err(err) => {
+            wrap_err!("{}: unable to resolve host '{}' due to err: {}", function_name, host, err)
+        }
+    }
+}
+
+pub fn connect_tcp(host: &str, port: u16, timeout: Option<Duration>, function_name: &'static str) -> LuaResult<TcpStream> {
+    let mut last_err: Option<std::io::Error> = None;
+    for address in resolve(host, port, function_name)? {
+        let result = match timeout {
+            Some(timeout) => TcpStream::connect_timeout(&address, timeout),
+            None => TcpStream::connect(address),
+        };
+        match result {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_err = Some(err),
+        }
+    }
+    match last_err {
+        Some(err) => wrap_err!("{}: unable to connect to {}:{} due to err: {}", function_name, host, port, err),
+        None => wrap_err!("{}: host '{}' didn't resolve to any addresses", function_name, host),
+    }
+}
+
+/// `{ host: string, port: number }`
+pub fn address_table(luau: &Lua, address: SocketAddr) -> LuaResult<LuaTable> {
+    TableBuilder::create(luau)?
+        .with_value("host", address.ip().to_string())?
+        .with_value("port", address.port())?
+        .build_readonly()
+}
+
+/// how long a read can block for, from the `timeout` the user passed
+pub enum ReadTimeout {
+    Block,
+    Nonblocking,
+    Until(Instant),
+}
+
+impl ReadTimeout {
+    pub fn new(timeout: Option<Duration>) -> Self {
+        match timeout {
+            None => Self::Block,
+            Some(timeout) if timeout.is_zero() => Self::Nonblocking,
+            Some(timeout) => match Instant::now().checked_add(timeout) {
+                Some(until) => Self::Until(until),
+                // timeouts too far off to represent are as good as none
+                None => Self::Block,
+            },
+        }
+    }
+
+    /// `(nonblocking, read_timeout)` for the socket, or `None` if we're already out of time
+    pub fn socket_options(&self) -> Option<(bool, Option<Duration>)> {
+        match self {
+            Self::Block => Some((false, None)),
+            Self::Nonblocking => Some((true, None)),
+            Self::Until(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    None
+                } else {
+                    Some((false, Some(remaining)))
+                }
+            }
+        }
+    }
+}
+
+pub struct TcpSocket {
+    /// `None` once the socket's been closed
+    stream: Option<TcpStream>,
+    /// bytes we've read off the socket but haven't handed to the user yet (`read_exact` and `read_to` read ahead)
+    pending: Vec<u8>,
+    /// the other end closed its half of the connection
+    eof: bool,
+}
+
+type SocketCell = Rc<RefCell<TcpSocket>>;
+
+impl TcpSocket {
+    fn new(stream: TcpStream) -> Self {
+        Self {
+            stream: Some(stream),
+            pending: Vec::new(),
+            eof: false,
+        }
+    }
+
+    fn stream(&self, function_name: &'static str) -> LuaResult<&TcpStream> {
+        match &self.stream {
+            Some(stream) => Ok(stream),
+            None => wrap_err!("{}: socket is already closed", function_name),
+        }
+    }
+
+    /// reads whatever's available off the socket into `pending`; returns `false` if nothing came in before the timeout or the other end hung up
+    fn receive(&mut self, timeout: &ReadTimeout, function_name: &'static str) -> LuaResult<bool> {
+        if self.eof {
+            return Ok(false);
+        }
+        let Some((nonblocking, read_timeout)) = timeout.socket_options() else {
+            return Ok(false);
+        };
+        let mut stream = self.stream(function_name)?;
+        if let Err(err) = stream.set_nonblocking(nonblocking) {
+            return wrap_err!("{}: unable to set socket blocking mode due to err: {}", function_name, err);
+        }
+        if let Err(err) = stream.set_read_timeout(read_timeout) {
+            return wrap_err!("{}: unable to set socket read timeout due to err: {}", function_name, err);
+        }
+
+        let mut chunk = [0_u8; DEFAULT_READ_SIZE];
+        let result = loop {
+            match stream.read(&mut chunk) {
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                result => break result,
+            }
+        };
+        match result {
+            Ok(0) => {
+                self.eof = true;
+                Ok(false)
+            },
+            Ok(n) => {
+                self.pending.extend_from_slice(&chunk[..n]);
+                Ok(true)
+            },
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => Ok(false),
+            Err(err) if matches!(err.kind(), ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted | ErrorKind::BrokenPipe) => {
+                self.eof = true;
+                Ok(false)
+            },
+            Err(err) => {
+                wrap_err!("{}: unable to read from socket due to err: {}", function_name, err)
+            }
+        }
+    }
+
+    /// up to `count` bytes; waits for at least one byte to come in if nothing's pending
+    pub fn read(&mut self, luau: &Lua, mut multivalue: LuaMultiValue) -> LuaValueResult {
+        let function_name = "TcpSocket:read(count: number?, timeout: number?)";
+        pop_self(&mut multivalue, function_name)?;
+        self.stream(function_name)?;
+
+        let count = match multivalue.pop_front() {
+            Some(LuaValue::Integer(count)) => int_to_usize(count, function_name, "count")?,
+            Some(LuaValue::Number(f)) => float_to_usize(f, function_name, "count")?,
+            Some(LuaNil) | None => DEFAULT_READ_SIZE,
+            Some(other) => {
+                return wrap_err!("{} expected count to be a number or nil, got: {:?}", function_name, other);
+            }
+        };
+        if count == 0 {
+            return wrap_err!("{}: count must be greater than 0", function_name);
+        }
+        let timeout = ReadTimeout::new(pop_timeout(&mut multivalue, function_name)?);
+
+        if self.pending.is_empty() && !self.receive(&timeout, function_name)? {
+            return Ok(LuaNil);
+        }
+        let count = count.min(self.pending.len());
+        let bytes: Vec<u8> = self.pending.drain(..count).collect();
+        ok_string(&bytes, luau)
+    }
+
+    /// waits for a full `count` bytes; anything read before the timeout stays around for the next read
+    pub fn read_exact(&mut self, luau: &Lua, mut multivalue: LuaMultiValue) -> LuaValueResult {
+        let function_name = "TcpSocket:read_exact(count: number, timeout: number?)";
+        pop_self(&mut multivalue, function_name)?;
+        self.stream(function_name)?;
+
+        let count = match multivalue.pop_front() {
+            Some(LuaValue::Integer(count)) => int_to_usize(count, function_name, "count")?,
+            Some(LuaValue::Number(f)) => float_to_usize(f, function_name, "count")?,
+            Some(other) => {
+                return wrap_err!("{} expected count to be a number, got: {:?}", function_name, other);
+            },
+            None => {
+                return wrap_err!("{} called without required argument 'count'", function_name);
+            }
+        };
+        let timeout = ReadTimeout::new(pop_timeout(&mut multivalue, function_name)?);
+
+        while self.pending.len() < count {
+            if !self.receive(&timeout, function_name)? {
+                return Ok(LuaNil);
+            }
+        }
+        let bytes: Vec<u8> = self.pending.drain(..count).collect();
+        ok_string(&bytes, luau)
+    }
+
+    /// reads until `term` shows up; with `allow_partial`, whatever's pending gets returned on timeout or when the other end hangs up
+    pub fn read_to(&mut self, luau: &Lua, mut multivalue: LuaMultiValue) -> LuaValueResult {
+        let function_name = "TcpSocket:read_to(term: string, inclusive: boolean?, timeout: number?, allow_partial: boolean?)";
+        pop_self(&mut multivalue, function_name)?;
+        self.stream(function_name)?;
+
+        let term = match multivalue.pop_front() {
+            Some(LuaValue::String(term)) => term.as_bytes().to_vec(),
+            Some(other) => {
+                return wrap_err!("{} expected term to be a string, got: {:?}", function_name, other);
+            },
+            None => {
+                return wrap_err!("{} called without required argument 'term'", function_name);
+            }
+        };
+        if term.is_empty() {
+            return wrap_err!("{}: term can't be an empty string", function_name);
+        }
+        let inclusive = match multivalue.pop_front() {
+            Some(LuaValue::Boolean(inclusive)) => inclusive,
+            Some(LuaNil) | None => false,
+            Some(other) => {
+                return wrap_err!("{} expected inclusive to be a boolean or nil (default false), got: {:?}", function_name, other);
+            }
+        };
+        let timeout = ReadTimeout::new(pop_timeout(&mut multivalue, function_name)?);
+        let allow_partial = match multivalue.pop_front() {
+            Some(LuaValue::Boolean(partial)) => partial,
+            Some(LuaNil) | None => false,
+            Some(other) => {
+                return wrap_err!("{} expected allow_partial to be a boolean or nil (default false), got: {:?}", function_name, other);
+            }
+        };
+
+        // no need to rescan bytes we've already looked at
+        let mut searched = 0;
+        loop {
+            if let Some(position) = self.pending[searched..].windows(term.len()).position(|window| window == term) {
+                let end = searched + position + term.len();
+                let mut bytes: Vec<u8> = self.pending.drain(..end).collect();
+                if !inclusive {
+                    bytes.truncate(bytes.len() - term.len());
+                }
+                return ok_string(&bytes, luau);
+            }
+            searched = self.pending.len().saturating_sub(term.len() - 1);
+            if !self.receive(&timeout, function_name)? {
+                if allow_partial && !self.pending.is_empty() {
+                    let bytes: Vec<u8> = self.pending.drain(..).collect();
+                    return ok_string(&bytes, luau);
+                }
+                return Ok(LuaNil);
+            }
+        }
+    }
+
+    /// reads as many bytes as are available (and fit) into `target`; returns how many, 0 on timeout or if the other end hung up
+    pub fn fill(&mut self, _luau: &Lua, mut multivalue: LuaMultiValue) -> LuaValueResult {
+        let function_name = "TcpSocket:fill(target: buffer, target_offset: number?, timeout: number?)";
+        pop_self(&mut multivalue, function_name)?;
+        self.stream(function_name)?;
+
+        let buffy = match multivalue.pop_front() {
+            Some(LuaValue::Buffer(buffy)) => buffy,
+            Some(other) => {
+                return wrap_err!("{} expected target to be a buffer, got: {:?}", function_name, other);
+            },
+            None => {
+                return wrap_err!("{} incorrectly called without target buffer", function_name);
+            }
+        };
+        let target_offset = match multivalue.pop_front() {
+            Some(LuaValue::Integer(offset)) => int_to_usize(offset, function_name, "target_offset")?,
+            Some(LuaValue::Number(f)) => float_to_usize(f, function_name, "target_offset")?,
+            Some(LuaNil) | None => 0,
+            Some(other) => {
+                return wrap_err!("{} expected target_offset to be a number or nil, got: {:?}", function_name, other);
+            }
+        };
+        if target_offset >= buffy.len() {
+            return wrap_err!("{}: target_offset {} is out of bounds for buffer of length {}", function_name, target_offset, buffy.len());
+        }
+        let timeout = ReadTimeout::new(pop_timeout(&mut multivalue, function_name)?);
+
+        if self.pending.is_empty() && !self.receive(&timeout, function_name)? {
+            return Ok(LuaValue::Integer(0));
+        }
+        let count = self.pending.len().min(buffy.len() - target_offset);
+        let bytes: Vec<u8> = self.pending.drain(..count).collect();
+        buffy.write_bytes(target_offset, &bytes);
+        Ok(LuaValue::Integer(count as i64))
+    }
+
+    /// writes exactly `count` bytes into `target`; returns false on timeout or if the other end hung up first
+    pub fn fill_exact(&mut self, _luau: &Lua, mut multivalue: LuaMultiValue) -> LuaValueResult {
+        let function_name = "TcpSocket:fill_exact(count: number, target: buffer, target_offset: number?, timeout: number?)";
+        pop_self(&mut multivalue, function_name)?;
+        self.stream(function_name)?;
+
+        let count = match multivalue.pop_front() {
+            Some(LuaValue::Integer(count)) => int_to_usize(count, function_name, "count")?,
+            Some(LuaValue::Number(f)) => float_to_usize(f, function_name, "count")?,
+            Some(other) => {
+                return wrap_err!("{} expected count to be a number, got: {:?}", function_name, other);
+            },
+            None => {
+                return wrap_err!("{} called without required argument 'count'", function_name);
+            }
+        };
+        let buffy = match multivalue.pop_front() {
+            Some(LuaValue::Buffer(buffy)) => buffy,
+            Some(other) => {
+                return wrap_err!("{} expected target to be a buffer, got: {:?}", function_name, other);
+            },
+            None => {
+                return wrap_err!("{} expected target to be a buffer, got nothing or nil", function_name);
+            }
+        };
+        let target_offset = match multivalue.pop_front() {
+            Some(LuaValue::Integer(offset)) => int_to_usize(offset, function_name, "target_offset")?,
+            Some(LuaValue::Number(f)) => float_to_usize(f, function_name, "target_offset")?,
+            Some(LuaNil) | None => 0,
+            Some(other) => {
+                return wrap_err!("{} expected target_offset to be a number or nil, got: {:?}", function_name, other);
+            }
+        };
+        if target_offset + count > buffy.len() {
+            return wrap_err!("{}: can't fit offset {} + count {} bytes into buffer of length {}", function_name, target_offset, count, buffy.len());
+        }
+        let timeout = ReadTimeout::new(pop_timeout(&mut multivalue, function_name)?);
+
+        while self.pending.len() < count {
+            if !self.receive(&timeout, function_name)? {
+                return Ok(LuaValue::Boolean(false));
+            }
+        }
+        let bytes: Vec<u8> = self.pending.drain(..count).collect();
+        buffy.write_bytes(target_offset, &bytes);
+        Ok(LuaValue::Boolean(true))
+    }
+
+    pub fn write(&mut self, mut multivalue: LuaMultiValue) -> LuaEmptyResult {
+        let function_name = "TcpSocket:write(data: string | buffer)";
+        pop_self(&mut multivalue, function_name)?;
+        let data = pop_data(&mut multivalue, function_name)?;
+        let mut stream = self.stream(function_name)?;
+        if let Err(err) = stream.write_all(&data) {
+            return wrap_err!("{}: unable to write to socket due to err: {}", function_name, err);
+        }
+        Ok(())
+    }
+
+    /// stops writing but keeps reading; the other end sees EOF
+    pub fn shutdown(&mut self) -> LuaEmptyResult {
+        let function_name = "TcpSocket:shutdown()";
+        if let Err(err) = self.stream(function_name)?.shutdown(Shutdown::Write)
+            && err.kind() != ErrorKind::NotConnected
+        {
+            return wrap_err!("{}: unable to shut down socket due to err: {}", function_name, err);
+        }
+        Ok(())
+    }
+
+    pub fn close(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+        self.pending.clear();
+    }
+
+    fn create_handle(socket_cell: SocketCell, luau: &Lua) -> LuaResult<LuaTable> {
+        let (local_address, peer_address) = {
+            let socket = socket_cell.borrow();
+            let stream = socket.stream("TcpSocket")?;
+            match (stream.local_addr(), stream.peer_addr()) {
+                (Ok(local), Ok(peer)) => (local, peer),
+                (Err(err), _) | (_, Err(err)) => {
+                    return wrap_err!("TcpSocket: unable to get socket addresses due to err: {}", err);
+                }
+            }
+        };
+
+        TableBuilder::create(luau)?
+            .with_value("local_address", address_table(luau, local_address)?)?
+            .with_value("peer_address", address_table(luau, peer_address)?)?
+            .with_function("read", {
+                let socket_cell = Rc::clone(&socket_cell);
+                move | luau: &Lua, multivalue: LuaMultiValue | -> LuaValueResult {
+                    borrow_socket(&socket_cell, "TcpSocket:read(count: number?, timeout: number?)")?.read(luau, multivalue)
+                }
+            })?
+            .with_function("read_exact", {
+                let socket_cell = Rc::clone(&socket_cell);
+                move | luau: &Lua, multivalue: LuaMultiValue | -> LuaValueResult {
+                    borrow_socket(&socket_cell, "TcpSocket:read_exact(count: number, timeout: number?)")?.read_exact(luau, multivalue)
+                }
+            })?
+            .with_function("read_to", {
+                let socket_cell = Rc::clone(&socket_cell);
+                move | luau: &Lua, multivalue: LuaMultiValue | -> LuaValueResult {
+                    borrow_socket(&socket_cell, "TcpSocket:read_to(term: string, inclusive: boolean?, timeout: number?, allow_partial: boolean?)")?.read_to(luau, multivalue)
+                }
+            })?
+            .with_function("fill", {
+                let socket_cell = Rc::clone(&socket_cell);
+                move | luau: &Lua, multivalue: LuaMultiValue | -> LuaValueResult {
+                    borrow_socket(&socket_cell, "TcpSocket:fill(target: buffer, target_offset: number?, timeout: number?)")?.fill(luau, multivalue)
+                }
+            })?
+            .with_function("fill_exact", {
+                let socket_cell = Rc::clone(&socket_cell);
+                move | luau: &Lua, multivalue: LuaMultiValue | -> LuaValueResult {
+                    borrow_socket(&socket_cell, "TcpSocket:fill_exact(count: number, target: buffer, target_offset: number?, timeout: number?)")?.fill_exact(luau, multivalue)
+                }
+            })?
+            .with_function("lines", {
+                let socket_cell = Rc::clone(&socket_cell);
+                move | luau: &Lua, mut multivalue: LuaMultiValue | -> LuaResult<LuaFunction> {
+                    let function_name = "TcpSocket:lines(timeout: number?)";
+                    pop_self(&mut multivalue, function_name)?;
+                    let timeout = pop_timeout(&mut multivalue, function_name)?;
+                    let socket_cell = Rc::clone(&socket_cell);
+                    luau.create_function(move | luau: &Lua, _value: LuaValue | -> LuaValueResult {
+                        let function_name = "TcpSocket:lines() iterator function";
+                        let mut socket = borrow_socket(&socket_cell, function_name)?;
+                        // each line gets the full timeout, otherwise long-lived connections would stop iterating partway through
+                        let timeout = ReadTimeout::new(timeout);
+                        let mut searched = 0;
+                        loop {
+                            if let Some(position) = socket.pending[searched..].iter().position(|&b| b == b'\n') {
+                                let line: Vec<u8> = socket.pending.drain(..=searched + position).collect();
+                                let line = line.strip_suffix(b"\n").unwrap_or(&line);
+                                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                                return ok_string(line, luau);
+                            }
+                            searched = socket.pending.len();
+                            if socket.stream.is_none() || !socket.receive(&timeout, function_name)? {
+                                return Ok(LuaNil);
+                            }
+                        }
+                    })
+                }
+            })?
+            .with_function("write", {
+                let socket_cell = Rc::clone(&socket_cell);
+                move | _luau: &Lua, multivalue: LuaMultiValue | -> LuaEmptyResult {
+                    borrow_socket(&socket_cell, "TcpSocket:write(data: string | buffer)")?.write(multivalue)
+                }
+            })?
+            .with_function("shutdown", {
+                let socket_cell = Rc::clone(&socket_cell);
+                move | _luau: &Lua, _value: LuaMultiValue | -> LuaEmptyResult {
+                    borrow_socket(&socket_cell, "TcpSocket:shutdown()")?.shutdown()
+                }
+            })?
+            .with_function("close", {
+                let socket_cell = Rc::clone(&socket_cell);
+                move | _luau: &Lua, _value: LuaMultiValue | -> LuaEmptyResult {
+                    borrow_socket(&socket_cell, "TcpSocket:close()")?.close();
+                    Ok(())
+                }
+            })?
+            .build_readonly()
+    }
+}
+
+fn borrow_socket<'a>(socket_cell: &'a SocketCell, function_name: &'static str) -> LuaResult<std::cell::RefMut<'a, TcpSocket>> {
+    match socket_cell.try_borrow_mut() {
+        Ok(socket) => Ok(socket),
+        Err(_) => wrap_err!("{}: socket already borrowed", function_name),
+    }
+}
+
+fn create_socket(luau: &Lua, stream: TcpStream) -> LuaResult<LuaTable> {
+    TcpSocket::create_handle(Rc::new(RefCell::new(TcpSocket::new(stream))), luau)
+}
+
+/// `tcp.connect(host: string, port: number, timeout: number?)`
+fn tcp_connect(luau: &Lua, mut multivalue: LuaMultiValue) -> LuaValueResult {
+    let function_name = "tcp.connect(host: string, port: number, timeout: number?)";
+    let (host, port) = pop_host_port(&mut multivalue, function_name, true)?;
+    let timeout = match pop_timeout(&mut multivalue, function_name)? {
+        Some(timeout) if timeout.is_zero() => {
+            return wrap_err!("{}: timeout must be greater than 0 (or nil to wait as long as the OS lets us)", function_name);
+        },
+        timeout => timeout,
+    };
+    let stream = connect_tcp(&host, port, timeout, function_name)?;
+    ok_table(create_socket(luau, stream))
+}
+
+/// `tcp.listen(host: string, port: number?)`; port 0 or nil picks any free port (check `TcpListener.local_address.port`)
+fn tcp_listen(luau: &Lua, mut multivalue: LuaMultiValue) -> LuaValueResult {
+    let function_name = "tcp.listen(host: string, port: number?)";
+    let (host, port) = pop_host_port(&mut multivalue, function_name, false)?;
+    let listener = match TcpListener::bind(resolve(&host, port, function_name)?.as_slice()) {
+        Ok(listener) => listener,
+        Err(err) => {
+            return wrap_err!("{}: unable to listen on {}:{} due to err: {}", function_name, host, port, err);
+        }
+    };
+    let local_address = match listener.local_addr() {
+        Ok(address) => address,
+        Err(err) => {
+            return wrap_err!("{}: unable to get listener address due to err: {}", function_name, err);
+        }
+    };
+
+    let listener_cell: Rc<RefCell<Option<TcpListener>>> = Rc::new(RefCell::new(Some(listener)));
+    TableBuilder::create(luau)?
+        .with_value("local_address", address_table(luau, local_address)?)?
+        .with_function("accept", {
+            let listener_cell = Rc::clone(&listener_cell);
+            move | luau: &Lua, mut multivalue: LuaMultiValue | -> LuaValueResult {
+                let function_name = "TcpListener:accept(timeout: number?)";
+                pop_self(&mut multivalue, function_name)?;
+                let timeout = ReadTimeout::new(pop_timeout(&mut multivalue, function_name)?);
+                let listener = listener_cell.borrow();
+                let Some(listener) = listener.as_ref() else {
+                    return wrap_err!("{}: listener is already closed", function_name);
+                };
+                // listeners don't have an accept timeout, so anything but blocking polls
+                if let Err(err) = listener.set_nonblocking(!matches!(timeout, ReadTimeout::Block)) {
+                    return wrap_err!("{}: unable to set listener blocking mode due to err: {}", function_name, err);
+                }
+                loop {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            if let Err(err) = stream.set_nonblocking(false) {
+                                return wrap_err!("{}: unable to set up accepted socket due to err: {}", function_name, err);
+                            }
+                            return ok_table(create_socket(luau, stream));
+                        },
+                        Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                            if timeout.socket_options().is_none() || matches!(timeout, ReadTimeout::Nonblocking) {
+                                return Ok(LuaNil);
+                            }
+                            std::thread::sleep(Duration::from_millis(10));
+                        },
+                        Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                        Err(err) => {
+                            return wrap_err!("{}: unable to accept connection due to err: {}", function_name, err);
+                        }
+                    }
+                }
+            }
+        })?
+        .with_function("close", {
+            let listener_cell = Rc::clone(&listener_cell);
+            move | _luau: &Lua, _value: LuaMultiValue | -> LuaEmptyResult {
+                listener_cell.borrow_mut().take();
+                Ok(())
+            }
+        })?
+        .build_readonly()
+        .map(LuaValue::Table)
+}
+
+pub fn create(luau: &Lua) -> LuaResult<LuaTable> {
+    TableBuilder::create(luau)?
+        .with_function("connect", tcp_connect)?
+        .with_function("listen", tcp_listen)?
+        .build_readonly()
+}
@@ -0,0 +1,194 @@
+//! Raw UDP sockets: `net.udp.bind`. Timeouts work like `TcpSocket` reads (`nil` blocks, `0` doesn't block).
+
+use mluau::prelude::*;
+use crate::prelude::*;
+use super::tcp::{address_table, pop_data, pop_host_port, pop_timeout, resolve, ReadTimeout};
+
+use std::cell::RefCell;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+use std::rc::Rc;
+
+/// the largest payload a UDP datagram can carry; anything past this would've been truncated by the OS anyway
+const MAX_DATAGRAM_SIZE: usize = 65_535;
+
+type SocketCell = Rc<RefCell<Option<UdpSocket>>>;
+
+fn with_socket<R>(socket_cell: &SocketCell, function_name: &'static str, f: impl FnOnce(&UdpSocket) -> LuaResult<R>) -> LuaResult<R> {
+    let socket = match socket_cell.try_borrow() {
+        Ok(socket) => socket,
+        Err(_) => {
+            return wrap_err!("{}: socket already borrowed", function_name);
+        }
+    };
+    match socket.as_ref() {
+        Some(socket) => f(socket),
+        None => wrap_err!("{}: socket is already closed", function_name),
+    }
+}
+
+/// the next datagram and who sent it, or `None` if nothing came in before the timeout
+fn receive(socket: &UdpSocket, timeout: Option<std::time::Duration>, function_name: &'static str) -> LuaResult<Option<(Vec<u8>, SocketAddr)>> {
+    let Some((nonblocking, read_timeout)) = ReadTimeout::new(timeout).socket_options() else {
+        return Ok(None);
+    };
+    if let Err(err) = socket.set_nonblocking(nonblocking) {
+        return wrap_err!("{}: unable to set socket blocking mode due to err: {}", function_name, err);
+    }
+    if let Err(err) = socket.set_read_timeout(read_timeout) {
+        return wrap_err!("{}: unable to set socket read timeout due to err: {}", function_name, err);
+    }
+    let mut datagram = vec![0_u8; MAX_DATAGRAM_SIZE];
+    loop {
+        match socket.recv_from(&mut datagram) {
+            Ok((size, from)) => {
+                datagram.truncate(size);
+                return Ok(Some((datagram, from)));
+            },
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => return Ok(None),
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            // on some platforms, an ICMP port unreachable from an earlier send shows up here
+            Err(err) if err.kind() == ErrorKind::ConnectionRefused => return Ok(None),
+            Err(err) => {
+                return wrap_err!("{}: unable to receive from socket due to err: {}", function_name, err);
+            }
+        }
+    }
+}
+
+/// `udp.bind(host: string, port: number?)`; port 0 or nil picks any free port (check `UdpSocket.local_address.port`)
+fn udp_bind(luau: &Lua, mut multivalue: LuaMultiValue) -> LuaValueResult {
+    let function_name = "udp.bind(host: string, port: number?)";
+    let (host, port) = pop_host_port(&mut multivalue, function_name, false)?;
+    let socket = match UdpSocket::bind(resolve(&host, port, function_name)?.as_slice()) {
+        Ok(socket) => socket,
+        Err(err) => {
+            return wrap_err!("{}: unable to bind to {}:{} due to err: {}", function_name, host, port, err);
+        }
+    };
+    let local_address = match socket.local_addr() {
+        Ok(address) => address,
+        Err(err) => {
+            return wrap_err!("{}: unable to get socket address due to err: {}", function_name, err);
+        }
+    };
+
+    let socket_cell: SocketCell = Rc::new(RefCell::new(Some(socket)));
+    TableBuilder::create(luau)?
+        .with_value("local_address", address_table(luau, local_address)?)?
+        .with_function("send_to", {
+            let socket_cell = Rc::clone(&socket_cell);
+            move | _luau: &Lua, mut multivalue: LuaMultiValue | -> LuaEmptyResult {
+                let function_name = "UdpSocket:send_to(data: string | buffer, host: string, port: number)";
+                pop_self(&mut multivalue, function_name)?;
+                let data = pop_data(&mut multivalue, function_name)?;
+                let (host, port) = pop_host_port(&mut multivalue, function_name, true)?;
+                let addresses = resolve(&host, port, function_name)?;
+                with_socket(&socket_cell, function_name, | socket | {
+                    match socket.send_to(&data, addresses.as_slice()) {
+                        Ok(_) => Ok(()),
+                        Err(err) => wrap_err!("{}: unable to send to {}:{} due to err: {}", function_name, host, port, err),
+                    }
+                })
+            }
+        })?
+        .with_function("recv_from", {
+            let socket_cell = Rc::clone(&socket_cell);
+            move | luau: &Lua, mut multivalue: LuaMultiValue | -> LuaMultiResult {
+                let function_name = "UdpSocket:recv_from(timeout: number?)";
+                pop_self(&mut multivalue, function_name)?;
+                let timeout = pop_timeout(&mut multivalue, function_name)?;
+                match with_socket(&socket_cell, function_name, | socket | receive(socket, timeout, function_name))? {
+                    Some((datagram, from)) => Ok(LuaMultiValue::from_vec(vec![
+                        ok_string(&datagram, luau)?,
+                        LuaValue::Table(address_table(luau, from)?),
+                    ])),
+                    None => Ok(LuaMultiValue::from_vec(vec![LuaNil, LuaNil])),
+                }
+            }
+        })?
+        .with_function("connect", {
+            let socket_cell = Rc::clone(&socket_cell);
+            move | _luau: &Lua, mut multivalue: LuaMultiValue | -> LuaEmptyResult {
+                let function_name = "UdpSocket:connect(host: string, port: number)";
+                pop_self(&mut multivalue, function_name)?;
+                let (host, port) = pop_host_port(&mut multivalue, function_name, true)?;
+                let addresses = resolve(&host, port, function_name)?;
+                with_socket(&socket_cell, function_name, | socket | {
+                    match socket.connect(addresses.as_slice()) {
+                        Ok(()) => Ok(()),
+                        Err(err) => wrap_err!("{}: unable to connect to {}:{} due to err: {}", function_name, host, port, err),
+                    }
+                })
+            }
+        })?
+        .with_function("send", {
+            let socket_cell = Rc::clone(&socket_cell);
+            move | _luau: &Lua, mut multivalue: LuaMultiValue | -> LuaEmptyResult {
+                let function_name = "UdpSocket:send(data: string | buffer)";
+                pop_self(&mut multivalue, function_name)?;
+                let data = pop_data(&mut multivalue, function_name)?;
+                with_socket(&socket_cell, function_name, | socket | {
+                    match socket.send(&data) {
+                        Ok(_) => Ok(()),
+                        Err(err) if err.kind() == ErrorKind::NotConnected => {
+                            wrap_err!("{}: socket isn't connected; call UdpSocket:connect first or use UdpSocket:send_to", function_name)
+                        },
+                        Err(err) => wrap_err!("{}: unable to send due to err: {}", function_name, err),
+                    }
+                })
+            }
+        })?
+        .with_function("recv", {
+            let socket_cell = Rc::clone(&socket_cell);
+            move | luau: &Lua, mut multivalue: LuaMultiValue | -> LuaValueResult {
+                let function_name = "UdpSocket:recv(timeout: number?)";
+                pop_self(&mut multivalue, function_name)?;
+                let timeout = pop_timeout(&mut multivalue, function_name)?;
+                // connected sockets only get datagrams from their peer, so there's no need to hand back who sent it
+                match with_socket(&socket_cell, function_name, | socket | receive(socket, timeout, function_name))? {
+                    Some((datagram, _)) => ok_string(&datagram, luau),
+                    None => Ok(LuaNil),
+                }
+            }
+        })?
+        .with_function("set_broadcast", {
+            let socket_cell = Rc::clone(&socket_cell);
+            move | _luau: &Lua, mut multivalue: LuaMultiValue | -> LuaEmptyResult {
+                let function_name = "UdpSocket:set_broadcast(enabled: boolean)";
+                pop_self(&mut multivalue, function_name)?;
+                let enabled = match multivalue.pop_front() {
+                    Some(LuaValue::Boolean(enabled)) => enabled,
+                    other => {
+                        return wrap_err!("{} expected enabled to be a boolean, got: {:?}", function_name, other);
+                    }
+                };
+                with_socket(&socket_cell, function_name, | socket | {
+                    match socket.set_broadcast(enabled) {
+                        Ok(()) => Ok(()),
+                        Err(err) => wrap_err!("{}: unable to set broadcast due to err: {}", function_name, err),
+                    }
+                })
+            }
+        })?
+        .with_function("close", {
+            let socket_cell = Rc::clone(&socket_cell);
+            move | _luau: &Lua, _value: LuaMultiValue | -> LuaEmptyResult {
+                match socket_cell.try_borrow_mut() {
+                    Ok(mut socket) => {
+                        socket.take();
+                        Ok(())
+                    },
+                    Err(_) => wrap_err!("UdpSocket:close(): socket already borrowed"),
+                }
+            }
+        })?
+        .build_readonly()
+        .map(LuaValue::Table)
+}
+
+pub fn create(luau: &Lua) -> LuaResult<LuaTable> {
+    TableBuilder::create(luau)?
+        .with_function("bind", udp_bind)?
+        .build_readonly()
+}
@@ -0,0 +1,584 @@
+//! `@std/net/url`: parsing, building, and resolving URLs (RFC 3986), plus the percent-encoding and query string
+//! helpers the rest of `@std/net` uses (form bodies, request params, router paths).
+
+use mluau::prelude::*;
+use crate::prelude::*;
+
+use std::fmt;
+
+/// bytes that never need percent-encoding
+const UNRESERVED: &[u8] = b"-._~";
+/// what can appear as-is in a userinfo (`user:password@`)
+const USERINFO: &[u8] = b"-._~!$&'()*+,;=";
+/// what can appear as-is in a path (pchar + `/`)
+const PATH: &[u8] = b"-._~!$&'()*+,;=:@/";
+/// what can appear as-is in a query string or fragment (pchar + `/` + `?`)
+const QUERY: &[u8] = b"-._~!$&'()*+,;=:@/?";
+/// like `QUERY`, but without the characters that would split up a query parameter (`&`, `=`, `+`)
+const QUERY_COMPONENT: &[u8] = b"-._~!$'()*,;:@/?";
+
+/// percent-encodes everything but ascii alphanumerics and `allowed`; if `keep_escapes`, existing `%XX` escapes are left alone
+fn encode_set(s: &[u8], allowed: &[u8], keep_escapes: bool) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for (index, &b) in s.iter().enumerate() {
+        let is_escape = b == b'%'
+            && s.get(index + 1..index + 3).is_some_and(|hex| hex.iter().all(u8::is_ascii_hexdigit));
+        if b.is_ascii_alphanumeric() || allowed.contains(&b) || (keep_escapes && is_escape) {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+/// percent-encodes everything but unreserved characters; with `plus_for_space`, spaces become `+` like browsers do for forms
+pub fn encode_component(s: &str, plus_for_space: bool) -> String {
+    if plus_for_space {
+        s.split(' ')
+            .map(|part| encode_set(part.as_bytes(), UNRESERVED, false))
+            .collect::<Vec<String>>()
+            .join("+")
+    } else {
+        encode_set(s.as_bytes(), UNRESERVED, false)
+    }
+}
+
+/// decodes `%XX` escapes, leaving anything that isn't a valid escape alone
+pub fn percent_decode_bytes(s: &str) -> Vec<u8> {
+    let bytes = s.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%'
+            && let Some(hex) = s.get(index + 1..index + 3)
+            && hex.bytes().all(|b| b.is_ascii_hexdigit())
+            && let Ok(byte) = u8::from_str_radix(hex, 16)
+        {
+            decoded.push(byte);
+            index += 3;
+        } else {
+            decoded.push(bytes[index]);
+            index += 1;
+        }
+    }
+    decoded
+}
+
+pub fn percent_decode(s: &str) -> String {
+    String::from_utf8_lossy(&percent_decode_bytes(s)).into_owned()
+}
+
+/// splits a query string like `a=1&b=hello+world` into decoded key value pairs
+pub fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(&key.replace('+', " ")), percent_decode(&value.replace('+', " ")))
+        })
+        .collect()
+}
+
+/// joins already-decoded key value pairs into a query string (or form body)
+pub fn encode_query(pairs: &[(String, String)], plus_for_space: bool) -> String {
+    pairs
+        .iter()
+        .map(|(key, value)| format!("{}={}", encode_component(key, plus_for_space), encode_component(value, plus_for_space)))
+        .collect::<Vec<String>>()
+        .join("&")
+}
+
+fn query_value_to_string(value: LuaValue, function_name: &'static str, key: &str) -> LuaResult<String> {
+    match value {
+        LuaValue::String(s) => Ok(s.to_string_lossy()),
+        LuaValue::Integer(n) => Ok(n.to_string()),
+        LuaValue::Number(f) => Ok(f.to_string()),
+        LuaValue::Boolean(b) => Ok(b.to_string()),
+        other => {
+            wrap_err!("{} expected field '{}' to be a string, number, boolean, or an array of them, got: {:?}", function_name, key, other)
+        }
+    }
+}
+
+/// query params for a url; characters like `/` and `:` stay readable since they don't need escaping inside a query
+fn encode_url_query(pairs: &[(String, String)]) -> String {
+    pairs
+        .iter()
+        .map(|(key, value)| format!("{}={}", encode_set(key.as_bytes(), QUERY_COMPONENT, false), encode_set(value.as_bytes(), QUERY_COMPONENT, false)))
+        .collect::<Vec<String>>()
+        .join("&")
+}
+
+/// `{ [string]: QueryValue | { QueryValue } }` into key value pairs, sorted by key; arrays repeat the key (`tag=a&tag=b`)
+pub fn query_pairs_from_table(fields: &LuaTable, function_name: &'static str) -> LuaResult<Vec<(String, String)>> {
+    let mut pairs: Vec<(String, String)> = Vec::new();
+    for pair in fields.pairs::<LuaValue, LuaValue>() {
+        let (key, value) = match pair? {
+            (LuaValue::String(key), value) => (key.to_string_lossy(), value),
+            (other, _) => {
+                return wrap_err!("{} expected field names to be strings, got: {:?}", function_name, other);
+            }
+        };
+        match (key, value) {
+            (key, LuaValue::Table(values)) => {
+                for value in values.sequence_values::<LuaValue>() {
+                    let value = query_value_to_string(value?, function_name, &key)?;
+                    pairs.push((key.clone(), value));
+                }
+            },
+            (key, value) => {
+                let value = query_value_to_string(value, function_name, &key)?;
+                pairs.push((key, value));
+            }
+        }
+    }
+    // pairs() order isn't stable; sorting keeps urls and bodies reproducible (sort_by is stable so repeated keys keep their order)
+    pairs.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(pairs)
+}
+
+/// the opposite of `query_pairs_from_table`: keys that show up more than once get an array of their values
+pub fn query_table(luau: &Lua, pairs: Vec<(String, String)>) -> LuaResult<LuaTable> {
+    let table = luau.create_table()?;
+    for (key, value) in pairs {
+        match table.raw_get::<LuaValue>(key.as_str())? {
+            LuaNil => table.raw_set(key, value)?,
+            LuaValue::Table(values) => values.raw_push(value)?,
+            existing => {
+                let values = luau.create_sequence_from([existing, LuaValue::String(luau.create_string(&value)?)])?;
+                table.raw_set(key, values)?;
+            }
+        }
+    }
+    Ok(table)
+}
+
+pub struct Authority {
+    /// percent-encoded `user:password`
+    pub userinfo: Option<String>,
+    /// without brackets, even for IPv6 addresses
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl Authority {
+    fn parse(authority: &str) -> Result<Self, String> {
+        let (userinfo, host_port) = match authority.rsplit_once('@') {
+            Some((userinfo, host_port)) => (Some(encode_set(userinfo.as_bytes(), b"-._~!$&'()*+,;=:", true)), host_port),
+            None => (None, authority),
+        };
+        let (host, port) = if let Some(rest) = host_port.strip_prefix('[') {
+            match rest.split_once(']') {
+                Some((host, "")) => (host, None),
+                Some((host, port)) => match port.strip_prefix(':') {
+                    Some(port) => (host, Some(port)),
+                    None => return Err(format!("unexpected '{}' after IPv6 address", port)),
+                },
+                None => return Err(String::from("IPv6 address is missing its closing ']'")),
+            }
+        } else {
+            match host_port.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (host_port, None),
+            }
+        };
+        if host.chars().any(char::is_whitespace) {
+            return Err(format!("host '{}' can't contain whitespace", host));
+        }
+        let port = match port {
+            Some("") | None => None,
+            Some(port) => match port.parse::<u16>() {
+                Ok(port) => Some(port),
+                Err(_) => return Err(format!("invalid port '{}'", port)),
+            },
+        };
+        Ok(Self { userinfo, host: host.to_ascii_lowercase(), port })
+    }
+}
+
+/// a URL or relative reference, with everything but the host kept percent-encoded
+pub struct Url {
+    pub scheme: Option<String>,
+    pub authority: Option<Authority>,
+    pub path: String,
+    pub query: Option<String>,
+    pub fragment: Option<String>,
+}
+
+fn is_scheme(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+}
+
+/// schemes where an empty path means `/`
+fn is_special(scheme: &str) -> bool {
+    matches!(scheme, "http" | "https" | "ws" | "wss")
+}
+
+/// RFC 3986 section 5.2.4
+fn remove_dot_segments(path: &str) -> String {
+    let mut input = path;
+    let mut output = String::with_capacity(path.len());
+    let pop_segment = |output: &mut String| {
+        let last_slash = output.rfind('/').unwrap_or(0);
+        output.truncate(last_slash);
+    };
+    while !input.is_empty() {
+        if let Some(rest) = input.strip_prefix("../").or_else(|| input.strip_prefix("./")) {
+            input = rest;
+        } else if input.starts_with("/./") {
+            input = &input[2..];
+        } else if input == "/." {
+            input = "/";
+        } else if input.starts_with("/../") {
+            input = &input[3..];
+            pop_segment(&mut output);
+        } else if input == "/.." {
+            input = "/";
+            pop_segment(&mut output);
+        } else if input == "." || input == ".." {
+            input = "";
+        } else {
+            let start = usize::from(input.starts_with('/'));
+            let end = input[start..].find('/').map_or(input.len(), |index| index + start);
+            output.push_str(&input[..end]);
+            input = &input[end..];
+        }
+    }
+    output
+}
+
+impl Url {
+    /// parses a URL or relative reference (`../img.png`, `?page=2`)
+    pub fn parse_reference(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        let (s, fragment) = match s.split_once('#') {
+            Some((s, fragment)) => (s, Some(encode_set(fragment.as_bytes(), QUERY, true))),
+            None => (s, None),
+        };
+        let (s, query) = match s.split_once('?') {
+            Some((s, query)) => (s, Some(encode_set(query.as_bytes(), QUERY, true))),
+            None => (s, None),
+        };
+        let (scheme, rest) = match s.split_once(':') {
+            Some((scheme, rest)) if is_scheme(scheme) => (Some(scheme.to_ascii_lowercase()), rest),
+            _ => (None, s),
+        };
+        let (authority, path) = match rest.strip_prefix("//") {
+            Some(rest) => {
+                let end = rest.find('/').unwrap_or(rest.len());
+                (Some(Authority::parse(&rest[..end])?), &rest[end..])
+            },
+            None => (None, rest),
+        };
+        let mut path = encode_set(path.as_bytes(), PATH, true);
+        if path.is_empty() && authority.is_some() && scheme.as_deref().is_some_and(is_special) {
+            path.push('/');
+        }
+        Ok(Self { scheme, authority, path, query, fragment })
+    }
+
+    /// parses an absolute URL (one with a scheme)
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let url = Self::parse_reference(s)?;
+        match &url.scheme {
+            None => Err(format!("'{}' is missing a scheme (like https://); use url.resolve for relative urls", s)),
+            Some(scheme) if is_special(scheme) && url.authority.as_ref().is_none_or(|authority| authority.host.is_empty()) => {
+                Err(format!("{} url '{}' is missing a host", scheme, s))
+            },
+            Some(_) => Ok(url),
+        }
+    }
+
+    /// resolves `reference` against `self` like a browser resolves links on a page (RFC 3986 section 5.2.2)
+    pub fn join(&self, reference: Url) -> Url {
+        let Url { scheme, authority, path, query, fragment } = reference;
+        if scheme.is_some() {
+            return Url { scheme, authority, path: remove_dot_segments(&path), query, fragment };
+        }
+        let base_authority = || self.authority.as_ref().map(|authority| Authority {
+            userinfo: authority.userinfo.clone(),
+            host: authority.host.clone(),
+            port: authority.port,
+        });
+        let (authority, path, query) = if authority.is_some() {
+            (authority, remove_dot_segments(&path), query)
+        } else if path.is_empty() {
+            (base_authority(), self.path.clone(), query.or_else(|| self.query.clone()))
+        } else if path.starts_with('/') {
+            (base_authority(), remove_dot_segments(&path), query)
+        } else {
+            let merged = if self.authority.is_some() && self.path.is_empty() {
+                format!("/{}", path)
+            } else {
+                let directory_end = self.path.rfind('/').map_or(0, |index| index + 1);
+                format!("{}{}", &self.path[..directory_end], path)
+            };
+            (base_authority(), remove_dot_segments(&merged), query)
+        };
+        Url { scheme: self.scheme.clone(), authority, path, query, fragment }
+    }
+
+    pub fn from_table(url: &LuaTable, function_name: &'static str) -> LuaResult<Self> {
+        let scheme = match url.raw_get("scheme")? {
+            LuaValue::String(scheme) if is_scheme(&scheme.to_string_lossy()) => scheme.to_string_lossy().to_ascii_lowercase(),
+            other => {
+                return wrap_err!("{} expected Url.scheme to be a scheme like \"https\", got: {:?}", function_name, other);
+            }
+        };
+        let optional_string = |field: &'static str| -> LuaResult<Option<String>> {
+            match url.raw_get(field)? {
+                LuaValue::String(s) => Ok(Some(s.to_string_lossy())),
+                LuaNil => Ok(None),
+                other => {
+                    wrap_err!("{} expected Url.{} to be a string or nil, got: {:?}", function_name, field, other)
+                }
+            }
+        };
+
+        let port = match url.raw_get("port")? {
+            LuaValue::Integer(port) => match u16::try_from(port) {
+                Ok(port) => Some(port),
+                Err(_) => {
+                    return wrap_err!("{}: Url.port {} is out of range (0-65535)", function_name, port);
+                }
+            },
+            LuaValue::Number(port) if port.fract() == 0.0 && (0.0..=65535.0).contains(&port) => Some(port as u16),
+            LuaNil => None,
+            other => {
+                return wrap_err!("{} expected Url.port to be an integer or nil, got: {:?}", function_name, other);
+            }
+        };
+        let userinfo = match (optional_string("username")?, optional_string("password")?) {
+            (None, None) => None,
+            (username, password) => {
+                let username = encode_set(username.unwrap_or_default().as_bytes(), USERINFO, false);
+                match password {
+                    Some(password) => Some(format!("{}:{}", username, encode_set(password.as_bytes(), USERINFO, false))),
+                    None => Some(username),
+                }
+            }
+        };
+        let authority = match optional_string("host")? {
+            Some(host) => Some(Authority { userinfo, host: host.trim_start_matches('[').trim_end_matches(']').to_ascii_lowercase(), port }),
+            None if userinfo.is_some() || port.is_some() => {
+                return wrap_err!("{}: Url.username, Url.password, and Url.port need a Url.host", function_name);
+            },
+            None => None,
+        };
+
+        let mut path = encode_set(optional_string("path")?.unwrap_or_default().as_bytes(), PATH, true);
+        if authority.is_some() && !path.is_empty() && !path.starts_with('/') {
+            path.insert(0, '/');
+        } else if authority.is_some() && path.is_empty() && is_special(&scheme) {
+            path.push('/');
+        }
+
+        let query = match url.raw_get("query")? {
+            LuaValue::Table(query) => {
+                let pairs = query_pairs_from_table(&query, function_name)?;
+                if pairs.is_empty() {
+                    None
+                } else {
+                    Some(encode_url_query(&pairs))
+                }
+            },
+            LuaValue::String(query) => {
+                let query = query.to_string_lossy();
+                let query = query.strip_prefix('?').unwrap_or(&query);
+                Some(encode_set(query.as_bytes(), QUERY, true))
+            },
+            LuaNil => None,
+            other => {
+                return wrap_err!("{} expected Url.query to be a table of query params, a query string, or nil, got: {:?}", function_name, other);
+            }
+        };
+        let fragment = optional_string("fragment")?
+            .map(|fragment| encode_set(fragment.strip_prefix('#').unwrap_or(&fragment).as_bytes(), QUERY, true));
+
+        Ok(Self { scheme: Some(scheme), authority, path, query, fragment })
+    }
+
+    /// a `Url` table, which can be edited and turned back into a string with `url.build` or `tostring`
+    pub fn to_table(&self, luau: &Lua) -> LuaResult<LuaTable> {
+        let table = luau.create_table()?;
+        table.raw_set("scheme", self.scheme.clone())?;
+        if let Some(authority) = &self.authority {
+            if let Some(userinfo) = &authority.userinfo {
+                let (username, password) = match userinfo.split_once(':') {
+                    Some((username, password)) => (username, Some(password)),
+                    None => (userinfo.as_str(), None),
+                };
+                table.raw_set("username", percent_decode(username))?;
+                table.raw_set("password", password.map(percent_decode))?;
+            }
+            table.raw_set("host", authority.host.as_str())?;
+            table.raw_set("port", authority.port)?;
+        }
+        table.raw_set("path", self.path.as_str())?;
+        table.raw_set("query", query_table(luau, parse_query(self.query.as_deref().unwrap_or_default()))?)?;
+        table.raw_set("fragment", self.fragment.as_deref())?;
+        table.set_metatable(Some(url_metatable(luau)?))?;
+        Ok(table)
+    }
+}
+
+impl fmt::Display for Url {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(scheme) = &self.scheme {
+            write!(f, "{}:", scheme)?;
+        }
+        if let Some(authority) = &self.authority {
+            f.write_str("//")?;
+            if let Some(userinfo) = &authority.userinfo {
+                write!(f, "{}@", userinfo)?;
+            }
+            if authority.host.contains(':') {
+                write!(f, "[{}]", authority.host)?;
+            } else {
+                f.write_str(&authority.host)?;
+            }
+            if let Some(port) = authority.port {
+                write!(f, ":{}", port)?;
+            }
+        }
+        f.write_str(&self.path)?;
+        if let Some(query) = &self.query {
+            write!(f, "?{}", query)?;
+        }
+        if let Some(fragment) = &self.fragment {
+            write!(f, "#{}", fragment)?;
+        }
+        Ok(())
+    }
+}
+
+/// lets `tostring(url)` work on `Url` tables
+fn url_metatable(luau: &Lua) -> LuaResult<LuaTable> {
+    TableBuilder::create(luau)?
+        .with_value("__type", "Url")?
+        .with_function("__tostring", |_luau: &Lua, url: LuaTable| -> LuaResult<String> {
+            Ok(Url::from_table(&url, "Url.__tostring")?.to_string())
+        })?
+        .build_readonly()
+}
+
+/// whether a table passed where a url or options table can go (like `http.get`) is a `Url` rather than the options
+pub fn is_url_table(table: &LuaTable) -> LuaResult<bool> {
+    Ok(table.raw_get::<LuaValue>("url")?.is_nil() && table.raw_get::<LuaValue>("scheme")?.is_string())
+}
+
+/// a url string, or a `Url` table built into one
+pub fn url_string(value: LuaValue, function_name: &'static str, field: &'static str) -> LuaResult<String> {
+    match value {
+        LuaValue::String(url) => Ok(url.to_string_lossy()),
+        LuaValue::Table(url) => Ok(Url::from_table(&url, function_name)?.to_string()),
+        other => {
+            wrap_err!("{} expected {} to be a string or Url, got: {:?}", function_name, field, other)
+        }
+    }
+}
+
+fn url_parse(luau: &Lua, value: LuaValue) -> LuaValueResult {
+    let function_name = "url.parse(url: string)";
+    let url = match value {
+        LuaValue::String(url) => url.to_string_lossy(),
+        other => {
+            return wrap_err!("{} expected url to be a string, got: {:?}", function_name, other);
+        }
+    };
+    match Url::parse(&url) {
+        Ok(url) => ok_table(url.to_table(luau)),
+        Err(err) => wrap_err!("{}: {}", function_name, err),
+    }
+}
+
+fn url_build(_luau: &Lua, value: LuaValue) -> LuaResult<String> {
+    let function_name = "url.build(url: Url)";
+    match value {
+        LuaValue::Table(url) => Ok(Url::from_table(&url, function_name)?.to_string()),
+        other => {
+            wrap_err!("{} expected url to be a Url table, got: {:?}", function_name, other)
+        }
+    }
+}
+
+fn url_resolve(_luau: &Lua, (base, reference): (LuaValue, LuaValue)) -> LuaResult<String> {
+    let function_name = "url.resolve(base: string | Url, reference: string)";
+    let base = match Url::parse(&url_string(base, function_name, "base")?) {
+        Ok(base) => base,
+        Err(err) => {
+            return wrap_err!("{}: invalid base url: {}", function_name, err);
+        }
+    };
+    let reference = match reference {
+        LuaValue::String(reference) => reference.to_string_lossy(),
+        LuaValue::Table(url) => Url::from_table(&url, function_name)?.to_string(),
+        other => {
+            return wrap_err!("{} expected reference to be a string, got: {:?}", function_name, other);
+        }
+    };
+    match Url::parse_reference(&reference) {
+        Ok(reference) => Ok(base.join(reference).to_string()),
+        Err(err) => wrap_err!("{}: invalid reference '{}': {}", function_name, reference, err),
+    }
+}
+
+fn url_encode(_luau: &Lua, value: LuaValue) -> LuaResult<String> {
+    let function_name = "url.encode(s: string)";
+    match value {
+        LuaValue::String(s) => Ok(encode_set(&s.as_bytes(), UNRESERVED, false)),
+        other => {
+            wrap_err!("{} expected s to be a string, got: {:?}", function_name, other)
+        }
+    }
+}
+
+fn url_decode(luau: &Lua, value: LuaValue) -> LuaValueResult {
+    let function_name = "url.decode(s: string)";
+    match value {
+        LuaValue::String(s) => ok_string(percent_decode_bytes(&s.to_string_lossy()), luau),
+        other => {
+            wrap_err!("{} expected s to be a string, got: {:?}", function_name, other)
+        }
+    }
+}
+
+fn url_encode_query(_luau: &Lua, value: LuaValue) -> LuaResult<String> {
+    let function_name = "url.encode_query(params: { [string]: QueryValue | { QueryValue } })";
+    match value {
+        LuaValue::Table(params) => Ok(encode_url_query(&query_pairs_from_table(&params, function_name)?)),
+        other => {
+            wrap_err!("{} expected params to be a table, got: {:?}", function_name, other)
+        }
+    }
+}
+
+fn url_decode_query(luau: &Lua, value: LuaValue) -> LuaValueResult {
+    let function_name = "url.decode_query(query: string)";
+    match value {
+        LuaValue::String(query) => {
+            let query = query.to_string_lossy();
+            let query = query.strip_prefix('?').unwrap_or(&query);
+            ok_table(query_table(luau, parse_query(query)))
+        },
+        other => {
+            wrap_err!("{} expected query to be a string, got: {:?}", function_name, other)
+        }
+    }
+}
+
+pub fn create(luau: &Lua) -> LuaResult<LuaTable> {
+    TableBuilder::create(luau)?
+        .with_function("parse", url_parse)?
+        .with_function("build", url_build)?
+        .with_function("resolve", url_resolve)?
+        .with_function("encode", url_encode)?
+        .with_function("decode", url_decode)?
+        .with_function("encode_query", url_encode_query)?
+        .with_function("decode_query", url_decode_query)?
+        .build_readonly()
+}
@@ -0,0 +1,429 @@
+//! WebSockets: `net.websocket.connect` for clients, and `ServeResponse.websocket` upgrades in `server.serve`.
+//! Both ends get the same `WebSocket` handle, shaped like `@std/thread` channels (`send`, `sendbytes`, `read`, `read_await`).
+
+use mluau::prelude::*;
+use crate::prelude::*;
+use crate::std_json;
+use super::connection::ServeConnection;
+use super::request_parser::ParsedRequest;
+use super::tcp::connect_tcp;
+
+use tungstenite::client::IntoClientRequest;
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::http::{HeaderName, HeaderValue};
+use tungstenite::protocol::frame::coding::CloseCode;
+use tungstenite::protocol::{CloseFrame, Role};
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Error as WsError, Message, WebSocket};
+
+use std::cell::RefCell;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// how long `close()` waits for the other end to acknowledge the close before giving up on it
+const CLOSE_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// streams a WebSocket can run over; reads switch the TCP socket underneath between blocking and nonblocking
+trait SocketStream: Read + Write {
+    fn tcp_stream(&self) -> Option<&TcpStream>;
+}
+
+impl SocketStream for MaybeTlsStream<TcpStream> {
+    fn tcp_stream(&self) -> Option<&TcpStream> {
+        match self {
+            MaybeTlsStream::Plain(stream) => Some(stream),
+            MaybeTlsStream::Rustls(stream) => Some(stream.get_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl SocketStream for ServeConnection {
+    fn tcp_stream(&self) -> Option<&TcpStream> {
+        Some(self.socket())
+    }
+}
+
+struct Connection<S: SocketStream> {
+    socket: WebSocket<S>,
+    /// set once either end closes the connection (or it drops); reads return nil from then on
+    closed: bool,
+}
+
+type ConnectionCell<S> = Rc<RefCell<Connection<S>>>;
+
+impl<S: SocketStream> Connection<S> {
+    /// the next text or binary message, or `None` if there isn't one yet (when not blocking) or the connection's closed
+    fn read_message(&mut self, blocking: bool, function_name: &'static str) -> LuaResult<Option<Message>> {
+        if self.closed {
+            return Ok(None);
+        }
+        if let Some(stream) = self.socket.get_ref().tcp_stream()
+            && let Err(err) = stream.set_nonblocking(!blocking)
+        {
+            return wrap_err!("{}: unable to set socket blocking mode due to err: {}", function_name, err);
+        }
+        loop {
+            match self.socket.read() {
+                Ok(message @ (Message::Text(_) | Message::Binary(_))) => return Ok(Some(message)),
+                Ok(Message::Close(_)) => {
+                    // tungstenite queues our half of the close handshake; send it off and we're done
+                    let _ = self.socket.flush();
+                    self.closed = true;
+                    return Ok(None);
+                },
+                // pings get answered for us; pongs and raw frames aren't interesting
+                Ok(_) => continue,
+                Err(WsError::Io(err)) if err.kind() == ErrorKind::WouldBlock => return Ok(None),
+                Err(WsError::Io(_) | WsError::ConnectionClosed | WsError::AlreadyClosed | WsError::Protocol(_)) => {
+                    self.closed = true;
+                    return Ok(None);
+                },
+                Err(err) => {
+                    self.closed = true;
+                    return wrap_err!("{}: unable to read message due to err: {}", function_name, err);
+                }
+            }
+        }
+    }
+
+    fn send_message(&mut self, message: Message, function_name: &'static str) -> LuaEmptyResult {
+        if self.closed {
+            return wrap_err!("{}: can't send on a closed WebSocket", function_name);
+        }
+        if let Some(stream) = self.socket.get_ref().tcp_stream() {
+            let _ = stream.set_nonblocking(false);
+        }
+        match self.socket.send(message) {
+            Ok(()) => Ok(()),
+            Err(err) => {
+                self.closed = true;
+                wrap_err!("{}: unable to send message due to err: {}", function_name, err)
+            }
+        }
+    }
+
+    fn close(&mut self, frame: Option<CloseFrame>) {
+        if self.closed {
+            return;
+        }
+        self.closed = true;
+        if let Some(stream) = self.socket.get_ref().tcp_stream() {
+            let _ = stream.set_nonblocking(false);
+            let _ = stream.set_read_timeout(Some(CLOSE_HANDSHAKE_TIMEOUT));
+        }
+        if self.socket.close(frame).is_err() {
+            return;
+        }
+        // wait for the other end's close frame so it knows we closed on purpose; everything else it sends is dropped
+        while self.socket.read().is_ok() {}
+    }
+}
+
+fn message_to_value(luau: &Lua, message: Option<Message>) -> LuaValueResult {
+    match message {
+        Some(Message::Text(text)) => Ok(LuaValue::String(luau.create_string(text.as_str())?)),
+        Some(Message::Binary(bytes)) => ok_buffy(bytes.to_vec(), luau),
+        _ => Ok(LuaNil),
+    }
+}
+
+fn borrow_connection<'a, S: SocketStream>(connection_cell: &'a ConnectionCell<S>, function_name: &'static str) -> LuaResult<std::cell::RefMut<'a, Connection<S>>> {
+    match connection_cell.try_borrow_mut() {
+        Ok(connection) => Ok(connection),
+        Err(_) => wrap_err!("{}: WebSocket already borrowed", function_name),
+    }
+}
+
+fn create_connection<S: SocketStream + 'static>(luau: &Lua, socket: WebSocket<S>, protocol: Option<String>) -> LuaResult<LuaTable> {
+    let connection_cell: ConnectionCell<S> = Rc::new(RefCell::new(Connection { socket, closed: false }));
+
+    TableBuilder::create(luau)?
+        .with_value("protocol", protocol)?
+        .with_function("send", {
+            let connection_cell = Rc::clone(&connection_cell);
+            move | luau: &Lua, mut multivalue: LuaMultiValue | -> LuaEmptyResult {
+                let function_name = "WebSocket:send(data: string | JsonSerializableTable)";
+                pop_self(&mut multivalue, function_name)?;
+                let text = match multivalue.pop_front() {
+                    Some(LuaValue::String(text)) => match text.to_str() {
+                        Ok(text) => text.to_string(),
+                        Err(_) => {
+                            return wrap_err!("{}: text messages must be valid utf-8; use WebSocket:sendbytes for binary data", function_name);
+                        }
+                    },
+                    Some(LuaValue::Table(data)) => std_json::json_raw_encode(luau, LuaValue::Table(data))?,
+                    Some(other) => {
+                        return wrap_err!("{} expected data to be a string or JsonSerializableTable, got: {:?}", function_name, other);
+                    },
+                    None => {
+                        return wrap_err!("{} called without 'data' (expected string or JsonSerializableTable, got nothing)", function_name);
+                    }
+                };
+                borrow_connection(&connection_cell, function_name)?.send_message(Message::text(text), function_name)
+            }
+        })?
+        .with_function("sendbytes", {
+            let connection_cell = Rc::clone(&connection_cell);
+            move | _luau: &Lua, mut multivalue: LuaMultiValue | -> LuaEmptyResult {
+                let function_name = "WebSocket:sendbytes(data: buffer)";
+                pop_self(&mut multivalue, function_name)?;
+                let bytes = match multivalue.pop_front() {
+                    Some(LuaValue::Buffer(buffy)) => buffy.to_vec(),
+                    Some(other) => {
+                        return wrap_err!("{} expected data to be a buffer, got: {:?}", function_name, other);
+                    },
+                    None => {
+                        return wrap_err!("{} called without required argument 'data'", function_name);
+                    }
+                };
+                borrow_connection(&connection_cell, function_name)?.send_message(Message::binary(bytes), function_name)
+            }
+        })?
+        .with_function("read", {
+            let connection_cell = Rc::clone(&connection_cell);
+            move | luau: &Lua, _value: LuaMultiValue | -> LuaValueResult {
+                let function_name = "WebSocket:read()";
+                let message = borrow_connection(&connection_cell, function_name)?.read_message(false, function_name)?;
+                message_to_value(luau, message)
+            }
+        })?
+        .with_function("read_await", {
+            let connection_cell = Rc::clone(&connection_cell);
+            move | luau: &Lua, _value: LuaMultiValue | -> LuaValueResult {
+                let function_name = "WebSocket:read_await()";
+                let message = borrow_connection(&connection_cell, function_name)?.read_message(true, function_name)?;
+                message_to_value(luau, message)
+            }
+        })?
+        .with_function("connected", {
+            let connection_cell = Rc::clone(&connection_cell);
+            move | _luau: &Lua, _value: LuaMultiValue | -> LuaResult<bool> {
+                Ok(!borrow_connection(&connection_cell, "WebSocket:connected()")?.closed)
+            }
+        })?
+        .with_function("close", {
+            let connection_cell = Rc::clone(&connection_cell);
+            move | _luau: &Lua, mut multivalue: LuaMultiValue | -> LuaEmptyResult {
+                let function_name = "WebSocket:close(code: number?, reason: string?)";
+                pop_self(&mut multivalue, function_name)?;
+                let code = match multivalue.pop_front() {
+                    Some(LuaValue::Integer(code)) => Some(code),
+                    Some(LuaValue::Number(code)) if code.fract() == 0.0 => Some(code as i64),
+                    Some(LuaNil) | None => None,
+                    Some(other) => {
+                        return wrap_err!("{} expected code to be an integer or nil, got: {:?}", function_name, other);
+                    }
+                };
+                let reason = match multivalue.pop_front() {
+                    Some(LuaValue::String(reason)) => reason.to_string_lossy(),
+                    Some(LuaNil) | None => String::new(),
+                    Some(other) => {
+                        return wrap_err!("{} expected reason to be a string or nil, got: {:?}", function_name, other);
+                    }
+                };
+                let frame = match code {
+                    Some(code) => match u16::try_from(code) {
+                        Ok(code) => Some(CloseFrame { code: CloseCode::from(code), reason: reason.into() }),
+                        Err(_) => {
+                            return wrap_err!("{}: close code {} is out of range", function_name, code);
+                        }
+                    },
+                    None => Some(CloseFrame { code: CloseCode::Normal, reason: reason.into() }),
+                };
+                borrow_connection(&connection_cell, function_name)?.close(frame);
+                Ok(())
+            }
+        })?
+        .build_readonly()
+}
+
+fn websocket_connect(luau: &Lua, value: LuaValue) -> LuaValueResult {
+    let function_name = "websocket.connect(config: string | WebSocketConfig)";
+    let (url, config) = match value {
+        LuaValue::String(url) => (url.to_string_lossy(), None),
+        LuaValue::Table(config) => match config.raw_get("url")? {
+            LuaValue::String(url) => (url.to_string_lossy(), Some(config)),
+            other => {
+                return wrap_err!("{} expected WebSocketConfig.url to be a string, got: {:?}", function_name, other);
+            }
+        },
+        other => {
+            return wrap_err!("{} expected url (string) or WebSocketConfig table, got: {:?}", function_name, other);
+        }
+    };
+
+    let mut request = match url.as_str().into_client_request() {
+        Ok(request) => request,
+        Err(err) => {
+            return wrap_err!("{}: invalid WebSocket url '{}': {}", function_name, url, err);
+        }
+    };
+
+    let mut timeout: Option<Duration> = None;
+    if let Some(config) = config {
+        match config.raw_get("headers")? {
+            LuaValue::Table(headers) => {
+                for pair in headers.pairs::<LuaValue, LuaValue>() {
+                    let (name, value) = match pair? {
+                        (LuaValue::String(name), LuaValue::String(value)) => (name.to_string_lossy(), value.to_string_lossy()),
+                        (name, value) => {
+                            return wrap_err!("{} expected WebSocketConfig.headers to be a table of strings to strings, got {:?} = {:?}", function_name, name, value);
+                        }
+                    };
+                    match (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(&value)) {
+                        (Ok(name), Ok(value)) => {
+                            request.headers_mut().insert(name, value);
+                        },
+                        _ => {
+                            return wrap_err!("{}: invalid header '{}: {}'", function_name, name, value);
+                        }
+                    }
+                }
+            },
+            LuaNil => {},
+            other => {
+                return wrap_err!("{} expected WebSocketConfig.headers to be a table or nil, got: {:?}", function_name, other);
+            }
+        }
+        match config.raw_get("protocols")? {
+            LuaValue::Table(protocols) => {
+                let protocols = protocols.sequence_values::<String>().collect::<LuaResult<Vec<String>>>()?.join(", ");
+                match HeaderValue::from_str(&protocols) {
+                    Ok(protocols) => {
+                        request.headers_mut().insert("Sec-WebSocket-Protocol", protocols);
+                    },
+                    Err(_) => {
+                        return wrap_err!("{}: invalid WebSocketConfig.protocols: {}", function_name, protocols);
+                    }
+                }
+            },
+            LuaNil => {},
+            other => {
+                return wrap_err!("{} expected WebSocketConfig.protocols to be an array of strings or nil, got: {:?}", function_name, other);
+            }
+        }
+        timeout = match config.raw_get("timeout")? {
+            LuaValue::Integer(seconds) if seconds > 0 => Some(Duration::from_secs(seconds as u64)),
+            // the socket's timeout setters reject zero durations, which is what tiny ones round down to
+            LuaValue::Number(seconds) if seconds.is_finite() && seconds > 0.0 => match Duration::try_from_secs_f64(seconds) {
+                Ok(timeout) if !timeout.is_zero() => Some(timeout),
+                Ok(_) => {
+                    return wrap_err!("{}: WebSocketConfig.timeout of {} seconds is too small", function_name, seconds);
+                },
+                Err(err) => {
+                    return wrap_err!("{}: error creating Duration from WebSocketConfig.timeout of {} seconds: {}", function_name, seconds, err);
+                }
+            },
+            LuaNil => None,
+            other => {
+                return wrap_err!("{} expected WebSocketConfig.timeout to be a finite number (in seconds) greater than 0 or nil, got: {:?}", function_name, other);
+            }
+        };
+    }
+
+    let uri = request.uri();
+    let secure = match uri.scheme_str() {
+        Some("ws") => false,
+        Some("wss") => true,
+        other => {
+            return wrap_err!("{}: expected a ws:// or wss:// url, got scheme {:?}", function_name, other);
+        }
+    };
+    let host = match uri.host() {
+        Some(host) => host.trim_start_matches('[').trim_end_matches(']').to_string(),
+        None => {
+            return wrap_err!("{}: url '{}' is missing a host", function_name, url);
+        }
+    };
+    let port = uri.port_u16().unwrap_or(if secure { 443 } else { 80 });
+
+    let stream = connect_tcp(&host, port, timeout, function_name)?;
+    // the timeout covers the handshake too, but not reads after it
+    if let Err(err) = stream.set_read_timeout(timeout) {
+        return wrap_err!("{}: unable to set handshake timeout due to err: {}", function_name, err);
+    }
+    let (socket, response) = match tungstenite::client_tls_with_config(request, stream, None, None) {
+        Ok(connected) => connected,
+        Err(err) => {
+            return wrap_err!("{}: WebSocket handshake with '{}' failed: {}", function_name, url, err);
+        }
+    };
+    if let Some(stream) = socket.get_ref().tcp_stream() {
+        let _ = stream.set_read_timeout(None);
+    }
+    let protocol = response
+        .headers()
+        .get("Sec-WebSocket-Protocol")
+        .and_then(|protocol| protocol.to_str().ok())
+        .map(String::from);
+
+    ok_table(create_connection(luau, socket, protocol))
+}
+
+/// whether a request is asking to be upgraded to a WebSocket
+pub fn is_upgrade_request(request: &ParsedRequest) -> bool {
+    let has_token = |header: &str, token: &str| request
+        .header(header)
+        .is_some_and(|value| value.split(',').any(|t| t.trim().eq_ignore_ascii_case(token)));
+    request.method.eq_ignore_ascii_case("GET")
+        && has_token("connection", "upgrade")
+        && has_token("upgrade", "websocket")
+        && request.header("sec-websocket-key").is_some()
+}
+
+/**
+Upgrades a `server.serve` connection to a WebSocket and hands it to `on_connect`.
+
+The connection's closed once `on_connect` returns, so it never goes back to serving HTTP requests.
+`additional_headers` (from `ServeResponse.headers` and `.cookies`) go in the `101 Switching Protocols` response.
+*/
+pub fn accept(
+    luau: &Lua,
+    buf_reader: &mut BufReader<ServeConnection>,
+    stream: &mut ServeConnection,
+    request: &ParsedRequest,
+    additional_headers: &str,
+    on_connect: LuaFunction,
+) -> LuaEmptyResult {
+    let function_name = "server.serve WebSocket upgrade";
+    let key = request.header("sec-websocket-key").unwrap_or_default();
+    let response_head = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n{}\r\n",
+        derive_accept_key(key.as_bytes()), additional_headers
+    );
+    if let Err(err) = stream.write_all(response_head.as_bytes()).and_then(|_| stream.flush()) {
+        return wrap_err!("{}: unable to write handshake response due to err: {}", function_name, err);
+    }
+
+    // keep-alive timeouts don't apply anymore; websockets sit idle for as long as they want
+    let _ = stream.socket().set_read_timeout(None);
+    // frames the client sent right behind its handshake might already be sitting in our read buffer
+    let already_read = buf_reader.buffer().to_vec();
+    buf_reader.consume(already_read.len());
+    let socket = WebSocket::from_partially_read(stream.clone(), already_read, Role::Server, None);
+    // the handler picks a subprotocol by setting the Sec-WebSocket-Protocol header itself
+    let protocol = additional_headers
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("sec-websocket-protocol"))
+        .map(|(_, protocol)| protocol.trim().to_string());
+
+    let connection = create_connection(luau, socket, protocol)?;
+    let result = on_connect.call::<LuaValue>(connection.clone());
+    // close it for them if they didn't
+    let close: LuaFunction = connection.raw_get("close")?;
+    close.call::<()>(connection)?;
+    match result {
+        Ok(_) => Ok(()),
+        Err(err) => wrap_err!("server.serve: ServeResponse.websocket function errored: {}", err),
+    }
+}
+
+pub fn create(luau: &Lua) -> LuaResult<LuaTable> {
+    TableBuilder::create(luau)?
+        .with_function("connect", websocket_connect)?
+        .build_readonly()
+}
@@ -0,0 +1,185 @@
+//! Detached children (`SpawnOptions.detached`) that keep running after seal exits, their pid files,
+//! and `process.attach` for checking up on them (or stopping them) later.
+
+use crate::prelude::*;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+use mluau::prelude::*;
+
+/// starts the child in its own session (on Windows, without a console), so it doesn't get taken down
+/// along with seal's terminal
+pub fn detach(command: &mut Command) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // SAFETY: setsid is async-signal-safe
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const DETACHED_PROCESS: u32 = 0x0000_0008;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+    }
+}
+
+/// whether there's a running process with this pid; on Linux, exited processes nobody's reaped yet (zombies) don't count
+pub fn is_alive(pid: i64) -> bool {
+    #[cfg(unix)]
+    {
+        let Ok(pid) = libc::pid_t::try_from(pid) else {
+            return false;
+        };
+        // SAFETY: signal 0 only checks whether the process exists (and we're allowed to signal it)
+        let exists = unsafe { libc::kill(pid, 0) } == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM);
+        #[cfg(target_os = "linux")]
+        {
+            if exists
+                && let Ok(stat) = fs::read_to_string(format!("/proc/{}/stat", pid))
+                && let Some(name_end) = stat.rfind(')')
+            {
+                return stat[name_end + 1..].split_whitespace().next() != Some("Z");
+            }
+        }
+        exists
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = pid;
+        false
+    }
+}
+
+/// the pid in a pid file, or `None` if there isn't one
+fn read_pid_file(path: &Path, function_name: &'static str) -> LuaResult<Option<i64>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => match contents.trim().parse::<i64>() {
+            Ok(pid) if pid > 0 => Ok(Some(pid)),
+            _ => wrap_err!("{}: pid file '{}' doesn't contain a pid, got: {:?}", function_name, path.display(), contents),
+        },
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => wrap_err!("{}: unable to read pid file '{}' due to err: {}", function_name, path.display(), err),
+    }
+}
+
+/// errors if the process in the pid file is still running; stale (or garbled) pid files just get overwritten
+pub fn check_pid_file(path: &Path, function_name: &'static str) -> LuaEmptyResult {
+    match read_pid_file(path, function_name) {
+        Ok(Some(pid)) if is_alive(pid) => {
+            wrap_err!("{}: already running as pid {} (according to pid file '{}')", function_name, pid, path.display())
+        }
+        _ => Ok(()),
+    }
+}
+
+pub fn write_pid_file(path: &Path, pid: u32, function_name: &'static str) -> LuaEmptyResult {
+    match fs::write(path, format!("{}\n", pid)) {
+        Ok(()) => Ok(()),
+        Err(err) => wrap_err!("{}: unable to write pid file '{}' due to err: {}", function_name, path.display(), err),
+    }
+}
+
+/// polls until the process exits; false if it's still running after `timeout`
+#[cfg(unix)]
+fn wait_for_exit(pid: i64, timeout: Option<std::time::Duration>) -> bool {
+    use std::time::Instant;
+    // timeouts too far off to represent are as good as none
+    let give_up_at = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+    loop {
+        if !is_alive(pid) {
+            return true;
+        }
+        if let Some(give_up_at) = give_up_at
+            && Instant::now() >= give_up_at
+        {
+            return false;
+        }
+        std::thread::sleep(super::status::POLL_INTERVAL);
+    }
+}
+
+/// `process.attach(pid: number | string)`
+pub fn process_attach(luau: &Lua, value: LuaValue) -> LuaValueResult {
+    let function_name = "process.attach(pid: number | string)";
+    let pid = match value {
+        LuaValue::String(path) => {
+            let path = path.to_string_lossy();
+            match read_pid_file(Path::new(&path), function_name)? {
+                Some(pid) => pid,
+                None => {
+                    return wrap_err!("{}: pid file '{}' doesn't exist", function_name, path);
+                }
+            }
+        }
+        other => super::signals::pid_from_value(Some(other), function_name)?,
+    };
+
+    #[cfg(not(unix))]
+    {
+        let _ = luau;
+        wrap_err!("{}: can't attach to pid {}; process.attach isn't supported on Windows", function_name, pid)
+    }
+    #[cfg(unix)]
+    {
+        use super::signals;
+        use super::status;
+
+        TableBuilder::create(luau)?
+            .with_value("id", pid)?
+            .with_function("alive", move |_luau: &Lua, _value: LuaMultiValue| -> LuaValueResult {
+                Ok(LuaValue::Boolean(is_alive(pid)))
+            })?
+            .with_function("signal", move |_luau: &Lua, mut multivalue: LuaMultiValue| -> LuaEmptyResult {
+                let function_name = "AttachedProcess:signal(signal: string)";
+                pop_self(&mut multivalue, function_name)?;
+                let name = match multivalue.pop_front() {
+                    Some(LuaValue::String(name)) => name.to_string_lossy(),
+                    other => {
+                        return wrap_err!("{} expected signal to be a string like \"SIGTERM\", got: {:?}", function_name, other);
+                    }
+                };
+                signals::send_signal(pid, &name, function_name)
+            })?
+            .with_function("wait", move |_luau: &Lua, mut multivalue: LuaMultiValue| -> LuaValueResult {
+                let function_name = "AttachedProcess:wait(timeout: number?)";
+                pop_self(&mut multivalue, function_name)?;
+                let timeout = status::seconds_from_value(multivalue.pop_front().unwrap_or(LuaNil), "AttachedProcess:wait(timeout: number?): timeout")?;
+                Ok(LuaValue::Boolean(wait_for_exit(pid, timeout)))
+            })?
+            .with_function("kill", move |_luau: &Lua, mut multivalue: LuaMultiValue| -> LuaEmptyResult {
+                let function_name = "AttachedProcess:kill(grace_period: number?)";
+                pop_self(&mut multivalue, function_name)?;
+                let grace_period = status::seconds_from_value(multivalue.pop_front().unwrap_or(LuaNil), "AttachedProcess:kill(grace_period: number?): grace_period")?;
+                // the process can exit on its own at any point in here, which is fine; it's what we wanted anyway
+                if let Some(grace_period) = grace_period
+                    && !grace_period.is_zero()
+                {
+                    if let Err(err) = signals::send_signal(pid, "SIGTERM", function_name)
+                        && is_alive(pid)
+                    {
+                        return Err(err);
+                    }
+                    if wait_for_exit(pid, Some(grace_period)) {
+                        return Ok(());
+                    }
+                }
+                match signals::send_signal(pid, "SIGKILL", function_name) {
+                    Err(err) if is_alive(pid) => Err(err),
+                    _ => Ok(()),
+                }
+            })?
+            .build_readonly()
+            .map(LuaValue::Table)
+    }
+}
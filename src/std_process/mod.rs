@@ -1,16 +1,26 @@
 use core::str;
 use std::cell::RefCell;
 use std::fmt::Debug;
-use std::io::{self, Write};
+use std::fs::File;
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
 use std::process::{self, Command, Output, Stdio};
 use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::{prelude::*, std_err};
-use crate::std_env;
+use crate::{std_env, std_task};
 use mluau::prelude::*;
 
+mod detached;
+mod pipeline;
+#[cfg(unix)]
+mod pty;
+mod signals;
+mod status;
 mod stream;
+mod system;
 use stream::{Stream, TruncateSide};
 
 #[derive(Debug)]
@@ -65,6 +75,87 @@ impl Shell {
     }
 }
 
+/// What gets fed to the child's stdin
+#[derive(Debug)]
+enum StdinSource {
+    /// `process.spawn`'s default; write to it with `ChildProcess.stdin:write`
+    Pipe,
+    Inherit,
+    /// `process.run`'s default
+    Null,
+    /// written to stdin all at once (from a separate thread so the child can't deadlock on a full pipe), then stdin's closed
+    Data(Vec<u8>),
+}
+
+impl StdinSource {
+    fn from_value(value: LuaValue) -> LuaResult<Option<Self>> {
+        match value {
+            LuaValue::String(data) => Ok(Some(match &*data.as_bytes() {
+                b"inherit" => StdinSource::Inherit,
+                b"null" => StdinSource::Null,
+                data => StdinSource::Data(data.to_vec()),
+            })),
+            LuaValue::Buffer(data) => Ok(Some(StdinSource::Data(data.to_vec()))),
+            LuaNil => Ok(None),
+            other => {
+                wrap_err!("SpawnOptions/RunOptions.stdin expected to be a string or buffer (to write to stdin), \"inherit\", \"null\", or nil, got: {:?}", other)
+            }
+        }
+    }
+
+    fn stdio(&self) -> Stdio {
+        match self {
+            StdinSource::Pipe | StdinSource::Data(_) => Stdio::piped(),
+            StdinSource::Inherit => Stdio::inherit(),
+            StdinSource::Null => Stdio::null(),
+        }
+    }
+}
+
+/// Where the child's stdout or stderr goes
+#[derive(Debug)]
+enum OutputTarget {
+    /// the default; captured into `RunResult.stdout` or streamed through `ChildProcess.stdout`
+    Pipe,
+    Inherit,
+    Null,
+    /// created (or truncated) right before the child's spawned, so bad options don't clobber the file
+    File(PathBuf),
+}
+
+impl OutputTarget {
+    fn from_value(value: LuaValue, field: &'static str) -> LuaResult<Self> {
+        match value {
+            LuaValue::String(target) => match target.to_string_lossy().as_str() {
+                "pipe" => Ok(OutputTarget::Pipe),
+                "inherit" => Ok(OutputTarget::Inherit),
+                "null" => Ok(OutputTarget::Null),
+                path => Ok(OutputTarget::File(PathBuf::from(path))),
+            },
+            LuaNil => Ok(OutputTarget::Pipe),
+            other => {
+                wrap_err!("SpawnOptions/RunOptions.{} expected to be \"pipe\", \"inherit\", \"null\", a file path, or nil, got: {:?}", field, other)
+            }
+        }
+    }
+
+    /// opens the file for `OutputTarget::File`; `field` is "stdout" or "stderr", for the error
+    fn stdio(self, field: &'static str) -> io::Result<Stdio> {
+        Ok(match self {
+            OutputTarget::Pipe => Stdio::piped(),
+            OutputTarget::Inherit => Stdio::inherit(),
+            OutputTarget::Null => Stdio::null(),
+            OutputTarget::File(path) => match File::create(&path) {
+                Ok(file) => Stdio::from(file),
+                Err(err) => {
+                    let message = format!("unable to open '{}' for writing (as {}): {}", path.display(), field, err);
+                    return Err(io::Error::new(err.kind(), message));
+                }
+            },
+        })
+    }
+}
+
 /// Represents process lib's `RunOptions` and `SpawnOptions` and I don't feel like making this an enum
 #[derive(Debug)]
 struct ProcessOptions {
@@ -72,6 +163,16 @@ struct ProcessOptions {
     args: Option<Vec<String>>,
     shell: Option<Shell>,
     cwd: Option<PathBuf>,
+    /// variables to set (`Some`) or unset (`None`) on top of seal's environment (or an empty one, with `clear_env`)
+    env: Vec<(String, Option<String>)>,
+    clear_env: bool,
+    /// `None` means whatever the default is for `process.run` or `process.spawn`
+    stdin: Option<StdinSource>,
+    stdout: OutputTarget,
+    stderr: OutputTarget,
+    /// `RunOptions.timeout`; `process.spawn` uses `ChildProcess:wait(timeout)` instead
+    timeout: Option<Duration>,
+    grace_period: Option<Duration>,
     stdout_capacity: Option<usize>,
     stderr_capacity: Option<usize>,
     stdout_truncate: Option<TruncateSide>,
@@ -136,6 +237,47 @@ impl ProcessOptions {
             }
         };
 
+        let env = match run_options.raw_get("env")? {
+            LuaValue::Table(env_table) => {
+                let mut env = Vec::new();
+                for pair in env_table.pairs::<LuaValue, LuaValue>() {
+                    let (key, value) = match pair? {
+                        (LuaValue::String(key), LuaValue::String(value)) => (key.to_string_lossy(), Some(value.to_string_lossy())),
+                        (LuaValue::String(key), LuaValue::Integer(n)) => (key.to_string_lossy(), Some(n.to_string())),
+                        (LuaValue::String(key), LuaValue::Number(f)) => (key.to_string_lossy(), Some(f.to_string())),
+                        // lets users unset a variable without clearing the whole environment
+                        (LuaValue::String(key), LuaValue::Boolean(false)) => (key.to_string_lossy(), None),
+                        (key, value) => {
+                            return wrap_err!("SpawnOptions/RunOptions.env expected to be a table of variable names to strings (or false to unset them), got {:?} = {:?}", key, value);
+                        }
+                    };
+                    if key.is_empty() || key.contains('=') || key.contains('\0') {
+                        return wrap_err!("SpawnOptions/RunOptions.env: invalid environment variable name {:?}", key);
+                    }
+                    env.push((key, value));
+                }
+                env
+            }
+            LuaNil => Vec::new(),
+            other => {
+                return wrap_err!("SpawnOptions/RunOptions.env expected to be a table or nil, got: {:?}", other);
+            }
+        };
+
+        let clear_env = match run_options.raw_get("clear_env")? {
+            LuaValue::Boolean(clear_env) => clear_env,
+            LuaNil => false,
+            other => {
+                return wrap_err!("SpawnOptions/RunOptions.clear_env expected to be a boolean or nil, got: {:?}", other);
+            }
+        };
+
+        let stdin = StdinSource::from_value(run_options.raw_get("stdin")?)?;
+        let stdout = OutputTarget::from_value(run_options.raw_get("stdout")?, "stdout")?;
+        let stderr = OutputTarget::from_value(run_options.raw_get("stderr")?, "stderr")?;
+        let timeout = status::seconds_from_value(run_options.raw_get("timeout")?, "RunOptions.timeout")?;
+        let grace_period = status::seconds_from_value(run_options.raw_get("grace_period")?, "RunOptions.grace_period")?;
+
         let (stdout_capacity, stderr_capacity, stdout_truncate, stderr_truncate) = match run_options.raw_get("stream")? {
             LuaValue::Table(stream_table) => (
                 match stream_table.raw_get("stdout_capacity")? {
@@ -208,6 +350,13 @@ impl ProcessOptions {
             args,
             shell,
             cwd,
+            env,
+            clear_env,
+            stdin,
+            stdout,
+            stderr,
+            timeout,
+            grace_period,
             stdout_capacity: Some(stdout_capacity),
             stderr_capacity: Some(stderr_capacity),
             stdout_truncate: Some(stdout_truncate),
@@ -216,6 +365,67 @@ impl ProcessOptions {
     }
 }
 
+impl ProcessOptions {
+    /// the `Command` to run, with everything but stdio set up
+    fn command(&self) -> Command {
+        let mut command = match &self.shell {
+            Some(shell) => {
+                let mut command = Command::new(shell.program_name());
+                command.args(shell.get_switches()).arg(&self.program);
+                if let Some(args) = &self.args {
+                    command.arg(args.join(" "));
+                }
+                command
+            }
+            None => {
+                let mut command = Command::new(&self.program);
+                if let Some(args) = &self.args {
+                    command.args(args);
+                }
+                command
+            }
+        };
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+        if self.clear_env {
+            command.env_clear();
+        }
+        for (key, value) in &self.env {
+            match value {
+                Some(value) => command.env(key, value),
+                None => command.env_remove(key),
+            };
+        }
+        command
+    }
+}
+
+/// writes `StdinSource::Data` to the child from another thread, so a child that fills up its stdout pipe
+/// before reading all of stdin can't deadlock us
+fn write_stdin_data(stdin: Option<process::ChildStdin>, data: Vec<u8>) -> Option<thread::JoinHandle<()>> {
+    let mut stdin = stdin?;
+    Some(thread::spawn(move || {
+        // the child doesn't have to read all of it; a broken pipe just means it exited (or closed stdin) early
+        let _ = stdin.write_all(&data);
+    }))
+}
+
+/// reads a child's output on its own thread, so a child writing lots to stderr (or to stdout, in a pipeline)
+/// can't block on a full pipe while we're waiting on something else
+fn read_to_end(stream: Option<impl Read + Send + 'static>) -> Option<thread::JoinHandle<Vec<u8>>> {
+    let mut stream = stream?;
+    Some(thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = stream.read_to_end(&mut bytes);
+        bytes
+    }))
+}
+
+fn join_output(reader: Option<thread::JoinHandle<Vec<u8>>>) -> Vec<u8> {
+    reader.and_then(|reader| reader.join().ok()).unwrap_or_default()
+}
+
 fn run_result_unwrap_or(luau: &Lua, mut multivalue: LuaMultiValue) -> LuaValueResult {
     let function_name = "RunResult:unwrap_or(default: string | (result: RunResult) -> string)";
     let run_result = match multivalue.pop_front() {
@@ -290,13 +500,18 @@ fn trim_end_or_return(vec: &[u8]) -> &[u8] {
     }
 }
 
-fn create_run_result_table(luau: &Lua, output: Output) -> LuaValueResult {
+fn create_run_result_table(luau: &Lua, output: Output, timed_out: bool) -> LuaValueResult {
+    ok_table(run_result_builder(luau, output, timed_out)?.build_readonly())
+}
+
+/// everything in a `RunResult`, left unbuilt so `process.pipeline` can tack its `stages` on
+fn run_result_builder(luau: &Lua, output: Output, timed_out: bool) -> LuaResult<TableBuilder<'_>> {
     let ok = output.status.success();
     let stdout = output.stdout.clone();
     let stderr = output.stderr.clone();
 
-    let run_result = TableBuilder::create(luau)?
-        .with_value("ok", ok)?
+    status::with_exit_status(TableBuilder::create(luau)?, &output.status)?
+        .with_value("timed_out", timed_out)?
         .with_value("out", {
             if ok {
                 let s = trim_end_or_return(&stdout);
@@ -325,64 +540,62 @@ fn create_run_result_table(luau: &Lua, output: Output) -> LuaValueResult {
                 }
             }
         })?
-        .with_function("unwrap_or", run_result_unwrap_or)?
-        .build_readonly();
-
-    ok_table(run_result)
+        .with_function("unwrap_or", run_result_unwrap_or)
 }
 
-fn run_command(options: ProcessOptions) -> io::Result<Output> {
-    let shell_switches = match options.shell {
-        Some(ref shell) => shell.get_switches(),
-        None => Vec::new(),
+/// runs the command to completion, returning its output and whether it had to be killed for running past `RunOptions.timeout`
+fn run_command(options: ProcessOptions) -> io::Result<(Output, bool)> {
+    let mut command = options.command();
+    if options.timeout.is_some() {
+        status::lead_process_group(&mut command);
+    }
+    let stdin = options.stdin.unwrap_or(StdinSource::Null);
+    command.stdin(stdin.stdio()).stdout(options.stdout.stdio("stdout")?).stderr(options.stderr.stdio("stderr")?);
+
+    let mut child = command.spawn()?;
+    let stdin_writer = match stdin {
+        StdinSource::Data(data) => write_stdin_data(child.stdin.take(), data),
+        _ => None,
     };
+    let stdout_reader = read_to_end(child.stdout.take());
+    let stderr_reader = read_to_end(child.stderr.take());
 
-    if let Some(ref shell) = options.shell {
-        let mut command = Command::new(shell.program_name());
-        command.args(shell_switches);
-        command.arg(options.program);
-        if let Some(args) = options.args {
-            command.arg(args.join(" "));
-        }
-        if let Some(cwd) = options.cwd {
-            command.current_dir(&cwd);
-        }
-        command.output()
-    } else {
-        let mut command = Command::new(&options.program);
-        if let Some(args) = options.args {
-            command.args(args);
-        }
-        if let Some(cwd) = options.cwd {
-            command.current_dir(&cwd);
-        }
-        command.output()
+    let mut waiter = status::Waiter::new(options.timeout, options.grace_period.unwrap_or(status::DEFAULT_GRACE_PERIOD));
+    let status = waiter.wait(&mut child)?;
+    // if the child spawned its own children that inherited stdout/stderr, we wait on them too
+    let output = Output {
+        status,
+        stdout: join_output(stdout_reader),
+        stderr: join_output(stderr_reader),
+    };
+    if let Some(stdin_writer) = stdin_writer {
+        let _ = stdin_writer.join();
     }
+    Ok((output, waiter.timed_out))
 }
 
-fn process_run(luau: &Lua, run_options: LuaValue) -> LuaValueResult {
-    let function_name = "process.run(options: RunOptions)";
-    let options = match run_options {
-        LuaValue::Table(run_options) => ProcessOptions::from_table(luau, run_options)?,
+fn run_options_from_value(luau: &Lua, run_options: LuaValue, function_name: &'static str) -> LuaResult<ProcessOptions> {
+    match run_options {
+        LuaValue::Table(run_options) => ProcessOptions::from_table(luau, run_options),
         LuaValue::Nil => {
-            return wrap_err!(
+            wrap_err!(
                 "{} expected RunOptions table of type {{ program: string, args: {{string}}?, shell: string?, cwd: string? }}, got nil.",
                 function_name
-            );
+            )
         }
         other => {
-            return wrap_err!(
+            wrap_err!(
                 "{} expected RunOptions table of type {{ program: string, args: {{string}}?, shell: string?, cwd: string? }}, got: {:#?}",
                 function_name,
                 other
-            );
+            )
         }
-    };
-
-    let program_to_run = options.program.clone();
+    }
+}
 
-    match run_command(options) {
-        Ok(output) => create_run_result_table(luau, output),
+fn run_result(luau: &Lua, result: io::Result<(Output, bool)>, program_to_run: &str, function_name: &'static str) -> LuaValueResult {
+    match result {
+        Ok((output, timed_out)) => create_run_result_table(luau, output, timed_out),
         Err(err) => {
             // we want to throw an error if the program was unable to spawn at all
             // this is because when a user calls process.run/shell, they expect their program to actually run
@@ -392,8 +605,24 @@ fn process_run(luau: &Lua, run_options: LuaValue) -> LuaValueResult {
     }
 }
 
-fn process_shell(luau: &Lua, shell_command: LuaValue) -> LuaValueResult {
-    let function_name = "process.shell(command: string)";
+fn process_run(luau: &Lua, run_options: LuaValue) -> LuaValueResult {
+    let function_name = "process.run(options: RunOptions)";
+    let options = run_options_from_value(luau, run_options, function_name)?;
+    let program_to_run = options.program.clone();
+    run_result(luau, run_command(options), &program_to_run, function_name)
+}
+
+/// `process.run` for @std/task tasks; the program runs (and gets waited on) in the background while other tasks run
+fn process_run_start(luau: &Lua, run_options: LuaValue) -> LuaResult<LuaFunction> {
+    let function_name = "process.run(options: RunOptions)";
+    let options = run_options_from_value(luau, run_options, function_name)?;
+    let program_to_run = options.program.clone();
+    std_task::poll_background(luau, move || run_command(options), move |luau, result| {
+        run_result(luau, result, &program_to_run, function_name)
+    })
+}
+
+fn shell_options(shell_command: LuaValue, function_name: &'static str) -> LuaResult<(ProcessOptions, String)> {
     let shell_name = std_env::get_current_shell();
     let shell_command = match shell_command {
         LuaValue::String(command) => command.to_str()?.to_string(),
@@ -403,28 +632,55 @@ fn process_shell(luau: &Lua, shell_command: LuaValue) -> LuaValueResult {
     };
 
     let run_options = ProcessOptions {
-        program: shell_command.clone(),
+        program: shell_command,
         args: None,
         shell: Some(Shell::from(shell_name.clone())),
         cwd: None,
+        env: Vec::new(),
+        clear_env: false,
+        stdin: None,
+        stdout: OutputTarget::Pipe,
+        stderr: OutputTarget::Pipe,
+        timeout: None,
+        grace_period: None,
         stdout_capacity: None,
         stderr_capacity: None,
         stdout_truncate: None,
         stderr_truncate: None,
     };
+    Ok((run_options, shell_name))
+}
 
-    match run_command(run_options) {
-        Ok(output) => create_run_result_table(luau, output),
+fn shell_result(luau: &Lua, result: io::Result<(Output, bool)>, shell_command: &str, shell_name: &str, function_name: &'static str) -> LuaValueResult {
+    match result {
+        Ok((output, timed_out)) => create_run_result_table(luau, output, timed_out),
         Err(err) => {
             wrap_err!("{} unable to run shell command '{}' with shell '{}' because of err: {}", function_name, shell_command, shell_name, err)
         }
     }
 }
 
+fn process_shell(luau: &Lua, shell_command: LuaValue) -> LuaValueResult {
+    let function_name = "process.shell(command: string)";
+    let (run_options, shell_name) = shell_options(shell_command, function_name)?;
+    let shell_command = run_options.program.clone();
+    shell_result(luau, run_command(run_options), &shell_command, &shell_name, function_name)
+}
+
+/// `process.shell` for @std/task tasks
+fn process_shell_start(luau: &Lua, shell_command: LuaValue) -> LuaResult<LuaFunction> {
+    let function_name = "process.shell(command: string)";
+    let (run_options, shell_name) = shell_options(shell_command, function_name)?;
+    let shell_command = run_options.program.clone();
+    std_task::poll_background(luau, move || run_command(run_options), move |luau, result| {
+        shell_result(luau, result, &shell_command, &shell_name, function_name)
+    })
+}
+
 fn process_spawn(luau: &Lua, spawn_options: LuaValue) -> LuaValueResult {
     let function_name = "process.spawn(options: SpawnOptions)";
-    let options = match spawn_options {
-        LuaValue::Table(run_options) => ProcessOptions::from_table(luau, run_options)?,
+    let spawn_options = match spawn_options {
+        LuaValue::Table(spawn_options) => spawn_options,
         LuaValue::Nil => {
             return wrap_err!(
                 "{} expected a RunOptions table of type {{ program: string, args: {{string}}?, shell: string? }}, got nil",
@@ -439,87 +695,187 @@ fn process_spawn(luau: &Lua, spawn_options: LuaValue) -> LuaValueResult {
             );
         }
     };
-
-    let shell_switches = match options.shell {
-        Some(ref shell) => shell.get_switches(),
-        None => Vec::new(),
+    #[cfg(unix)]
+    let pty_size = pty::PtySize::from_value(spawn_options.raw_get("pty")?)?;
+    #[cfg(not(unix))]
+    if !matches!(spawn_options.raw_get("pty")?, LuaNil | LuaValue::Boolean(false)) {
+        return wrap_err!("{}: SpawnOptions.pty isn't supported on Windows", function_name);
+    }
+    let detached = match spawn_options.raw_get("detached")? {
+        LuaValue::Boolean(detached) => detached,
+        LuaNil => false,
+        other => {
+            return wrap_err!("{}: SpawnOptions.detached expected to be a boolean or nil, got: {:?}", function_name, other);
+        }
+    };
+    let pid_file = match spawn_options.raw_get("pid_file")? {
+        LuaValue::String(path) => Some(PathBuf::from(path.to_string_lossy())),
+        LuaNil => None,
+        other => {
+            return wrap_err!("{}: SpawnOptions.pid_file expected to be a string or nil, got: {:?}", function_name, other);
+        }
     };
+    // detached children outlive seal, so they can't write to pipes seal reads from; their output goes nowhere unless it's sent to a file
+    let (stdout_set, stderr_set) = (!spawn_options.raw_get::<LuaValue>("stdout")?.is_nil(), !spawn_options.raw_get::<LuaValue>("stderr")?.is_nil());
+    let mut options = ProcessOptions::from_table(luau, spawn_options)?;
+    if detached {
+        #[cfg(unix)]
+        if pty_size.is_some() {
+            return wrap_err!("{}: detached children can't have a pty", function_name);
+        }
+        if !stdout_set {
+            options.stdout = OutputTarget::Null;
+        }
+        if !stderr_set {
+            options.stderr = OutputTarget::Null;
+        }
+        if matches!(options.stdout, OutputTarget::Pipe) || matches!(options.stderr, OutputTarget::Pipe) {
+            return wrap_err!("{}: detached children can't pipe stdout or stderr back to seal; send them to a file instead", function_name);
+        }
+        options.stdin.get_or_insert(StdinSource::Null);
+    }
+    if let Some(pid_file) = &pid_file {
+        detached::check_pid_file(pid_file, function_name)?;
+    }
 
-    let mut child = {
-        match if let Some(ref shell) = options.shell {
-            let mut command = Command::new(shell.program_name());
-            command.args(shell_switches).arg(options.program);
-            if let Some(args) = options.args {
-                command.arg(args.join(" "));
-            }
-            command.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()
-        } else {
-            let mut command = Command::new(options.program);
-            if let Some(args) = options.args {
-                command.args(args);
-            }
-            command.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()
-        } {
-            Ok(child) => child,
+    let mut command = options.command();
+    if detached {
+        detached::detach(&mut command);
+    }
+    #[cfg(unix)]
+    if pty_size.is_some() {
+        if options.stdin.is_some() || !matches!(options.stdout, OutputTarget::Pipe) || !matches!(options.stderr, OutputTarget::Pipe) {
+            return wrap_err!("{}: can't set stdin, stdout, or stderr along with pty; the pty is all three", function_name);
+        }
+        // most programs only bother with colors (and cursor movement) if they know what kind of terminal they're on
+        let term_set = options.env.iter().any(|(key, _)| key == "TERM");
+        if !term_set && (options.clear_env || std::env::var_os("TERM").is_none()) {
+            command.env("TERM", "xterm-256color");
+        }
+    }
+    let stdin_source = options.stdin.unwrap_or(StdinSource::Pipe);
+    let (stdout, stderr) = match (options.stdout.stdio("stdout"), options.stderr.stdio("stderr")) {
+        (Ok(stdout), Ok(stderr)) => (stdout, stderr),
+        (Err(err), _) | (_, Err(err)) => {
+            return wrap_err!("{}: {}", function_name, err);
+        }
+    };
+    command.stdin(stdin_source.stdio()).stdout(stdout).stderr(stderr);
+    #[cfg(unix)]
+    let pty = match pty_size {
+        // replaces the stdio we just set up with the terminal side of the pty
+        Some(size) => match pty::Pty::attach(&mut command, size) {
+            Ok(pty) => Some(pty),
             Err(err) => {
-                return wrap_err!("process.spawn failed to execute process: {}", err);
+                return wrap_err!("{}: unable to open a pseudo-terminal due to err: {}", function_name, err);
             }
+        },
+        None => None,
+    };
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(err) => {
+            return wrap_err!("process.spawn failed to execute process: {}", err);
         }
     };
+    // our copy of the pty's terminal side has to be closed, otherwise reading from the pty never hits EOF
+    drop(command);
 
     let child_id = child.id();
-    let stdout = child.stdout.take().unwrap();
-    let stderr = child.stderr.take().unwrap();
-    let mut stdin = child.stdin.take().unwrap();
+    if let Some(pid_file) = &pid_file
+        && let Err(err) = detached::write_pid_file(pid_file, child_id, function_name)
+    {
+        let _ = child.kill();
+        return Err(err);
+    }
+    // streams that aren't piped (inherit, null, or a file) don't get a handle
+    #[allow(unused_mut, reason = "needs to be mut on unix")]
+    let mut stdout: Option<Box<dyn Read + Send>> = child.stdout.take().map(|stdout| Box::new(stdout) as _);
+    let stderr = child.stderr.take();
+    #[allow(unused_mut, reason = "needs to be mut on unix")]
+    let mut stdin: Option<Box<dyn Write>> = match stdin_source {
+        StdinSource::Data(data) => {
+            // nobody waits on the writer; it finishes (or hits a broken pipe) on its own
+            write_stdin_data(child.stdin.take(), data);
+            None
+        }
+        _ => child.stdin.take().map(|stdin| Box::new(stdin) as _),
+    };
+    #[cfg(unix)]
+    if let Some(pty) = &pty {
+        match (pty.reader(), pty.writer()) {
+            (Ok(reader), Ok(writer)) => {
+                stdout = Some(Box::new(reader));
+                stdin = Some(Box::new(writer));
+            }
+            (Err(err), _) | (_, Err(err)) => {
+                let _ = child.kill();
+                return wrap_err!("{}: unable to clone pseudo-terminal handle due to err: {}", function_name, err);
+            }
+        }
+    }
 
     let child_cell = Rc::new(RefCell::new(child));
 
     let child_process_handle = {
-        let stdout_stream = Stream::new(
-            function_name,
-            stdout,
-            stream::StreamType::Stdout,
-            options.stdout_capacity.unwrap_or(2048),
-            options.stdout_truncate.unwrap_or(TruncateSide::Front),
-        )?;
-        let stdout_cell = Rc::new(RefCell::new(stdout_stream));
-        let stdout_handle = Stream::create_handle(stdout_cell, luau)?;
-
-        let stderr_stream = Stream::new(
-            function_name,
-            stderr,
-            stream::StreamType::Stderr,
-            options.stderr_capacity.unwrap_or(1024),
-            options.stderr_truncate.unwrap_or(TruncateSide::Front),
-        )?;
-        let stderr_cell = Rc::new(RefCell::new(stderr_stream));
-        let stderr_handle = Stream::create_handle(stderr_cell, luau)?;
-
-        let stdin_handle = TableBuilder::create(luau)?
-            .with_function_mut("write", {
-                move |luau: &Lua, mut multivalue: LuaMultiValue| -> LuaValueResult {
-                    let function_name = "child.stdin:write(data: string)";
-                    pop_self(&mut multivalue, function_name)?;
-                    let data_to_write = match multivalue.pop_front() {
-                        Some(LuaValue::String(data)) => data.as_bytes().to_vec(),
-                        Some(LuaValue::Buffer(b)) => b.to_vec(),
-                        Some(other) => {
-                            return wrap_err!("{} expected data to be a string or buffer, got: {:?}", function_name, other);
-                        }
-                        None => {
-                            return wrap_err!("{} expected data to be string or buffer, unexpectedly got nothing (not even nil)", function_name);
-                        }
-                    };
+        let stdout_handle = match stdout {
+            Some(stdout) => {
+                let stdout_stream = Stream::new(
+                    function_name,
+                    stdout,
+                    stream::StreamType::Stdout,
+                    options.stdout_capacity.unwrap_or(2048),
+                    options.stdout_truncate.unwrap_or(TruncateSide::Front),
+                )?;
+                let stdout_cell = Rc::new(RefCell::new(stdout_stream));
+                Some(Stream::create_handle(stdout_cell, luau)?)
+            }
+            None => None,
+        };
 
-                    match stdin.write_all(&data_to_write) {
-                        Ok(_) => Ok(LuaNil),
-                        Err(err) => {
-                            std_err::WrappedError::from_message(format!("{} can't write to stdin due to err: {}", function_name, err)).get_userdata(luau)
+        let stderr_handle = match stderr {
+            Some(stderr) => {
+                let stderr_stream = Stream::new(
+                    function_name,
+                    stderr,
+                    stream::StreamType::Stderr,
+                    options.stderr_capacity.unwrap_or(1024),
+                    options.stderr_truncate.unwrap_or(TruncateSide::Front),
+                )?;
+                let stderr_cell = Rc::new(RefCell::new(stderr_stream));
+                Some(Stream::create_handle(stderr_cell, luau)?)
+            }
+            None => None,
+        };
+
+        let stdin_handle = match stdin {
+            Some(mut stdin) => Some(TableBuilder::create(luau)?
+                .with_function_mut("write", {
+                    move |luau: &Lua, mut multivalue: LuaMultiValue| -> LuaValueResult {
+                        let function_name = "child.stdin:write(data: string)";
+                        pop_self(&mut multivalue, function_name)?;
+                        let data_to_write = match multivalue.pop_front() {
+                            Some(LuaValue::String(data)) => data.as_bytes().to_vec(),
+                            Some(LuaValue::Buffer(b)) => b.to_vec(),
+                            Some(other) => {
+                                return wrap_err!("{} expected data to be a string or buffer, got: {:?}", function_name, other);
+                            }
+                            None => {
+                                return wrap_err!("{} expected data to be string or buffer, unexpectedly got nothing (not even nil)", function_name);
+                            }
+                        };
+
+                        match stdin.write_all(&data_to_write) {
+                            Ok(_) => Ok(LuaNil),
+                            Err(err) => {
+                                std_err::WrappedError::from_message(format!("{} can't write to stdin due to err: {}", function_name, err)).get_userdata(luau)
+                            }
                         }
                     }
-                }
-            })?
-            .build_readonly()?;
+                })?
+                .build_readonly()?),
+            None => None,
+        };
 
         TableBuilder::create(luau)?
             .with_value("id", child_id)?
@@ -541,15 +897,72 @@ fn process_spawn(luau: &Lua, spawn_options: LuaValue) -> LuaValueResult {
                     }
                 }
             })?
+            .with_value("wait", {
+                let blocking = luau.create_function({
+                    let child_cell = Rc::clone(&child_cell);
+                    move |luau: &Lua, mut multivalue: LuaMultiValue| -> LuaValueResult {
+                        let function_name = "ChildProcess:wait(timeout: number?)";
+                        pop_self(&mut multivalue, function_name)?;
+                        let timeout = status::seconds_from_value(multivalue.pop_front().unwrap_or(LuaNil), "ChildProcess:wait(timeout: number?): timeout")?;
+                        let mut child = match child_cell.try_borrow_mut() {
+                            Ok(child) => child,
+                            Err(_) => {
+                                return wrap_err!("{}: child already borrowed", function_name);
+                            }
+                        };
+                        match status::wait_timeout(&mut child, timeout) {
+                            Ok(Some(status)) => ok_table(status::exit_status_table(luau, &status)),
+                            Ok(None) => Ok(LuaNil),
+                            Err(err) => wrap_err!("{}: unable to wait on child due to err: {}", function_name, err),
+                        }
+                    }
+                })?;
+                // in @std/task tasks, other tasks run while we wait
+                let start = luau.create_function({
+                    let child_cell = Rc::clone(&child_cell);
+                    move |luau: &Lua, mut multivalue: LuaMultiValue| -> LuaResult<LuaFunction> {
+                        let function_name = "ChildProcess:wait(timeout: number?)";
+                        pop_self(&mut multivalue, function_name)?;
+                        let timeout = status::seconds_from_value(multivalue.pop_front().unwrap_or(LuaNil), "ChildProcess:wait(timeout: number?): timeout")?;
+                        let give_up_at = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+                        let child_cell = Rc::clone(&child_cell);
+                        std_task::poll_fn(luau, move |luau| {
+                            let mut child = match child_cell.try_borrow_mut() {
+                                Ok(child) => child,
+                                Err(_) => {
+                                    return wrap_err!("{}: child already borrowed", function_name);
+                                }
+                            };
+                            match child.try_wait() {
+                                Ok(Some(status)) => Ok(Some(LuaMultiValue::from_vec(vec![ok_table(status::exit_status_table(luau, &status))?]))),
+                                Ok(None) if give_up_at.is_some_and(|give_up_at| Instant::now() >= give_up_at) => {
+                                    Ok(Some(LuaMultiValue::from_vec(vec![LuaNil])))
+                                },
+                                Ok(None) => Ok(None),
+                                Err(err) => wrap_err!("{}: unable to wait on child due to err: {}", function_name, err),
+                            }
+                        })
+                    }
+                })?;
+                std_task::yielding(luau, blocking, start)?
+            })?
             .with_function("kill", {
-                let function_name = "ChildProcess:kill()";
                 let child_cell = Rc::clone(&child_cell);
-                move |_luau: &Lua, _value: LuaValue| -> LuaEmptyResult {
+                move |_luau: &Lua, mut multivalue: LuaMultiValue| -> LuaEmptyResult {
+                    let function_name = "ChildProcess:kill(grace_period: number?)";
+                    pop_self(&mut multivalue, function_name)?;
+                    let grace_period = status::seconds_from_value(multivalue.pop_front().unwrap_or(LuaNil), "ChildProcess:kill(grace_period: number?): grace_period")?;
                     match child_cell.try_borrow_mut() {
-                        Ok(ref mut child) => match child.kill() {
-                            Ok(_) => Ok(()),
-                            Err(err) => {
-                                wrap_err!("{} could not murder child due to err: {}", function_name, err)
+                        Ok(ref mut child) => {
+                            let result = match grace_period {
+                                Some(grace_period) => status::terminate(child, grace_period).map(|_| ()),
+                                None => child.kill(),
+                            };
+                            match result {
+                                Ok(_) => Ok(()),
+                                Err(err) => {
+                                    wrap_err!("{} could not murder child due to err: {}", function_name, err)
+                                }
                             }
                         },
                         Err(_) => {
@@ -558,10 +971,23 @@ fn process_spawn(luau: &Lua, spawn_options: LuaValue) -> LuaValueResult {
                     }
                 }
             })?
-            .build_readonly()
+    };
+    // only children with a pty can be resized
+    #[cfg(unix)]
+    let child_process_handle = match pty {
+        Some(pty) => child_process_handle.with_function("resize", move |_luau: &Lua, mut multivalue: LuaMultiValue| -> LuaEmptyResult {
+            let function_name = "ChildProcess:resize(rows: number, cols: number)";
+            pop_self(&mut multivalue, function_name)?;
+            let size = pty::PtySize::from_args(&mut multivalue, function_name)?;
+            match pty.resize(size) {
+                Ok(()) => Ok(()),
+                Err(err) => wrap_err!("{}: unable to resize pseudo-terminal due to err: {}", function_name, err),
+            }
+        })?,
+        None => child_process_handle,
     };
 
-    ok_table(child_process_handle)
+    ok_table(child_process_handle.build_readonly())
 }
 
 fn set_exit_callback(luau: &Lua, f: Option<LuaValue>) -> LuaValueResult {
@@ -627,9 +1053,16 @@ fn exit(luau: &Lua, exit_code: Option<LuaValue>) -> LuaResult<()> {
 
 pub fn create(luau: &Lua) -> LuaResult<LuaTable> {
     TableBuilder::create(luau)?
-        .with_function("run", process_run)?
+        .with_value("run", std_task::yielding(luau, luau.create_function(process_run)?, luau.create_function(process_run_start)?)?)?
         .with_function("spawn", process_spawn)?
-        .with_function("shell", process_shell)?
+        .with_value("pipeline", pipeline::create(luau)?)?
+        .with_function("pid", system::process_pid)?
+        .with_function("list", system::process_list)?
+        .with_function("which", system::process_which)?
+        .with_function("signal", signals::process_signal)?
+        .with_function("on_signal", signals::process_on_signal)?
+        .with_function("attach", detached::process_attach)?
+        .with_value("shell", std_task::yielding(luau, luau.create_function(process_shell)?, luau.create_function(process_shell_start)?)?)?
         .with_function("setexitcallback", set_exit_callback)?
         .with_function("exit", exit)?
         .build_readonly()
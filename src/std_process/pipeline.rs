@@ -0,0 +1,221 @@
+//! `process.pipeline`: runs programs with each one's stdout wired straight into the next one's stdin,
+//! like `a | b | c` in a shell but without needing a shell (or quoting anything).
+
+use crate::prelude::*;
+use crate::std_task;
+use std::io;
+use std::process::{Child, ChildStdout, ExitStatus, Output, Stdio};
+use std::thread::{self, JoinHandle};
+
+use mluau::prelude::*;
+
+use super::status::{self, Waiter};
+use super::{join_output, read_to_end, run_result_builder, write_stdin_data, OutputTarget, ProcessOptions, StdinSource};
+
+struct RunningStage {
+    program: String,
+    child: Child,
+    waiter: Waiter,
+    stderr_reader: Option<JoinHandle<Vec<u8>>>,
+}
+
+/// how a stage exited, and whether it had to be killed for running past its `timeout`
+struct StageStatus {
+    program: String,
+    status: ExitStatus,
+    timed_out: bool,
+}
+
+/// kills and reaps every stage that's been started; their output readers finish on their own once the pipes close
+fn kill_all(running: &mut [RunningStage]) {
+    for stage in running.iter_mut() {
+        // errors if it's already exited, which is fine
+        let _ = stage.child.kill();
+        let _ = stage.child.wait();
+    }
+}
+
+/// runs every stage to completion; returns each stage's program name and exit status, plus the combined output:
+/// the last stage's stdout, every stage's stderr (in order), and the rightmost failing exit status (like `set -o pipefail`)
+fn run_pipeline(stages: Vec<ProcessOptions>) -> PipelineResult {
+    let last_index = stages.len() - 1;
+    let mut running: Vec<RunningStage> = Vec::with_capacity(stages.len());
+    let mut previous_stdout: Option<ChildStdout> = None;
+    let mut stdin_writer = None;
+    let mut stdout_reader = None;
+
+    for (index, options) in stages.into_iter().enumerate() {
+        let mut command = options.command();
+        if options.timeout.is_some() {
+            status::lead_process_group(&mut command);
+        }
+        let program = options.program;
+        let waiter = Waiter::new(options.timeout, options.grace_period.unwrap_or(status::DEFAULT_GRACE_PERIOD));
+        let stdin = options.stdin.unwrap_or(StdinSource::Null);
+        command.stdin(match previous_stdout.take() {
+            Some(stdout) => Stdio::from(stdout),
+            None => stdin.stdio(),
+        });
+        let spawned = options.stdout.stdio("stdout").and_then(|stdout| {
+            let stderr = options.stderr.stdio("stderr")?;
+            command.stdout(stdout).stderr(stderr).spawn()
+        });
+
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(err) => {
+                // don't leave the earlier stages running (or blocked on a pipe nobody's going to read)
+                kill_all(&mut running);
+                return Err((program, err));
+            }
+        };
+        if let StdinSource::Data(data) = stdin {
+            stdin_writer = write_stdin_data(child.stdin.take(), data);
+        }
+        if index < last_index {
+            previous_stdout = child.stdout.take();
+        } else {
+            stdout_reader = read_to_end(child.stdout.take());
+        }
+        let stderr_reader = read_to_end(child.stderr.take());
+        running.push(RunningStage { program, child, waiter, stderr_reader });
+        // dropping `command` here closes our copy of the pipe between stages, so the next stage sees EOF
+        // once the previous one exits instead of waiting on us forever
+    }
+
+    // stages can have different timeouts, so we check on all of them instead of waiting on each in turn
+    let mut exit_statuses: Vec<Option<ExitStatus>> = vec![None; running.len()];
+    let mut poll_error = None;
+    'polling: loop {
+        let mut still_running = false;
+        for (stage, exit_status) in running.iter_mut().zip(exit_statuses.iter_mut()) {
+            if exit_status.is_some() {
+                continue;
+            }
+            match stage.waiter.poll(&mut stage.child) {
+                Ok(status) => *exit_status = status,
+                Err(err) => {
+                    poll_error = Some((stage.program.clone(), err));
+                    break 'polling;
+                }
+            }
+            still_running |= exit_status.is_none();
+        }
+        if !still_running {
+            break;
+        }
+        thread::sleep(status::POLL_INTERVAL);
+    }
+    if let Some(err) = poll_error {
+        // same as when a stage can't be spawned: nothing should outlive the pipeline
+        kill_all(&mut running);
+        return Err(err);
+    }
+
+    let stdout = join_output(stdout_reader);
+    let mut stderr = Vec::new();
+    let mut statuses = Vec::with_capacity(running.len());
+    for (stage, exit_status) in running.into_iter().zip(exit_statuses) {
+        stderr.extend(join_output(stage.stderr_reader));
+        statuses.push(StageStatus {
+            program: stage.program,
+            // every stage has exited by now
+            status: exit_status.unwrap_or_default(),
+            timed_out: stage.waiter.timed_out,
+        });
+    }
+    if let Some(stdin_writer) = stdin_writer {
+        let _ = stdin_writer.join();
+    }
+
+    let status = statuses
+        .iter()
+        .rev()
+        .map(|stage| stage.status)
+        .find(|status| !status.success())
+        .unwrap_or(statuses[last_index].status);
+
+    Ok((statuses, Output { status, stdout, stderr }))
+}
+
+const FUNCTION_NAME: &str = "process.pipeline(stages: { RunOptions })";
+
+fn pipeline_stages(luau: &Lua, value: LuaValue, function_name: &'static str) -> LuaResult<Vec<ProcessOptions>> {
+    let stages_table = match value {
+        LuaValue::Table(stages_table) => stages_table,
+        other => {
+            return wrap_err!("{} expected stages to be an array of RunOptions tables, got: {:?}", function_name, other);
+        }
+    };
+
+    let mut stages = Vec::new();
+    for stage in stages_table.sequence_values::<LuaValue>() {
+        match stage? {
+            LuaValue::Table(stage) => stages.push(ProcessOptions::from_table(luau, stage)?),
+            other => {
+                return wrap_err!("{} expected stage {} to be a RunOptions table, got: {:?}", function_name, stages.len() + 1, other);
+            }
+        }
+    }
+    if stages.is_empty() {
+        return wrap_err!("{} expected at least one stage, got an empty table", function_name);
+    }
+
+    let last_index = stages.len() - 1;
+    for (index, stage) in stages.iter().enumerate() {
+        if index > 0 && stage.stdin.is_some() {
+            return wrap_err!(
+                "{}: only the first stage can set stdin; stage {} ('{}') reads from stage {}'s stdout",
+                function_name, index + 1, stage.program, index
+            );
+        }
+        if index < last_index && !matches!(stage.stdout, OutputTarget::Pipe) {
+            return wrap_err!(
+                "{}: stage {} ('{}') can't redirect its stdout because it's piped into stage {}; only the last stage can set stdout",
+                function_name, index + 1, stage.program, index + 2
+            );
+        }
+    }
+    Ok(stages)
+}
+
+type PipelineResult = Result<(Vec<StageStatus>, Output), (String, io::Error)>;
+
+fn pipeline_result(luau: &Lua, result: PipelineResult, function_name: &'static str) -> LuaValueResult {
+    match result {
+        Ok((statuses, output)) => {
+            let timed_out = statuses.iter().any(|stage| stage.timed_out);
+            let stage_results = luau.create_table_with_capacity(statuses.len(), 0)?;
+            for stage in statuses {
+                stage_results.raw_push(
+                    status::with_exit_status(TableBuilder::create(luau)?, &stage.status)?
+                        .with_value("program", stage.program)?
+                        .with_value("timed_out", stage.timed_out)?
+                        .build_readonly()?,
+                )?;
+            }
+            stage_results.set_readonly(true);
+            ok_table(run_result_builder(luau, output, timed_out)?.with_value("stages", stage_results)?.build_readonly())
+        }
+        Err((program, err)) => {
+            wrap_err!("{} was unable to run the program '{}': {}", function_name, program, err)
+        }
+    }
+}
+
+fn process_pipeline(luau: &Lua, value: LuaValue) -> LuaValueResult {
+    let stages = pipeline_stages(luau, value, FUNCTION_NAME)?;
+    pipeline_result(luau, run_pipeline(stages), FUNCTION_NAME)
+}
+
+/// `process.pipeline` for @std/task tasks; the stages run (and get waited on) in the background while other tasks run
+fn process_pipeline_start(luau: &Lua, value: LuaValue) -> LuaResult<LuaFunction> {
+    let stages = pipeline_stages(luau, value, FUNCTION_NAME)?;
+    std_task::poll_background(luau, move || run_pipeline(stages), move |luau, result| {
+        pipeline_result(luau, result, FUNCTION_NAME)
+    })
+}
+
+pub fn create(luau: &Lua) -> LuaResult<LuaFunction> {
+    std_task::yielding(luau, luau.create_function(process_pipeline)?, luau.create_function(process_pipeline_start)?)
+}
@@ -0,0 +1,141 @@
+//! Pseudo-terminals for `process.spawn { pty = ... }`, so children think they're running in a real terminal
+//! (and keep their colors, prompts, and line editing).
+
+use crate::prelude::*;
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+
+use mluau::prelude::*;
+
+#[derive(Debug, Clone, Copy)]
+pub struct PtySize {
+    pub rows: u16,
+    pub cols: u16,
+}
+
+impl PtySize {
+    /// `SpawnOptions.pty`: `true` for a 24x80 terminal, or `{ rows: number?, cols: number? }`
+    pub fn from_value(value: LuaValue) -> LuaResult<Option<Self>> {
+        match value {
+            LuaValue::Boolean(true) => Ok(Some(Self { rows: 24, cols: 80 })),
+            LuaValue::Boolean(false) | LuaNil => Ok(None),
+            LuaValue::Table(size) => Ok(Some(Self {
+                rows: dimension(size.raw_get("rows")?, "SpawnOptions.pty.rows", 24)?,
+                cols: dimension(size.raw_get("cols")?, "SpawnOptions.pty.cols", 80)?,
+            })),
+            other => {
+                wrap_err!("SpawnOptions.pty expected to be true, {{ rows: number?, cols: number? }}, or nil, got: {:?}", other)
+            }
+        }
+    }
+
+    pub fn from_args(multivalue: &mut LuaMultiValue, function_name: &'static str) -> LuaResult<Self> {
+        let rows = dimension(multivalue.pop_front().unwrap_or(LuaNil), function_name, 0)?;
+        let cols = dimension(multivalue.pop_front().unwrap_or(LuaNil), function_name, 0)?;
+        if rows == 0 || cols == 0 {
+            return wrap_err!("{} expected rows and cols to be positive numbers, got {} rows and {} cols", function_name, rows, cols);
+        }
+        Ok(Self { rows, cols })
+    }
+
+    fn winsize(self) -> libc::winsize {
+        libc::winsize {
+            ws_row: self.rows,
+            ws_col: self.cols,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        }
+    }
+}
+
+fn dimension(value: LuaValue, what: &'static str, default: u16) -> LuaResult<u16> {
+    match value {
+        LuaValue::Integer(i) => match u16::try_from(i) {
+            Ok(n) => Ok(n),
+            Err(_) => wrap_err!("{} expected a number between 0 and 65535, got: {}", what, i),
+        },
+        LuaValue::Number(f) if f.fract() == 0.0 && (0.0..=65535.0).contains(&f) => Ok(f as u16),
+        LuaNil => Ok(default),
+        other => wrap_err!("{} expected a whole number between 0 and 65535, got: {:?}", what, other),
+    }
+}
+
+/// The parent's (master) side of a pseudo-terminal; the child gets the other end as its stdin, stdout, and stderr.
+pub struct Pty {
+    master: File,
+}
+
+impl Pty {
+    /// opens a pty and hooks its terminal side up to `command`, which becomes the session leader with the pty as its
+    /// controlling terminal; the terminal side gets closed (in our process) when `command` is dropped
+    pub fn attach(command: &mut Command, size: PtySize) -> io::Result<Self> {
+        let mut master_fd: libc::c_int = -1;
+        let mut slave_fd: libc::c_int = -1;
+        let mut winsize = size.winsize();
+        // SAFETY: openpty only writes to the two fds we pass it; null name and termios are allowed
+        let result = unsafe { libc::openpty(&mut master_fd, &mut slave_fd, std::ptr::null_mut(), std::ptr::null_mut(), &mut winsize) };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: openpty succeeded, so both fds are open and nobody else owns them
+        let (master, slave) = unsafe { (OwnedFd::from_raw_fd(master_fd), OwnedFd::from_raw_fd(slave_fd)) };
+        // don't leak our end of the pty into other children (they'd keep it open after this one exits)
+        // SAFETY: fcntl on an fd we own
+        if unsafe { libc::fcntl(master.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        command
+            .stdin(Stdio::from(slave.try_clone()?))
+            .stdout(Stdio::from(slave.try_clone()?))
+            .stderr(Stdio::from(slave));
+        // SAFETY: setsid and ioctl are async-signal-safe, and we don't allocate between fork and exec
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                // stdin's the pty by now; make it our controlling terminal so ctrl-c, job control, and /dev/tty work
+                if libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+
+        Ok(Self { master: File::from(master) })
+    }
+
+    pub fn reader(&self) -> io::Result<PtyReader> {
+        Ok(PtyReader(self.master.try_clone()?))
+    }
+
+    pub fn writer(&self) -> io::Result<File> {
+        self.master.try_clone()
+    }
+
+    pub fn resize(&self, size: PtySize) -> io::Result<()> {
+        let winsize = size.winsize();
+        // SAFETY: TIOCSWINSZ only reads the winsize we pass it
+        if unsafe { libc::ioctl(self.master.as_raw_fd(), libc::TIOCSWINSZ as _, &winsize) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+/// Reads what the child writes to its terminal.
+pub struct PtyReader(File);
+
+impl Read for PtyReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.read(buf) {
+            // linux reports EIO instead of EOF once every process on the terminal side has closed it
+            Err(err) if err.raw_os_error() == Some(libc::EIO) => Ok(0),
+            other => other,
+        }
+    }
+}
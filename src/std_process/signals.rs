@@ -0,0 +1,223 @@
+//! Signal names, `process.signal`, and `process.on_signal`.
+//!
+//! `process.on_signal` handlers don't touch Luau: they just mark the signal as pending, and the VM interrupt
+//! (which Luau calls between instructions) calls the registered callback the next time the VM runs.
+
+use crate::prelude::*;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use mluau::prelude::*;
+
+/// signals `process.signal` knows by name
+#[cfg(unix)]
+const SIGNALS: &[(&str, libc::c_int)] = &[
+    ("SIGHUP", libc::SIGHUP),
+    ("SIGINT", libc::SIGINT),
+    ("SIGQUIT", libc::SIGQUIT),
+    ("SIGILL", libc::SIGILL),
+    ("SIGTRAP", libc::SIGTRAP),
+    ("SIGABRT", libc::SIGABRT),
+    ("SIGBUS", libc::SIGBUS),
+    ("SIGFPE", libc::SIGFPE),
+    ("SIGKILL", libc::SIGKILL),
+    ("SIGUSR1", libc::SIGUSR1),
+    ("SIGSEGV", libc::SIGSEGV),
+    ("SIGUSR2", libc::SIGUSR2),
+    ("SIGPIPE", libc::SIGPIPE),
+    ("SIGALRM", libc::SIGALRM),
+    ("SIGTERM", libc::SIGTERM),
+    ("SIGCHLD", libc::SIGCHLD),
+    ("SIGCONT", libc::SIGCONT),
+    ("SIGSTOP", libc::SIGSTOP),
+    ("SIGTSTP", libc::SIGTSTP),
+    ("SIGTTIN", libc::SIGTTIN),
+    ("SIGTTOU", libc::SIGTTOU),
+    ("SIGXCPU", libc::SIGXCPU),
+    ("SIGXFSZ", libc::SIGXFSZ),
+    ("SIGWINCH", libc::SIGWINCH),
+];
+
+/// signals `process.on_signal` can handle; a signal's index here is its bit in `PENDING`
+#[cfg(unix)]
+const HANDLEABLE_SIGNALS: &[(&str, libc::c_int)] = &[("SIGINT", libc::SIGINT), ("SIGTERM", libc::SIGTERM), ("SIGHUP", libc::SIGHUP)];
+/// Windows only has (C runtime emulated) SIGINT and SIGTERM, and nothing sends SIGTERM
+#[cfg(windows)]
+const HANDLEABLE_SIGNALS: &[(&str, libc::c_int)] = &[("SIGINT", libc::SIGINT), ("SIGTERM", libc::SIGTERM)];
+
+/// signals that came in but haven't been handled by their `process.on_signal` callback yet
+static PENDING: AtomicU32 = AtomicU32::new(0);
+/// signals with a `process.on_signal` callback
+static HANDLED: AtomicU32 = AtomicU32::new(0);
+
+const CALLBACKS_REGISTRY_KEY: &str = "seal.process.on_signal";
+
+/// the name of a signal, like `"SIGKILL"`
+#[cfg(unix)]
+pub fn signal_name(signal: libc::c_int) -> String {
+    match SIGNALS.iter().find(|(_, number)| *number == signal) {
+        Some((name, _)) => name.to_string(),
+        None => format!("SIG{}", signal),
+    }
+}
+
+fn signal_bit(signal: libc::c_int) -> u32 {
+    match HANDLEABLE_SIGNALS.iter().position(|(_, number)| *number == signal) {
+        Some(index) => 1 << index,
+        None => 0,
+    }
+}
+
+extern "C" fn handle_signal(signal: libc::c_int) {
+    // SAFETY (for the whole handler): signal and raise are async-signal-safe, and atomics are lock-free
+    #[cfg(windows)]
+    unsafe {
+        // the C runtime resets the handler before calling it
+        libc::signal(signal, handle_signal as libc::sighandler_t);
+    }
+    let bit = signal_bit(signal);
+    let previous = PENDING.fetch_or(bit, Ordering::SeqCst);
+    if previous & bit != 0 {
+        // the last one still hasn't been handled, probably because seal's stuck in something blocking (like time.wait)
+        // and hasn't gotten back to running luau; a second ctrl-c (or kill) does what it would've without a handler
+        unsafe {
+            libc::signal(signal, libc::SIG_DFL);
+            libc::raise(signal);
+        }
+    }
+}
+
+/// calls the callbacks for signals that came in since we last checked
+fn dispatch_pending(luau: &Lua) -> LuaResult<()> {
+    let handled = HANDLED.load(Ordering::SeqCst);
+    let pending = PENDING.fetch_and(!handled, Ordering::SeqCst) & handled;
+    if pending == 0 {
+        return Ok(());
+    }
+    let callbacks: LuaTable = luau.named_registry_value(CALLBACKS_REGISTRY_KEY)?;
+    for (index, (name, _)) in HANDLEABLE_SIGNALS.iter().enumerate() {
+        if pending & (1 << index) != 0
+            && let LuaValue::Function(callback) = callbacks.raw_get(*name)?
+        {
+            callback.call::<()>(*name)?;
+        }
+    }
+    Ok(())
+}
+
+fn install_interrupt(luau: &Lua) {
+    // interrupts can fire while a callback's running; don't start handling the next signal in the middle of it
+    let dispatching = Cell::new(false);
+    luau.set_interrupt(move |luau| {
+        if dispatching.get() || PENDING.load(Ordering::Relaxed) == 0 {
+            return Ok(LuaVmState::Continue);
+        }
+        dispatching.set(true);
+        let result = dispatch_pending(luau);
+        dispatching.set(false);
+        result.map(|_| LuaVmState::Continue)
+    });
+}
+
+/// `process.on_signal(signal: "SIGINT" | "SIGTERM" | "SIGHUP", callback: ((signal: string) -> ())?)`
+pub fn process_on_signal(luau: &Lua, mut multivalue: LuaMultiValue) -> LuaEmptyResult {
+    let function_name = "process.on_signal(signal: \"SIGINT\" | \"SIGTERM\" | \"SIGHUP\", callback: ((signal: string) -> ())?)";
+    let name = match multivalue.pop_front() {
+        Some(LuaValue::String(name)) => name.to_string_lossy(),
+        other => {
+            return wrap_err!("{} expected signal to be a string, got: {:?}", function_name, other);
+        }
+    };
+    let Some(&(name, signal)) = HANDLEABLE_SIGNALS.iter().find(|(signal_name, _)| *signal_name == name) else {
+        let supported: Vec<&str> = HANDLEABLE_SIGNALS.iter().map(|(name, _)| *name).collect();
+        return wrap_err!("{}: can't handle signal '{}' on this platform; supported signals: {}", function_name, name, supported.join(", "));
+    };
+    let callback = match multivalue.pop_front() {
+        Some(LuaValue::Function(callback)) => Some(callback),
+        Some(LuaNil) | None => None,
+        Some(other) => {
+            return wrap_err!("{} expected callback to be a function or nil, got: {:?}", function_name, other);
+        }
+    };
+
+    let callbacks = match luau.named_registry_value(CALLBACKS_REGISTRY_KEY)? {
+        LuaValue::Table(callbacks) => callbacks,
+        _ => {
+            let callbacks = luau.create_table()?;
+            luau.set_named_registry_value(CALLBACKS_REGISTRY_KEY, callbacks.clone())?;
+            install_interrupt(luau);
+            callbacks
+        }
+    };
+
+    let bit = signal_bit(signal);
+    // SAFETY: handle_signal only does async-signal-safe things
+    match callback {
+        Some(callback) => {
+            callbacks.raw_set(name, callback)?;
+            HANDLED.fetch_or(bit, Ordering::SeqCst);
+            unsafe {
+                libc::signal(signal, handle_signal as libc::sighandler_t);
+            }
+        }
+        None => {
+            // back to the default (usually exiting)
+            callbacks.raw_set(name, LuaNil)?;
+            HANDLED.fetch_and(!bit, Ordering::SeqCst);
+            PENDING.fetch_and(!bit, Ordering::SeqCst);
+            unsafe {
+                libc::signal(signal, libc::SIG_DFL);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// a pid passed to `process.signal` or `process.attach`
+pub fn pid_from_value(value: Option<LuaValue>, function_name: &'static str) -> LuaResult<i64> {
+    match value {
+        Some(LuaValue::Integer(pid)) if pid > 0 => Ok(pid),
+        Some(LuaValue::Number(pid)) if pid > 0.0 && pid.fract() == 0.0 => Ok(pid as i64),
+        other => wrap_err!("{} expected pid to be a positive whole number, got: {:?}", function_name, other),
+    }
+}
+
+/// sends the signal called `name` (like `"SIGTERM"`) to `pid`
+pub fn send_signal(pid: i64, name: &str, function_name: &'static str) -> LuaEmptyResult {
+    #[cfg(unix)]
+    {
+        let Some(&(_, signal)) = SIGNALS.iter().find(|(signal_name, _)| *signal_name == name) else {
+            return wrap_err!("{}: unknown signal '{}'; expected a name like \"SIGTERM\" or \"SIGKILL\"", function_name, name);
+        };
+        let Ok(pid) = libc::pid_t::try_from(pid) else {
+            return wrap_err!("{}: pid {} is too large", function_name, pid);
+        };
+        // SAFETY: kill doesn't touch our memory, and pid is positive so it can't signal a whole process group
+        if unsafe { libc::kill(pid, signal) } == -1 {
+            let err = std::io::Error::last_os_error();
+            return match err.raw_os_error() {
+                Some(libc::ESRCH) => wrap_err!("{}: no process with pid {}", function_name, pid),
+                Some(libc::EPERM) => wrap_err!("{}: not allowed to send {} to pid {}", function_name, name, pid),
+                _ => wrap_err!("{}: unable to send {} to pid {} due to err: {}", function_name, name, pid, err),
+            };
+        }
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    {
+        wrap_err!("{}: can't send {} to pid {}; sending signals isn't supported on Windows", function_name, name, pid)
+    }
+}
+
+/// `process.signal(pid: number, signal: string)`
+pub fn process_signal(_luau: &Lua, mut multivalue: LuaMultiValue) -> LuaEmptyResult {
+    let function_name = "process.signal(pid: number, signal: string)";
+    let pid = pid_from_value(multivalue.pop_front(), function_name)?;
+    let name = match multivalue.pop_front() {
+        Some(LuaValue::String(name)) => name.to_string_lossy(),
+        other => {
+            return wrap_err!("{} expected signal to be a string like \"SIGTERM\", got: {:?}", function_name, other);
+        }
+    };
+    send_signal(pid, &name, function_name)
+}
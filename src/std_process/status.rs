@@ -0,0 +1,229 @@
+//! Exit statuses, and waiting on children with timeouts (escalating from SIGTERM to SIGKILL once they're up).
+
+use crate::prelude::*;
+use std::io;
+use std::process::{Child, Command, ExitStatus};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(unix)]
+use std::os::unix::process::ExitStatusExt;
+
+use mluau::prelude::*;
+
+/// how long a timed out child gets between SIGTERM and SIGKILL unless `RunOptions.grace_period` says otherwise
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// how often we check on children we're waiting on with a timeout
+pub const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// a timeout or grace period in seconds; `nil` is `None`
+pub fn seconds_from_value(value: LuaValue, what: &str) -> LuaResult<Option<Duration>> {
+    let seconds = match value {
+        LuaValue::Number(f) => f,
+        LuaValue::Integer(i) => i as f64,
+        LuaNil => {
+            return Ok(None);
+        }
+        other => {
+            return wrap_err!("{} expected to be a number (in seconds) or nil, got: {:?}", what, other);
+        }
+    };
+    if !seconds.is_finite() || seconds < 0.0 {
+        return wrap_err!("{} must be a finite, non-negative number of seconds, got: {}", what, seconds);
+    }
+    match Duration::try_from_secs_f64(seconds) {
+        Ok(duration) => Ok(Some(duration)),
+        Err(err) => wrap_err!("{}: error creating Duration from {} seconds: {}", what, seconds, err),
+    }
+}
+
+/// the name of the signal that killed the process (`"SIGKILL"`), if it was killed by one
+pub fn signal_name(status: &ExitStatus) -> Option<String> {
+    #[cfg(unix)]
+    {
+        status.signal().map(super::signals::signal_name)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = status;
+        None
+    }
+}
+
+/// adds `ok`, `code`, and `signal` to a `RunResult`, `PipelineStage`, or `ExitStatus` table
+pub fn with_exit_status<'luau>(builder: TableBuilder<'luau>, status: &ExitStatus) -> LuaResult<TableBuilder<'luau>> {
+    builder
+        .with_value("ok", status.success())?
+        // no exit code if the process was killed by a signal
+        .with_value("code", status.code())?
+        .with_value("signal", signal_name(status))
+}
+
+pub fn exit_status_table(luau: &Lua, status: &ExitStatus) -> LuaResult<LuaTable> {
+    with_exit_status(TableBuilder::create(luau)?, status)?.build_readonly()
+}
+
+/// puts the child in a process group of its own, so a timeout can take down everything it started too;
+/// otherwise grandchildren (like a shell's) keep running, holding onto its stdout and stderr pipes
+pub fn lead_process_group(command: &mut Command) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = command;
+    }
+}
+
+/// signals the child, or its whole process group if it leads one (see `lead_process_group`)
+#[cfg(unix)]
+fn send_signal(child: &Child, signal: libc::c_int, group: bool) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(child.id()) else {
+        return false;
+    };
+    let pid = if group { -pid } else { pid };
+    // SAFETY: kill doesn't touch memory; worst case the pid already exited (and hasn't been reaped yet, so it can't be reused)
+    unsafe { libc::kill(pid, signal) == 0 }
+}
+
+/// asks the child to exit; returns false if we can't ask nicely on this platform
+fn send_sigterm(child: &Child) -> bool {
+    #[cfg(unix)]
+    {
+        send_signal(child, libc::SIGTERM, false)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = child;
+        false
+    }
+}
+
+enum WaitState {
+    Running,
+    /// sent SIGTERM, SIGKILL at the `Instant` (never, if the grace period's too long to represent)
+    Terminating(Option<Instant>),
+    Killed,
+}
+
+/// waits on a child, terminating it if it runs longer than its timeout
+pub struct Waiter {
+    timeout_at: Option<Instant>,
+    /// whether the child leads its own process group, which gets signalled along with it
+    group: bool,
+    grace_period: Duration,
+    state: WaitState,
+    pub timed_out: bool,
+}
+
+impl Waiter {
+    /// children with a timeout should've been spawned with `lead_process_group`
+    pub fn new(timeout: Option<Duration>, grace_period: Duration) -> Self {
+        Self {
+            // timeouts too far off to represent are as good as none
+            timeout_at: timeout.and_then(|timeout| Instant::now().checked_add(timeout)),
+            group: timeout.is_some(),
+            grace_period,
+            state: WaitState::Running,
+            timed_out: false,
+        }
+    }
+
+    /// SIGTERMs the child (and its process group); returns false if we can't ask nicely on this platform
+    fn terminate(&self, child: &Child) -> bool {
+        #[cfg(unix)]
+        {
+            send_signal(child, libc::SIGTERM, self.group)
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = child;
+            false
+        }
+    }
+
+    /// SIGKILLs the rest of the child's process group, if it leads one; `Child::kill` takes care of the child itself
+    fn kill_group(&self, child: &Child) {
+        #[cfg(unix)]
+        if self.group {
+            send_signal(child, libc::SIGKILL, true);
+        }
+        #[cfg(not(unix))]
+        let _ = child;
+    }
+
+    /// checks on the child once, escalating to SIGTERM then SIGKILL when it's overstayed its welcome;
+    /// returns its exit status if it's exited
+    pub fn poll(&mut self, child: &mut Child) -> io::Result<Option<ExitStatus>> {
+        if let Some(status) = child.try_wait()? {
+            if self.timed_out {
+                // anything it started that shrugged off SIGTERM would otherwise keep its pipes open (and us waiting on them)
+                self.kill_group(child);
+            }
+            return Ok(Some(status));
+        }
+        let now = Instant::now();
+        if let WaitState::Running = self.state
+            && let Some(timeout_at) = self.timeout_at
+            && now >= timeout_at
+        {
+            self.timed_out = true;
+            self.state = WaitState::Terminating(now.checked_add(self.grace_period));
+            if self.grace_period.is_zero() || !self.terminate(child) {
+                self.state = WaitState::Terminating(Some(now));
+            }
+        }
+        if let WaitState::Terminating(Some(kill_at)) = self.state
+            && now >= kill_at
+        {
+            self.kill_group(child);
+            // errors if the child exited since try_wait, which is what we wanted anyway
+            let _ = child.kill();
+            self.state = WaitState::Killed;
+        }
+        Ok(None)
+    }
+
+    pub fn wait(&mut self, child: &mut Child) -> io::Result<ExitStatus> {
+        if self.timeout_at.is_none() {
+            return child.wait();
+        }
+        loop {
+            if let Some(status) = self.poll(child)? {
+                return Ok(status);
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+/// waits up to `timeout` for the child to exit without killing it; `None` if it's still running
+pub fn wait_timeout(child: &mut Child, timeout: Option<Duration>) -> io::Result<Option<ExitStatus>> {
+    let Some(give_up_at) = timeout.and_then(|timeout| Instant::now().checked_add(timeout)) else {
+        return child.wait().map(Some);
+    };
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        let now = Instant::now();
+        if now >= give_up_at {
+            return Ok(None);
+        }
+        thread::sleep(POLL_INTERVAL.min(give_up_at - now));
+    }
+}
+
+/// SIGTERMs the child, giving it `grace_period` to exit before SIGKILLing it
+pub fn terminate(child: &mut Child, grace_period: Duration) -> io::Result<ExitStatus> {
+    if !grace_period.is_zero() && send_sigterm(child)
+        && let Some(status) = wait_timeout(child, Some(grace_period))?
+    {
+        return Ok(status);
+    }
+    let _ = child.kill();
+    child.wait()
+}
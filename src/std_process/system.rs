@@ -0,0 +1,154 @@
+//! `process.pid`, `process.list`, and `process.which`: finding out about processes (and programs) we didn't spawn.
+
+use crate::prelude::*;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use mluau::prelude::*;
+
+pub fn process_pid(_luau: &Lua, _value: LuaValue) -> LuaValueResult {
+    Ok(LuaValue::Integer(std::process::id() as i64))
+}
+
+#[cfg(target_os = "linux")]
+struct ProcessInfo {
+    pid: u32,
+    ppid: u32,
+    name: String,
+    cmdline: Vec<String>,
+    /// resident set size in bytes
+    memory: u64,
+}
+
+/// reads `/proc/<pid>`; `None` if the process exited while we were reading it (or we can't read it)
+#[cfg(target_os = "linux")]
+fn read_process(pid: u32, page_size: u64) -> Option<ProcessInfo> {
+    use std::fs;
+
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // the name's in parentheses and can have spaces and parentheses of its own, so it ends at the last ')'
+    let name_start = stat.find('(')? + 1;
+    let name_end = stat.rfind(')')?;
+    let name = stat.get(name_start..name_end)?.to_string();
+    // after the name comes the state, then the ppid
+    let ppid = stat.get(name_end + 1..)?.split_whitespace().nth(1)?.parse().ok()?;
+
+    // kernel threads have an empty cmdline
+    let cmdline = fs::read(format!("/proc/{}/cmdline", pid)).unwrap_or_default();
+    let cmdline = cmdline
+        .split(|byte| *byte == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect();
+
+    let memory = fs::read_to_string(format!("/proc/{}/statm", pid))
+        .ok()
+        .and_then(|statm| statm.split_whitespace().nth(1)?.parse::<u64>().ok())
+        .unwrap_or(0)
+        * page_size;
+
+    Some(ProcessInfo { pid, ppid, name, cmdline, memory })
+}
+
+#[cfg(target_os = "linux")]
+pub fn process_list(luau: &Lua, _value: LuaValue) -> LuaValueResult {
+    let function_name = "process.list()";
+    let entries = match std::fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(err) => {
+            return wrap_err!("{}: unable to read /proc due to err: {}", function_name, err);
+        }
+    };
+    // SAFETY: sysconf doesn't touch our memory
+    let page_size = u64::try_from(unsafe { libc::sysconf(libc::_SC_PAGESIZE) }).unwrap_or(4096);
+
+    let list = luau.create_table()?;
+    for entry in entries.flatten() {
+        // everything in /proc that's all digits is a process
+        let Some(pid) = entry.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) else {
+            continue;
+        };
+        let Some(info) = read_process(pid, page_size) else {
+            continue;
+        };
+        list.raw_push(
+            TableBuilder::create(luau)?
+                .with_value("pid", info.pid)?
+                .with_value("ppid", info.ppid)?
+                .with_value("name", info.name)?
+                .with_value("cmdline", info.cmdline)?
+                .with_value("memory", info.memory)?
+                .build_readonly()?,
+        )?;
+    }
+    ok_table(Ok(list))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn process_list(_luau: &Lua, _value: LuaValue) -> LuaValueResult {
+    wrap_err!("process.list() is only supported on Linux")
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    match path.metadata() {
+        Ok(metadata) => metadata.is_file() && metadata.permissions().mode() & 0o111 != 0,
+        Err(_) => false,
+    }
+}
+
+#[cfg(windows)]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// names to look for in each directory: just `program`, or on Windows `program` with each of `PATHEXT`'s extensions
+/// (unless it already has one)
+fn candidate_names(program: &str) -> Vec<String> {
+    #[cfg(windows)]
+    {
+        if Path::new(program).extension().is_none() {
+            let path_ext = env::var("PATHEXT").unwrap_or_else(|_| String::from(".COM;.EXE;.BAT;.CMD"));
+            return path_ext
+                .split(';')
+                .filter(|ext| !ext.is_empty())
+                .map(|ext| format!("{}{}", program, ext))
+                .collect();
+        }
+    }
+    vec![program.to_string()]
+}
+
+fn which(program: &str) -> Option<PathBuf> {
+    // paths (`./build.sh`, `/usr/bin/env`) aren't looked up in PATH
+    if program.contains('/') || (cfg!(windows) && program.contains('\\')) {
+        return candidate_names(program)
+            .into_iter()
+            .map(PathBuf::from)
+            .find(|candidate| is_executable(candidate));
+    }
+    let path_var = env::var_os("PATH")?;
+    let names = candidate_names(program);
+    env::split_paths(&path_var)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|candidate| is_executable(candidate))
+}
+
+pub fn process_which(luau: &Lua, value: LuaValue) -> LuaValueResult {
+    let function_name = "process.which(program: string)";
+    let program = match value {
+        LuaValue::String(program) => program.to_string_lossy(),
+        other => {
+            return wrap_err!("{} expected program to be a string, got: {:?}", function_name, other);
+        }
+    };
+    if program.is_empty() {
+        return wrap_err!("{} expected program to be a non-empty string", function_name);
+    }
+    match which(&program) {
+        Some(path) => ok_string(path.to_string_lossy().as_bytes(), luau),
+        None => Ok(LuaNil),
+    }
+}
@@ -0,0 +1,408 @@
+//! `@std/task`: a cooperative scheduler for running Luau coroutines (tasks) concurrently on one Luau VM.
+//!
+//! `run` runs the entry chunk as the first task, then keeps resuming tasks until none are left.
+//! Tasks never run in parallel. They take turns whenever one yields, either from `task.wait` or from a blocking stdlib call
+//! that supports yielding.
+//!
+//! Rust functions can't yield, so a stdlib function that yields comes in two halves:
+//! - the usual blocking function;
+//! - a `start` function that kicks the work off and returns a poll function (or, for plain sleeps, a `Wake`).
+//!
+//! When a task calls it, the Luau wrapper from `yielding` calls `start` instead of blocking.
+//! It then hands the poll function to the scheduler and yields.
+//! The scheduler polls that function between tasks and resumes the task with the results once they're ready.
+//! Where there's nothing to yield to, the wrapper just calls the blocking function. That's the case in `seal repl`,
+//! at the top level of a `require`d module, and in coroutines the scheduler doesn't own (like generators made with
+//! `coroutine.wrap`), whose yields belong to whoever resumes them.
+
+use crate::prelude::*;
+use crossbeam_channel::TryRecvError;
+use mluau::prelude::*;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// how soon the scheduler first checks on a task waiting for blocking work
+const POLL_INTERVAL: Duration = Duration::from_millis(2);
+/// the longest the scheduler backs off to between checks on work that's taking a while
+const MAX_POLL_INTERVAL: Duration = Duration::from_millis(32);
+
+const YIELDING_REGISTRY_KEY: &str = "seal.task.yielding";
+/// weak-keyed table of the threads the scheduler owns (and so is allowed to yield and resume)
+const TASKS_REGISTRY_KEY: &str = "seal.task.tasks";
+const YIELDING_SRC: &str = include_str!("./yielding.luau");
+const STD_TASK_SRC: &str = include_str!("./task.luau");
+
+struct Sleeper {
+    wake_at: Instant,
+    since: Instant,
+    thread: LuaThread,
+    /// what to resume the thread with; `None` (`task.wait`) resumes it with how long it slept
+    args: Option<LuaMultiValue>,
+}
+
+/// a task waiting on blocking work (see `yielding`)
+struct Waiter {
+    thread: LuaThread,
+    poll: LuaFunction,
+    next_poll: Instant,
+    /// doubles (up to `MAX_POLL_INTERVAL`) every time the work isn't done yet, so long waits don't spin
+    interval: Duration,
+}
+
+/// returned by a `start` function that just needs the task to sleep until `at`, then resume it with `results`;
+/// sleeping tasks cost nothing until they're due, unlike polling
+pub struct Wake {
+    at: Instant,
+    results: LuaMultiValue,
+}
+impl LuaUserData for Wake {}
+
+#[derive(Default)]
+struct Scheduler {
+    /// whether `run` is driving the scheduler; if it isn't, nothing would ever resume a yielded task
+    active: bool,
+    ready: VecDeque<(LuaThread, LuaMultiValue)>,
+    sleeping: Vec<Sleeper>,
+    waiting: Vec<Waiter>,
+}
+
+impl Scheduler {
+    fn cancel(&mut self, thread: &LuaThread) {
+        self.ready.retain(|(ready, _)| ready != thread);
+        self.sleeping.retain(|sleeper| &sleeper.thread != thread);
+        self.waiting.retain(|waiter| &waiter.thread != thread);
+    }
+
+    /// moves tasks that are done sleeping onto the ready queue, earliest first
+    fn wake_sleepers(&mut self, now: Instant) {
+        let (mut due, sleeping): (Vec<Sleeper>, Vec<Sleeper>) = std::mem::take(&mut self.sleeping)
+            .into_iter()
+            .partition(|sleeper| sleeper.wake_at <= now);
+        self.sleeping = sleeping;
+        due.sort_by_key(|sleeper| sleeper.wake_at);
+        for sleeper in due {
+            let args = match sleeper.args {
+                Some(args) => args,
+                None => LuaMultiValue::from_vec(vec![LuaValue::Number((now - sleeper.since).as_secs_f64())]),
+            };
+            self.ready.push_back((sleeper.thread, args));
+        }
+    }
+
+    /// how long to sleep for when no task is ready, or `None` if there's nothing left to wait for
+    fn idle_time(&self, now: Instant) -> Option<Duration> {
+        self.sleeping
+            .iter()
+            .map(|sleeper| sleeper.wake_at)
+            .chain(self.waiting.iter().map(|waiter| waiter.next_poll))
+            .min()
+            .map(|next| next.saturating_duration_since(now))
+    }
+}
+
+fn get_scheduler(luau: &Lua) -> Rc<RefCell<Scheduler>> {
+    if let Some(scheduler) = luau.app_data_ref::<Rc<RefCell<Scheduler>>>() {
+        return Rc::clone(&scheduler);
+    }
+    let scheduler = Rc::new(RefCell::new(Scheduler::default()));
+    luau.set_app_data(Rc::clone(&scheduler));
+    scheduler
+}
+
+fn tasks(luau: &Lua) -> LuaResult<LuaTable> {
+    match luau.named_registry_value(TASKS_REGISTRY_KEY)? {
+        LuaValue::Table(tasks) => Ok(tasks),
+        _ => {
+            let tasks = luau.create_table()?;
+            let metatable = luau.create_table()?;
+            // finished tasks can still be garbage collected
+            metatable.raw_set("__mode", "k")?;
+            tasks.set_metatable(Some(metatable))?;
+            luau.set_named_registry_value(TASKS_REGISTRY_KEY, &tasks)?;
+            Ok(tasks)
+        }
+    }
+}
+
+/// marks `thread` as a task the scheduler runs
+fn own(luau: &Lua, thread: &LuaThread) -> LuaEmptyResult {
+    tasks(luau)?.raw_set(thread.clone(), true)
+}
+
+/// whether the running coroutine is one of the scheduler's tasks, and so can yield to it
+fn in_task(luau: &Lua) -> LuaResult<bool> {
+    tasks(luau)?.raw_get::<bool>(luau.current_thread())
+}
+
+/// resumes the task if it's still suspended (it might've been cancelled, or resumed by someone else in the meantime);
+/// errors come with the task's traceback and end the program like errors in the main chunk do
+fn resume(thread: &LuaThread, args: LuaMultiValue) -> LuaEmptyResult {
+    if thread.status() != LuaThreadStatus::Resumable {
+        return Ok(());
+    }
+    thread.resume::<LuaMultiValue>(args)?;
+    Ok(())
+}
+
+/// polls tasks waiting for blocking work, moving the ones whose work is done onto the ready queue
+fn poll_waiting(scheduler: &RefCell<Scheduler>, now: Instant) -> LuaEmptyResult {
+    let waiting = std::mem::take(&mut scheduler.borrow_mut().waiting);
+    let mut still_waiting = Vec::with_capacity(waiting.len());
+    for mut waiter in waiting {
+        if waiter.next_poll > now {
+            still_waiting.push(waiter);
+            continue;
+        }
+        let results = match waiter.poll.call::<LuaMultiValue>(()) {
+            Ok(results) => results,
+            Err(err) => {
+                // raised in the task (see yielding.luau), so it can be pcalled like the blocking version
+                let args = LuaMultiValue::from_vec(vec![LuaValue::Boolean(false), LuaValue::Error(Box::new(err))]);
+                scheduler.borrow_mut().ready.push_back((waiter.thread, args));
+                continue;
+            }
+        };
+        match results.front() {
+            Some(LuaValue::Boolean(true)) => scheduler.borrow_mut().ready.push_back((waiter.thread, results)),
+            _ => {
+                waiter.next_poll = now + waiter.interval;
+                waiter.interval = (waiter.interval * 2).min(MAX_POLL_INTERVAL);
+                still_waiting.push(waiter);
+            },
+        }
+    }
+    let mut scheduler = scheduler.borrow_mut();
+    still_waiting.append(&mut scheduler.waiting);
+    scheduler.waiting = still_waiting;
+    Ok(())
+}
+
+/// Runs `main` as the first task, then keeps resuming tasks until there aren't any left.
+pub fn run(luau: &Lua, main: LuaFunction) -> LuaEmptyResult {
+    let scheduler = get_scheduler(luau);
+    scheduler.borrow_mut().active = true;
+    let main = luau.create_thread(main)?;
+    own(luau, &main)?;
+    resume(&main, LuaMultiValue::new())?;
+    loop {
+        let now = Instant::now();
+        scheduler.borrow_mut().wake_sleepers(now);
+        poll_waiting(&scheduler, now)?;
+
+        // tasks deferred while these run go to the back of the queue, and wait for the next round
+        let ready = std::mem::take(&mut scheduler.borrow_mut().ready);
+        if ready.is_empty() {
+            let idle_time = scheduler.borrow().idle_time(Instant::now());
+            match idle_time {
+                Some(idle_time) => thread::sleep(idle_time),
+                None => break,
+            }
+            continue;
+        }
+        for (thread, args) in ready {
+            resume(&thread, args)?;
+        }
+    }
+    scheduler.borrow_mut().active = false;
+    Ok(())
+}
+
+/// Wraps a blocking stdlib function so it yields to the scheduler when called from a task.
+///
+/// `start` takes the same arguments as `blocking`. It returns a poll function (see `poll_fn`) that hands back
+/// `blocking`'s results once they're ready.
+pub fn yielding(luau: &Lua, blocking: LuaFunction, start: LuaFunction) -> LuaResult<LuaFunction> {
+    let wrap = match luau.named_registry_value(YIELDING_REGISTRY_KEY)? {
+        LuaValue::Function(wrap) => wrap,
+        _ => {
+            let wrap: LuaFunction = luau.load(YIELDING_SRC).call(create_internal(luau)?)?;
+            luau.set_named_registry_value(YIELDING_REGISTRY_KEY, &wrap)?;
+            wrap
+        }
+    };
+    wrap.call((blocking, start))
+}
+
+/// Creates a poll function for a `yielding` function's `start`; `poll` returns `Some(results)` once the work's done.
+pub fn poll_fn<F>(luau: &Lua, mut poll: F) -> LuaResult<LuaFunction>
+where
+    F: FnMut(&Lua) -> LuaResult<Option<LuaMultiValue>> + 'static,
+{
+    luau.create_function_mut(move |luau: &Lua, _value: LuaMultiValue| -> LuaMultiResult {
+        match poll(luau)? {
+            Some(mut results) => {
+                results.push_front(LuaValue::Boolean(true));
+                Ok(results)
+            },
+            None => Ok(LuaMultiValue::from_vec(vec![LuaValue::Boolean(false)])),
+        }
+    })
+}
+
+/// Creates a `Wake` for a `yielding` function's `start` that resumes the task with `results` after `duration`.
+pub fn wake_after(luau: &Lua, duration: Duration, results: LuaMultiValue, function_name: &'static str) -> LuaValueResult {
+    let at = deadline(Instant::now(), duration, function_name)?;
+    Ok(LuaValue::UserData(luau.create_userdata(Wake { at, results })?))
+}
+
+/// `now + duration`, or an error if that's too far off for an `Instant`
+fn deadline(now: Instant, duration: Duration, function_name: &'static str) -> LuaResult<Instant> {
+    match now.checked_add(duration) {
+        Some(deadline) => Ok(deadline),
+        None => wrap_err!("{}: can't wait that long ({:?})", function_name, duration),
+    }
+}
+
+/// Runs `work` on a background thread. The returned poll function hands its result to `finish` (back on the
+/// Luau thread) once it's done.
+pub fn poll_background<T, W, F>(luau: &Lua, work: W, finish: F) -> LuaResult<LuaFunction>
+where
+    T: Send + 'static,
+    W: FnOnce() -> T + Send + 'static,
+    F: FnOnce(&Lua, T) -> LuaValueResult + 'static,
+{
+    let (sender, receiver) = crossbeam_channel::bounded(1);
+    thread::spawn(move || {
+        // the task might've been cancelled (and the receiver dropped) by now, which is fine
+        let _ = sender.send(work());
+    });
+    let mut finish = Some(finish);
+    poll_fn(luau, move |luau| match receiver.try_recv() {
+        Ok(result) => match finish.take() {
+            Some(finish) => Ok(Some(LuaMultiValue::from_vec(vec![finish(luau, result)?]))),
+            None => wrap_err!("task: [INTERNAL] background work was finished twice"),
+        },
+        Err(TryRecvError::Empty) => Ok(None),
+        Err(TryRecvError::Disconnected) => wrap_err!("task: background work stopped (panicked?) before finishing"),
+    })
+}
+
+fn thread_arg(value: Option<LuaValue>, function_name: &'static str) -> LuaResult<LuaThread> {
+    match value {
+        Some(LuaValue::Thread(thread)) => Ok(thread),
+        other => wrap_err!("{} expected a thread, got: {:?}", function_name, other),
+    }
+}
+
+fn seconds_arg(value: Option<LuaValue>, function_name: &'static str) -> LuaResult<Duration> {
+    let seconds = match value {
+        Some(LuaValue::Number(seconds)) => seconds,
+        Some(LuaValue::Integer(seconds)) => seconds as f64,
+        other => {
+            return wrap_err!("{} expected seconds to be a number, got: {:?}", function_name, other);
+        }
+    };
+    match Duration::try_from_secs_f64(seconds.max(0.0)) {
+        Ok(duration) => Ok(duration),
+        Err(_) => wrap_err!("{} expected seconds to be a finite number, got: {}", function_name, seconds),
+    }
+}
+
+/// errors unless `run` is driving the scheduler; used by functions that'd otherwise queue up tasks that never run
+fn expect_active(scheduler: &RefCell<Scheduler>, function_name: &'static str) -> LuaEmptyResult {
+    if scheduler.borrow().active {
+        Ok(())
+    } else {
+        wrap_err!("{}: tasks can't be scheduled here because nothing's running the scheduler (like in seal repl)", function_name)
+    }
+}
+
+/// the Rust half of `@std/task` (and `yielding.luau`); the Luau half checks arguments and does the actual yielding
+fn create_internal(luau: &Lua) -> LuaResult<LuaTable> {
+    let scheduler = get_scheduler(luau);
+    TableBuilder::create(luau)?
+        .with_function("can_yield", {
+            let scheduler = Rc::clone(&scheduler);
+            move |luau: &Lua, _value: LuaValue| -> LuaValueResult {
+                Ok(LuaValue::Boolean(scheduler.borrow().active && in_task(luau)?))
+            }
+        })?
+        .with_function("spawn", |luau: &Lua, mut multivalue: LuaMultiValue| -> LuaEmptyResult {
+            let thread = thread_arg(multivalue.pop_front(), "task.spawn")?;
+            own(luau, &thread)?;
+            resume(&thread, multivalue)
+        })?
+        .with_function("defer", {
+            let scheduler = Rc::clone(&scheduler);
+            move |luau: &Lua, mut multivalue: LuaMultiValue| -> LuaEmptyResult {
+                let function_name = "task.defer";
+                let thread = thread_arg(multivalue.pop_front(), function_name)?;
+                expect_active(&scheduler, function_name)?;
+                own(luau, &thread)?;
+                scheduler.borrow_mut().ready.push_back((thread, multivalue));
+                Ok(())
+            }
+        })?
+        .with_function("delay", {
+            let scheduler = Rc::clone(&scheduler);
+            move |luau: &Lua, mut multivalue: LuaMultiValue| -> LuaEmptyResult {
+                let function_name = "task.delay";
+                let duration = seconds_arg(multivalue.pop_front(), function_name)?;
+                let thread = thread_arg(multivalue.pop_front(), function_name)?;
+                expect_active(&scheduler, function_name)?;
+                let now = Instant::now();
+                let wake_at = deadline(now, duration, function_name)?;
+                own(luau, &thread)?;
+                scheduler.borrow_mut().sleeping.push(Sleeper { wake_at, since: now, thread, args: Some(multivalue) });
+                Ok(())
+            }
+        })?
+        .with_function("sleep", {
+            let scheduler = Rc::clone(&scheduler);
+            move |_luau: &Lua, mut multivalue: LuaMultiValue| -> LuaEmptyResult {
+                let function_name = "task.wait";
+                let duration = seconds_arg(multivalue.pop_front(), function_name)?;
+                let thread = thread_arg(multivalue.pop_front(), function_name)?;
+                expect_active(&scheduler, function_name)?;
+                let now = Instant::now();
+                let wake_at = deadline(now, duration, function_name)?;
+                scheduler.borrow_mut().sleeping.push(Sleeper { wake_at, since: now, thread, args: None });
+                Ok(())
+            }
+        })?
+        .with_function("block", |_luau: &Lua, mut multivalue: LuaMultiValue| -> LuaValueResult {
+            let duration = seconds_arg(multivalue.pop_front(), "task.wait")?;
+            let since = Instant::now();
+            thread::sleep(duration);
+            Ok(LuaValue::Number(since.elapsed().as_secs_f64()))
+        })?
+        .with_function("await", {
+            let scheduler = Rc::clone(&scheduler);
+            move |_luau: &Lua, mut multivalue: LuaMultiValue| -> LuaEmptyResult {
+                let function_name = "task: [INTERNAL] await";
+                let thread = thread_arg(multivalue.pop_front(), function_name)?;
+                expect_active(&scheduler, function_name)?;
+                let now = Instant::now();
+                match multivalue.pop_front() {
+                    Some(LuaValue::Function(poll)) => {
+                        scheduler.borrow_mut().waiting.push(Waiter { thread, poll, next_poll: now, interval: POLL_INTERVAL });
+                    },
+                    Some(LuaValue::UserData(wake)) if wake.is::<Wake>() => {
+                        let Wake { at, mut results } = wake.take::<Wake>()?;
+                        results.push_front(LuaValue::Boolean(true));
+                        scheduler.borrow_mut().sleeping.push(Sleeper { wake_at: at, since: now, thread, args: Some(results) });
+                    },
+                    other => {
+                        return wrap_err!("{} expected a poll function or Wake, got: {:?}", function_name, other);
+                    }
+                }
+                Ok(())
+            }
+        })?
+        .with_function("cancel", {
+            let scheduler = Rc::clone(&scheduler);
+            move |_luau: &Lua, mut multivalue: LuaMultiValue| -> LuaEmptyResult {
+                let thread = thread_arg(multivalue.pop_front(), "task.cancel")?;
+                scheduler.borrow_mut().cancel(&thread);
+                Ok(())
+            }
+        })?
+        .build_readonly()
+}
+
+pub fn create(luau: &Lua) -> LuaResult<LuaTable> {
+    luau.load(STD_TASK_SRC).call::<LuaTable>(create_internal(luau)?)
+}
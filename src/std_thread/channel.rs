@@ -47,7 +47,7 @@ impl<T> Clone for Sender<T> {
 /// we have to wrap crossbeam_channel::Receiver in our own newtype
 /// because of borrow checking rules (i originally wanted these methods on Channel instead)
 pub struct Receiver<T> {
-    receiver: crossbeam_channel::Receiver<T>
+    pub receiver: crossbeam_channel::Receiver<T>,
 }
 impl<T> Receiver<T> {
     pub fn try_recv(&self, function_name: &'static str) -> LuaResult<Option<T>> {
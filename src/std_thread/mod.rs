@@ -3,15 +3,19 @@ use std::sync::Mutex;
 use std::thread;
 
 use crate::prelude::*;
-use crate::{std_json, globals, err};
+use crate::{std_task, globals, err};
 use crossbeam_channel::TrySendError;
 use mluau::prelude::*;
 
 mod channel;
-mod thread_spawn_options;
+mod pool;
+mod select;
+mod shared;
+mod transit;
+pub mod thread_spawn_options;
 
 use thread_spawn_options::ThreadSpawnOptions;
-use channel::Channel;
+use channel::{Channel, Receiver};
 
 fn thread_sleep(_luau: &Lua, duration: LuaNumber) -> LuaValueResult {
     let dur = Duration::from_millis(duration as u64);
@@ -19,10 +23,34 @@ fn thread_sleep(_luau: &Lua, duration: LuaNumber) -> LuaValueResult {
     Ok(LuaValue::Boolean(true)) // ensure while thread.sleep(n) do end works
 }
 
+/// `thread.sleep` for @std/task tasks, which yields to other tasks instead of blocking them
+fn thread_sleep_start(luau: &Lua, duration: LuaNumber) -> LuaValueResult {
+    let duration = Duration::from_millis(duration as u64);
+    std_task::wake_after(luau, duration, LuaMultiValue::from_vec(vec![LuaValue::Boolean(true)]), "thread.sleep(milliseconds: number)")
+}
+
+/// `read_await`/`readbytes_await`, which yield to other @std/task tasks while waiting for a message
+fn read_await_function<T: 'static>(luau: &Lua, receiver: Receiver<T>, function_name: &'static str, convert: fn(&Lua, T) -> LuaValueResult) -> LuaResult<LuaFunction> {
+    let blocking = luau.create_function({
+        let receiver = receiver.clone();
+        move | luau: &Lua, _value: LuaValue | -> LuaValueResult {
+            convert(luau, receiver.recv_await(function_name)?)
+        }
+    })?;
+    let start = luau.create_function(move | luau: &Lua, _value: LuaValue | -> LuaResult<LuaFunction> {
+        let receiver = receiver.clone();
+        std_task::poll_fn(luau, move |luau| match receiver.try_recv(function_name)? {
+            Some(data) => Ok(Some(LuaMultiValue::from_vec(vec![convert(luau, data)?]))),
+            None => Ok(None),
+        })
+    })?;
+    std_task::yielding(luau, blocking, start)
+}
+
 struct Channels {
-    parent_to_child: Channel<String>,
+    parent_to_child: Channel<Vec<u8>>,
     parent_to_child_bytes: Channel<Vec<u8>>,
-    child_to_parent: Channel<String>,
+    child_to_parent: Channel<Vec<u8>>,
     child_to_parent_bytes: Channel<Vec<u8>>,
 }
 
@@ -57,8 +85,9 @@ fn thread_spawn(luau: &Lua, value: LuaValue) -> LuaValueResult {
         };
         
         globals::set_globals(&new_luau, options.chunk_name.clone())?;
-        // must use globals.set() due to safeenv
-        new_luau.globals().set("channel", TableBuilder::create(&new_luau)?
+        let parent_receiver = channels.parent_to_child.receiver.clone();
+        let parent_bytes_receiver = channels.parent_to_child_bytes.receiver.clone();
+        let channel = TableBuilder::create(&new_luau)?
             .with_function("read", {
                 let receiver = channels.parent_to_child.receiver.clone();
                 move | luau: &Lua, _value: LuaValue | -> LuaValueResult {
@@ -69,16 +98,7 @@ fn thread_spawn(luau: &Lua, value: LuaValue) -> LuaValueResult {
                     }
                 }
             })?
-            .with_function("read_await", {
-                let receiver = channels.parent_to_child.receiver;
-                move | luau: &Lua, _value: LuaValue | -> LuaValueResult {
-                    let function_name = "channel:read_await()";
-                    match receiver.recv_await(function_name) {
-                        Ok(data) => deserialize_data_from_transit(luau, data),
-                        Err(err) => Err(err)
-                    }
-                }
-            })?
+            .with_value("read_await", read_await_function(&new_luau, channels.parent_to_child.receiver, "channel:read_await()", deserialize_data_from_transit)?)?
             .with_function("readbytes", {
                 let receiver = channels.parent_to_child_bytes.receiver.clone();
                 move | luau: &Lua, _value: LuaValue | -> LuaValueResult {
@@ -89,20 +109,11 @@ fn thread_spawn(luau: &Lua, value: LuaValue) -> LuaValueResult {
                     }
                 }
             })?
-            .with_function("readbytes_await", {
-                let receiver = channels.parent_to_child_bytes.receiver;
-                move | luau: &Lua, _value: LuaValue | -> LuaValueResult {
-                    let function_name = "channel:readbytes_await()";
-                    match receiver.recv_await(function_name) {
-                        Ok(data) => ok_buffy(data, luau),
-                        Err(err) => Err(err)
-                    }
-                }
-            })?
+            .with_value("readbytes_await", read_await_function(&new_luau, channels.parent_to_child_bytes.receiver, "channel:readbytes_await()", |luau, data| ok_buffy(data, luau))?)?
             .with_function("send", {
                 let sender = channels.child_to_parent.sender.clone();
                 move | luau: &Lua, mut multivalue: LuaMultiValue | -> LuaEmptyResult {
-                    let function_name = "channel:send(data: string | JsonSerializableTable)";
+                    let function_name = "channel:send(data: Sendable)";
                     let _s = pop_self(&mut multivalue, function_name)?;
                     let value = match multivalue.pop_front() {
                         Some(v) => v,
@@ -117,12 +128,12 @@ fn thread_spawn(luau: &Lua, value: LuaValue) -> LuaValueResult {
             .with_function("try_send", {
                 let sender = channels.child_to_parent.sender.clone();
                 move | luau: &Lua, mut multivalue: LuaMultiValue | -> LuaMultiResult {
-                    let function_name = "channel:try_send(data: string | JsonSerializableTable)";
+                    let function_name = "channel:try_send(data: Sendable)";
                     let _s = pop_self(&mut multivalue, function_name)?;
                     let value = match multivalue.pop_front() {
                         Some(v) => v,
                         None => {
-                            return wrap_err!("{} called without 'data' (expected string or JsonSerializableTable, got nothing)", function_name);
+                            return wrap_err!("{} called without 'data' (expected Sendable data, got nothing)", function_name);
                         }
                     };
                     let data = serialize_data_for_transit(luau, value, function_name)?;
@@ -206,10 +217,14 @@ fn thread_spawn(luau: &Lua, value: LuaValue) -> LuaValueResult {
                 }
             })?
             .with_value("data", data)?
-            .build_readonly()?
-        )?;
+            .build_readonly()?;
+        select::register(&new_luau, &channel, &parent_receiver, &parent_bytes_receiver)?;
+        // must use globals.set() due to safeenv
+        new_luau.globals().set("channel", channel)?;
 
-        match new_luau.load(src).set_name(options.chunk_name).exec() {
+        let result = new_luau.load(src).set_name(options.chunk_name).into_function()
+            .and_then(|chunk| std_task::run(&new_luau, chunk));
+        match result {
             Ok(_) => Ok(()),
             Err(err) => {
                 let formatted_err = LuaError::external(format!("{}{}{}\n Error occurred in thread '{}', which was spawned at {}", colors::RED, err, colors::RESET, thread_name, options.spawned_at));
@@ -226,6 +241,8 @@ fn thread_spawn(luau: &Lua, value: LuaValue) -> LuaValueResult {
         }
     };
 
+    let child_receiver = channels.child_to_parent.receiver.clone();
+    let child_bytes_receiver = channels.child_to_parent_bytes.receiver.clone();
     let thread_handle = TableBuilder::create(luau)?
         .with_value("name", luau.create_string(options.name.clone())?)?
         .with_function("join", {
@@ -264,16 +281,7 @@ fn thread_spawn(luau: &Lua, value: LuaValue) -> LuaValueResult {
                 }
             }
         })?
-        .with_function("read_await", {
-            let receiver = channels.child_to_parent.receiver;
-            move | luau: &Lua, _value: LuaValue | -> LuaValueResult {
-                let function_name = "ThreadHandle:read_await()";
-                match receiver.recv_await(function_name) {
-                    Ok(data) => deserialize_data_from_transit(luau, data),
-                    Err(err) => Err(err)
-                }
-            }
-        })?
+        .with_value("read_await", read_await_function(luau, channels.child_to_parent.receiver, "ThreadHandle:read_await()", deserialize_data_from_transit)?)?
         .with_function("readbytes", {
             let receiver = channels.child_to_parent_bytes.receiver.clone();
             move | luau: &Lua, _value: LuaValue | -> LuaValueResult {
@@ -284,25 +292,16 @@ fn thread_spawn(luau: &Lua, value: LuaValue) -> LuaValueResult {
                 }
             }
         })?
-        .with_function("readbytes_await", {
-            let receiver = channels.child_to_parent_bytes.receiver;
-            move | luau: &Lua, _value: LuaValue | -> LuaValueResult {
-                let function_name = "ThreadHandle:readbytes_await()";
-                match receiver.recv_await(function_name) {
-                    Ok(data) => ok_buffy(data, luau),
-                    Err(err) => Err(err)
-                }
-            }
-        })?
+        .with_value("readbytes_await", read_await_function(luau, channels.child_to_parent_bytes.receiver, "ThreadHandle:readbytes_await()", |luau, data| ok_buffy(data, luau))?)?
         .with_function("send", {
             let sender = channels.parent_to_child.sender.clone();
             move | luau: &Lua, mut multivalue: LuaMultiValue | -> LuaEmptyResult {
-                let function_name = "ThreadHandle:send(data: string | JsonSerializableTable)";
+                let function_name = "ThreadHandle:send(data: Sendable)";
                 let _s = pop_self(&mut multivalue, function_name)?;
                 let value = match multivalue.pop_front() {
                     Some(v) => v,
                     None => {
-                        return wrap_err!("{} called without 'data' (expected string or JsonSerializableTable, got nothing)", function_name);
+                        return wrap_err!("{} called without 'data' (expected Sendable data, got nothing)", function_name);
                     }
                 };
                 let data = serialize_data_for_transit(luau, value, function_name)?;
@@ -312,12 +311,12 @@ fn thread_spawn(luau: &Lua, value: LuaValue) -> LuaValueResult {
         .with_function("try_send", {
             let sender = channels.parent_to_child.sender;
             move | luau: &Lua, mut multivalue: LuaMultiValue | -> LuaMultiResult {
-                let function_name = "ThreadHandle:try_send(data: string | JsonSerializableTable)";
+                let function_name = "ThreadHandle:try_send(data: Sendable)";
                 let _s = pop_self(&mut multivalue, function_name)?;
                 let value = match multivalue.pop_front() {
                     Some(v) => v,
                     None => {
-                        return wrap_err!("{} called without 'data' (expected string or JsonSerializableTable, got nothing)", function_name);
+                        return wrap_err!("{} called without 'data' (expected Sendable data, got nothing)", function_name);
                     }
                 };
                 let data = serialize_data_for_transit(luau, value, function_name)?;
@@ -400,34 +399,22 @@ fn thread_spawn(luau: &Lua, value: LuaValue) -> LuaValueResult {
                 }
             }
         })?
-        .build_readonly();
+        .build_readonly()?;
+    select::register(luau, &thread_handle, &child_receiver, &child_bytes_receiver)?;
 
-    ok_table(thread_handle)
+    Ok(LuaValue::Table(thread_handle))
 }
 
-fn serialize_data_for_transit(luau: &Lua, value: LuaValue, function_name: &'static str) -> LuaResult<String> {
-    let data = match value {
-        LuaValue::Table(data) => {
-            match std_json::encode(luau, data, std_json::EncodeOptions::default()) {
-                Ok(data) => data,
-                Err(err) => {
-                    return wrap_err!("{}: unable to serialize table (to send across the wire) due to err: {}", function_name, err);
-                }
-            }
-        },
-        LuaValue::String(s) => s.to_str()?.to_string(),
-        other => {
-            return wrap_err!("{} expected data to be a string or json-serializable table, got: {:?}", function_name, other);
-        }
-    };
-    Ok(data)
+/// encodes `value` with our binary structured-clone format (see transit.rs) so it can be sent to another thread
+fn serialize_data_for_transit(_luau: &Lua, value: LuaValue, function_name: &'static str) -> LuaResult<Vec<u8>> {
+    if value.is_nil() {
+        return wrap_err!("{} expected data to not be nil (reading nil means there was nothing to read)", function_name);
+    }
+    transit::encode(&value, function_name)
 }
 
-fn deserialize_data_from_transit(luau: &Lua, data: String) -> LuaValueResult {
-    match std_json::json_decode(luau, data.clone()) {
-        Ok(d) => Ok(d),
-        Err(_) => ok_string(data, luau),
-    }
+fn deserialize_data_from_transit(luau: &Lua, data: Vec<u8>) -> LuaValueResult {
+    transit::decode(luau, &data)
 }
 
 fn pop_self(multivalue: &mut LuaMultiValue, function_name: &'static str) -> LuaResult<LuaTable> {
@@ -445,6 +432,9 @@ fn pop_self(multivalue: &mut LuaMultiValue, function_name: &'static str) -> LuaR
 pub fn create(luau: &Lua) -> LuaResult<LuaTable> {
     TableBuilder::create(luau)?
         .with_function("spawn", thread_spawn)?
-        .with_function("sleep", thread_sleep)?
+        .with_function("pool", pool::thread_pool)?
+        .with_value("select", select::create(luau)?)?
+        .with_value("shared", shared::create(luau)?)?
+        .with_value("sleep", std_task::yielding(luau, luau.create_function(thread_sleep)?, luau.create_function(thread_sleep_start)?)?)?
         .build_readonly()
 }
\ No newline at end of file
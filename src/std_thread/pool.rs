@@ -0,0 +1,375 @@
+//! `thread.pool`: a fixed number of worker threads, each keeping its own warm Luau VM, that take jobs off a shared queue.
+//!
+//! Like `server.serve`'s workers, each worker runs the pool's module once and calls the function it returns for every job.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::thread;
+
+use crate::err::display_error_and_exit;
+use crate::prelude::*;
+use crate::{globals, std_task};
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use mluau::prelude::*;
+
+use super::thread_spawn_options::ThreadSpawnOptions;
+use super::{deserialize_data_from_transit, serialize_data_for_transit};
+
+/// what a job's handler returned (`None` for nil), or the error it threw, with its traceback
+type JobResult = Result<Option<Vec<u8>>, String>;
+
+struct Job {
+    id: u64,
+    data: Option<Vec<u8>>,
+}
+
+struct Pool {
+    name: String,
+    /// `None` once the pool's closed
+    jobs: Option<Sender<Job>>,
+    results: Receiver<(u64, JobResult)>,
+    /// results that came in while we were waiting for other jobs
+    finished: HashMap<u64, JobResult>,
+    next_id: u64,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+impl Pool {
+    fn submit(&mut self, data: Option<Vec<u8>>, function_name: &'static str) -> LuaResult<u64> {
+        let Some(jobs) = &self.jobs else {
+            return wrap_err!("{}: thread pool '{}' is already closed", function_name, self.name);
+        };
+        let id = self.next_id;
+        self.next_id += 1;
+        match jobs.send(Job { id, data }) {
+            Ok(_) => Ok(id),
+            Err(_) => wrap_err!("{}: all of thread pool '{}''s workers unexpectedly exited", function_name, self.name),
+        }
+    }
+
+    /// moves results that have come in into `finished`, blocking until at least one does if `block` is set
+    fn collect(&mut self, block: bool, function_name: &'static str) -> LuaEmptyResult {
+        if block {
+            match self.results.recv() {
+                Ok((id, result)) => {
+                    self.finished.insert(id, result);
+                },
+                Err(_) => {
+                    return wrap_err!("{}: all of thread pool '{}''s workers unexpectedly exited", function_name, self.name);
+                }
+            }
+        }
+        loop {
+            match self.results.try_recv() {
+                Ok((id, result)) => {
+                    self.finished.insert(id, result);
+                },
+                Err(TryRecvError::Empty) => return Ok(()),
+                Err(TryRecvError::Disconnected) => {
+                    return wrap_err!("{}: all of thread pool '{}''s workers unexpectedly exited", function_name, self.name);
+                }
+            }
+        }
+    }
+
+    /// takes the results for all of `ids` (in order) if they're all in
+    fn take(&mut self, ids: &[u64]) -> Option<Vec<JobResult>> {
+        if !ids.iter().all(|id| self.finished.contains_key(id)) {
+            return None;
+        }
+        Some(ids.iter().filter_map(|id| self.finished.remove(id)).collect())
+    }
+
+    /// blocks until all of `ids` are done
+    fn wait(&mut self, ids: &[u64], function_name: &'static str) -> LuaResult<Vec<JobResult>> {
+        loop {
+            self.collect(false, function_name)?;
+            if let Some(results) = self.take(ids) {
+                return Ok(results);
+            }
+            self.collect(true, function_name)?;
+        }
+    }
+
+    /// like `wait`, but returns a poll function for @std/task tasks; `finish` turns the results into the return value
+    fn poll_wait<F>(pool: &Rc<RefCell<Pool>>, luau: &Lua, ids: Vec<u64>, function_name: &'static str, finish: F) -> LuaResult<LuaFunction>
+    where
+        F: Fn(&Lua, &str, Vec<JobResult>) -> LuaValueResult + 'static,
+    {
+        let pool = Rc::clone(pool);
+        std_task::poll_fn(luau, move |luau| {
+            let mut pool = pool.borrow_mut();
+            pool.collect(false, function_name)?;
+            match pool.take(&ids) {
+                Some(results) => Ok(Some(LuaMultiValue::from_vec(vec![finish(luau, &pool.name, results)?]))),
+                None => Ok(None),
+            }
+        })
+    }
+
+    fn close(&mut self, function_name: &'static str) -> LuaEmptyResult {
+        // workers stop once the queue's empty and there's no more sender
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            if let Err(err) = worker.join() {
+                return wrap_err!("{}: unable to join thread pool '{}''s worker due to err: {:?}", function_name, self.name, err);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn job_value(luau: &Lua, pool_name: &str, result: JobResult, function_name: &'static str) -> LuaValueResult {
+    match result {
+        Ok(Some(data)) => deserialize_data_from_transit(luau, data),
+        Ok(None) => Ok(LuaNil),
+        Err(err) => wrap_err!("{}: job failed in thread pool '{}': {}", function_name, pool_name, err),
+    }
+}
+
+fn job_values(luau: &Lua, pool_name: &str, results: Vec<JobResult>, function_name: &'static str) -> LuaValueResult {
+    let values = luau.create_table_with_capacity(results.len(), 0)?;
+    for result in results {
+        values.raw_push(job_value(luau, pool_name, result, function_name)?)?;
+    }
+    Ok(LuaValue::Table(values))
+}
+
+fn job_data(luau: &Lua, value: LuaValue, function_name: &'static str) -> LuaResult<Option<Vec<u8>>> {
+    match value {
+        LuaNil => Ok(None),
+        value => serialize_data_for_transit(luau, value, function_name).map(Some),
+    }
+}
+
+fn run_job(luau: &Lua, handler: &LuaFunction, data: Option<Vec<u8>>) -> JobResult {
+    let function_name = "thread.pool worker";
+    let data = match data {
+        Some(data) => deserialize_data_from_transit(luau, data).map_err(|err| err.to_string())?,
+        None => LuaNil,
+    };
+    // errors from calling the handler come with its traceback
+    match handler.call::<LuaValue>(data) {
+        Ok(LuaNil) => Ok(None),
+        Ok(value) => serialize_data_for_transit(luau, value, function_name).map(Some).map_err(|err| err.to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// `thread.pool(options: PoolOptions)`
+pub fn thread_pool(luau: &Lua, value: LuaValue) -> LuaValueResult {
+    let function_name = "thread.pool(options: PoolOptions)";
+    let options_table = match value {
+        LuaValue::Table(options_table) => options_table,
+        other => {
+            return wrap_err!("{} expected options to be a PoolOptions table (with fields path or src and optionally size), got: {:?}", function_name, other);
+        }
+    };
+    let size = match options_table.raw_get("size")? {
+        LuaValue::Integer(size) if size > 0 => int_to_usize(size, function_name, "size")?,
+        LuaNil => thread::available_parallelism().map(|n| n.get()).unwrap_or(4),
+        other => {
+            return wrap_err!("{} expected PoolOptions.size to be a positive integer or nil (defaults to number of cpus), got: {:?}", function_name, other);
+        }
+    };
+    let options = ThreadSpawnOptions::from_table(options_table, luau, function_name)?;
+    let src = options.get_src(function_name)?;
+
+    let (job_sender, job_receiver) = crossbeam_channel::unbounded::<Job>();
+    let (result_sender, result_receiver) = crossbeam_channel::unbounded::<(u64, JobResult)>();
+
+    let mut workers = Vec::with_capacity(size);
+    for index in 0..size {
+        let job_receiver = job_receiver.clone();
+        let result_sender = result_sender.clone();
+        let src = src.clone();
+        let chunk_name = options.chunk_name.clone();
+        let data = options.data.clone();
+        let worker_name = format!("{}-{}", options.name, index + 1);
+        let spawned_at = options.spawned_at.clone();
+        let handle_result = thread::Builder::new().name(worker_name.clone()).spawn(move || {
+            let worker_luau = Lua::default();
+            let handler = worker_luau.sandbox(true)
+                .and_then(|_| globals::set_globals(&worker_luau, &chunk_name))
+                .and_then(|_| {
+                    let data = match data {
+                        Some(data) => deserialize_data_from_transit(&worker_luau, data)?,
+                        None => LuaNil,
+                    };
+                    worker_luau.load(src).set_name(&chunk_name).call::<LuaValue>(data)
+                })
+                .and_then(|value| match value {
+                    LuaValue::Function(handler) => Ok(handler),
+                    other => wrap_err!("thread.pool: worker module '{}' should return a function that handles each job, got: {:?}", chunk_name, other),
+                });
+            let handler = match handler {
+                Ok(handler) => handler,
+                Err(err) => {
+                    let formatted_err = LuaError::external(format!("{}{}{}\n Error occurred in thread pool worker '{}', which was spawned at {}",
+                        colors::RED, err, colors::RESET, worker_name, spawned_at));
+                    display_error_and_exit(formatted_err);
+                }
+            };
+            // recv errs once the pool's closed (or garbage collected) and every job's been taken
+            while let Ok(job) = job_receiver.recv() {
+                let result = run_job(&worker_luau, &handler, job.data);
+                if result_sender.send((job.id, result)).is_err() {
+                    break;
+                }
+            }
+        });
+        match handle_result {
+            Ok(handle) => workers.push(handle),
+            Err(err) => {
+                return wrap_err!("{}: can't spawn thread pool worker due to io error: {}", function_name, err);
+            }
+        }
+    }
+
+    let pool = Rc::new(RefCell::new(Pool {
+        name: options.name.clone(),
+        jobs: Some(job_sender),
+        results: result_receiver,
+        finished: HashMap::new(),
+        next_id: 0,
+        workers,
+    }));
+
+    let map = {
+        let function_name = "ThreadPool:map(items: { any })";
+        let submit_all = {
+            let pool = Rc::clone(&pool);
+            move |luau: &Lua, mut multivalue: LuaMultiValue| -> LuaResult<Vec<u64>> {
+                pop_self(&mut multivalue, function_name)?;
+                let items = match multivalue.pop_front() {
+                    Some(LuaValue::Table(items)) => items,
+                    other => {
+                        return wrap_err!("{} expected items to be an array-like table, got: {:?}", function_name, other);
+                    }
+                };
+                let mut pool = pool.borrow_mut();
+                let mut ids = Vec::with_capacity(items.raw_len());
+                for item in items.sequence_values::<LuaValue>() {
+                    let data = job_data(luau, item?, function_name)?;
+                    ids.push(pool.submit(data, function_name)?);
+                }
+                Ok(ids)
+            }
+        };
+        let submit_all = Rc::new(submit_all);
+        let blocking = luau.create_function({
+            let pool = Rc::clone(&pool);
+            let submit_all = Rc::clone(&submit_all);
+            move |luau: &Lua, multivalue: LuaMultiValue| -> LuaValueResult {
+                let ids = submit_all(luau, multivalue)?;
+                let mut pool = pool.borrow_mut();
+                let results = pool.wait(&ids, function_name)?;
+                job_values(luau, &pool.name, results, function_name)
+            }
+        })?;
+        let start = luau.create_function({
+            let pool = Rc::clone(&pool);
+            move |luau: &Lua, multivalue: LuaMultiValue| -> LuaResult<LuaFunction> {
+                let ids = submit_all(luau, multivalue)?;
+                Pool::poll_wait(&pool, luau, ids, function_name, move |luau, pool_name, results| {
+                    job_values(luau, pool_name, results, function_name)
+                })
+            }
+        })?;
+        std_task::yielding(luau, blocking, start)?
+    };
+
+    TableBuilder::create(luau)?
+        .with_value("name", options.name)?
+        .with_value("size", size)?
+        .with_function("submit", {
+            let pool = Rc::clone(&pool);
+            move |luau: &Lua, mut multivalue: LuaMultiValue| -> LuaValueResult {
+                let function_name = "ThreadPool:submit(data: any)";
+                pop_self(&mut multivalue, function_name)?;
+                let data = job_data(luau, multivalue.pop_front().unwrap_or(LuaNil), function_name)?;
+                let id = pool.borrow_mut().submit(data, function_name)?;
+                ok_table(create_pool_job(luau, &pool, id))
+            }
+        })?
+        .with_value("map", map)?
+        .with_function("close", {
+            let pool = Rc::clone(&pool);
+            move |_luau: &Lua, mut multivalue: LuaMultiValue| -> LuaEmptyResult {
+                let function_name = "ThreadPool:close()";
+                pop_self(&mut multivalue, function_name)?;
+                pool.borrow_mut().close(function_name)
+            }
+        })?
+        .build_readonly()
+        .map(LuaValue::Table)
+}
+
+/// a PoolJob's value (or error) once it's been awaited, since its result only comes back from the pool once
+type Awaited = Rc<RefCell<Option<LuaValueResult>>>;
+
+fn settle(awaited: &Awaited, result: LuaValueResult) -> LuaValueResult {
+    *awaited.borrow_mut() = Some(result.clone());
+    result
+}
+
+fn create_pool_job(luau: &Lua, pool: &Rc<RefCell<Pool>>, id: u64) -> LuaResult<LuaTable> {
+    let function_name = "PoolJob:await()";
+    let awaited: Awaited = Rc::default();
+    let blocking = luau.create_function({
+        let pool = Rc::clone(pool);
+        let awaited = Rc::clone(&awaited);
+        move |luau: &Lua, mut multivalue: LuaMultiValue| -> LuaValueResult {
+            pop_self(&mut multivalue, function_name)?;
+            if let Some(result) = awaited.borrow().clone() {
+                return result;
+            }
+            let mut pool = pool.borrow_mut();
+            let mut results = pool.wait(&[id], function_name)?;
+            settle(&awaited, job_value(luau, &pool.name, results.remove(0), function_name))
+        }
+    })?;
+    let start = luau.create_function({
+        let pool = Rc::clone(pool);
+        let awaited = Rc::clone(&awaited);
+        move |luau: &Lua, mut multivalue: LuaMultiValue| -> LuaResult<LuaFunction> {
+            pop_self(&mut multivalue, function_name)?;
+            let pool = Rc::clone(&pool);
+            let awaited = Rc::clone(&awaited);
+            std_task::poll_fn(luau, move |luau| {
+                // another task might've awaited (and taken) the result while we were waiting on it
+                if let Some(result) = awaited.borrow().clone() {
+                    return Ok(Some(LuaMultiValue::from_vec(vec![result?])));
+                }
+                let mut pool = pool.borrow_mut();
+                pool.collect(false, function_name)?;
+                match pool.take(&[id]) {
+                    Some(mut results) => {
+                        let value = settle(&awaited, job_value(luau, &pool.name, results.remove(0), function_name))?;
+                        Ok(Some(LuaMultiValue::from_vec(vec![value])))
+                    },
+                    None => Ok(None),
+                }
+            })
+        }
+    })?;
+    TableBuilder::create(luau)?
+        .with_value("id", id)?
+        .with_value("await", std_task::yielding(luau, blocking, start)?)?
+        .with_function("done", {
+            let pool = Rc::clone(pool);
+            let awaited = Rc::clone(&awaited);
+            move |_luau: &Lua, mut multivalue: LuaMultiValue| -> LuaValueResult {
+                let function_name = "PoolJob:done()";
+                pop_self(&mut multivalue, function_name)?;
+                if awaited.borrow().is_some() {
+                    return Ok(LuaValue::Boolean(true));
+                }
+                let mut pool = pool.borrow_mut();
+                pool.collect(false, function_name)?;
+                Ok(LuaValue::Boolean(pool.finished.contains_key(&id)))
+            }
+        })?
+        .build_readonly()
+}
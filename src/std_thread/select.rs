@@ -0,0 +1,205 @@
+//! `thread.select`: wait on several `ThreadHandle`s (or a child thread's `channel`) at once, instead of polling each one's `read()`.
+//!
+//! Only thread channels can be selected on; ChildProcess streams and `fs.watch` watchers aren't channels underneath.
+
+use std::time::{Duration, Instant};
+
+use crate::prelude::*;
+use crate::std_task;
+use crossbeam_channel::{Select, TryRecvError};
+use mluau::prelude::*;
+
+use super::channel::Receiver;
+use super::deserialize_data_from_transit;
+
+/// weak-keyed table of selectable tables (ThreadHandles, `channel`) -> their `Selectable` receivers
+const SELECTABLES_REGISTRY_KEY: &str = "seal.thread.selectables";
+
+/// the receiving ends of a ThreadHandle or `channel`
+#[derive(Clone)]
+pub struct Selectable {
+    regular: crossbeam_channel::Receiver<Vec<u8>>,
+    bytes: crossbeam_channel::Receiver<Vec<u8>>,
+}
+impl LuaUserData for Selectable {}
+
+enum Message {
+    Regular(Vec<u8>),
+    Bytes(Vec<u8>),
+}
+
+fn selectables(luau: &Lua) -> LuaResult<LuaTable> {
+    match luau.named_registry_value(SELECTABLES_REGISTRY_KEY)? {
+        LuaValue::Table(selectables) => Ok(selectables),
+        _ => {
+            let selectables = luau.create_table()?;
+            let metatable = luau.create_table()?;
+            // so handles can still be garbage collected
+            metatable.raw_set("__mode", "k")?;
+            selectables.set_metatable(Some(metatable))?;
+            luau.set_named_registry_value(SELECTABLES_REGISTRY_KEY, &selectables)?;
+            Ok(selectables)
+        }
+    }
+}
+
+/// lets `source` be passed to `thread.select`, which receives on `regular` and `bytes`
+pub fn register(luau: &Lua, source: &LuaTable, regular: &Receiver<Vec<u8>>, bytes: &Receiver<Vec<u8>>) -> LuaEmptyResult {
+    let selectable = Selectable {
+        regular: regular.receiver.clone(),
+        bytes: bytes.receiver.clone(),
+    };
+    selectables(luau)?.raw_set(source.clone(), luau.create_userdata(selectable)?)
+}
+
+type Sources = Vec<(LuaValue, Selectable)>;
+
+fn select_args(luau: &Lua, mut multivalue: LuaMultiValue, function_name: &'static str) -> LuaResult<(Sources, Option<Duration>)> {
+    let sources_table = match multivalue.pop_front() {
+        Some(LuaValue::Table(sources)) => sources,
+        other => {
+            return wrap_err!("{} expected sources to be an array of ThreadHandles (or channel), got: {:?}", function_name, other);
+        }
+    };
+    let timeout = match multivalue.pop_front() {
+        Some(LuaValue::Integer(ms)) if ms >= 0 => Some(Duration::from_millis(ms as u64)),
+        // math.huge means waiting forever
+        Some(LuaValue::Number(ms)) if ms == f64::INFINITY => None,
+        Some(LuaValue::Number(ms)) if ms >= 0.0 => match Duration::try_from_secs_f64(ms / 1000.0) {
+            Ok(timeout) => Some(timeout),
+            Err(err) => {
+                return wrap_err!("{}: error creating Duration from timeout of {} ms: {}", function_name, ms, err);
+            }
+        },
+        Some(LuaNil) | None => None,
+        Some(other) => {
+            return wrap_err!("{} expected timeout to be a positive number (of milliseconds) or nil, got: {:?}", function_name, other);
+        }
+    };
+
+    let selectables = selectables(luau)?;
+    let mut sources = Vec::new();
+    for (index, source) in sources_table.sequence_values::<LuaValue>().enumerate() {
+        let source = source?;
+        let selectable = match selectables.raw_get::<LuaValue>(source.clone())? {
+            LuaValue::UserData(ud) => ud.borrow::<Selectable>()?.clone(),
+            _ => {
+                // ChildProcess streams and fs.watch watchers buffer their data themselves instead of using channels we can select on
+                return wrap_err!(
+                    "{} expected sources[{}] to be a ThreadHandle or channel (ChildProcess streams and fs.watch watchers aren't selectable), got: {:?}",
+                    function_name, index + 1, source
+                );
+            }
+        };
+        sources.push((source, selectable));
+    }
+    if sources.is_empty() {
+        return wrap_err!("{}: nothing to select on (sources is empty)", function_name);
+    }
+    Ok((sources, timeout))
+}
+
+fn selected(luau: &Lua, sources: &Sources, received: Option<(usize, Message)>) -> LuaMultiResult {
+    let Some((index, message)) = received else {
+        return Ok(LuaMultiValue::from_vec(vec![LuaNil]));
+    };
+    let message = match message {
+        Message::Regular(data) => deserialize_data_from_transit(luau, data)?,
+        Message::Bytes(data) => ok_buffy(data, luau)?,
+    };
+    Ok(LuaMultiValue::from_vec(vec![sources[index].0.clone(), message]))
+}
+
+fn all_disconnected<T>(function_name: &'static str) -> LuaResult<T> {
+    wrap_err!("{}: every source is disconnected (their threads have all exited), so nothing can be received", function_name)
+}
+
+fn select_blocking(sources: &Sources, timeout: Option<Duration>, function_name: &'static str) -> LuaResult<Option<(usize, Message)>> {
+    let mut select = Select::new();
+    // select operation index -> (source index, whether it's the bytes channel)
+    let mut operations = Vec::with_capacity(sources.len() * 2);
+    for (index, (_, selectable)) in sources.iter().enumerate() {
+        select.recv(&selectable.regular);
+        operations.push((index, false));
+        select.recv(&selectable.bytes);
+        operations.push((index, true));
+    }
+    // timeouts too far off to represent are as good as none
+    let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+    let mut connected = operations.len();
+    loop {
+        if connected == 0 {
+            return all_disconnected(function_name);
+        }
+        let operation = match deadline {
+            Some(deadline) => match select.select_deadline(deadline) {
+                Ok(operation) => operation,
+                Err(_) => return Ok(None),
+            },
+            None => select.select(),
+        };
+        let operation_index = operation.index();
+        let (index, is_bytes) = operations[operation_index];
+        let selectable = &sources[index].1;
+        let received = if is_bytes {
+            operation.recv(&selectable.bytes).map(Message::Bytes)
+        } else {
+            operation.recv(&selectable.regular).map(Message::Regular)
+        };
+        match received {
+            Ok(message) => return Ok(Some((index, message))),
+            // disconnected channels are always ready; stop selecting on them
+            Err(_) => {
+                select.remove(operation_index);
+                connected -= 1;
+            }
+        }
+    }
+}
+
+fn try_select(sources: &Sources, function_name: &'static str) -> LuaResult<Option<(usize, Message)>> {
+    let mut connected = 0;
+    for (index, (_, selectable)) in sources.iter().enumerate() {
+        match selectable.regular.try_recv() {
+            Ok(data) => return Ok(Some((index, Message::Regular(data)))),
+            Err(TryRecvError::Empty) => connected += 1,
+            Err(TryRecvError::Disconnected) => {},
+        }
+        match selectable.bytes.try_recv() {
+            Ok(data) => return Ok(Some((index, Message::Bytes(data)))),
+            Err(TryRecvError::Empty) => connected += 1,
+            Err(TryRecvError::Disconnected) => {},
+        }
+    }
+    if connected == 0 {
+        return all_disconnected(function_name);
+    }
+    Ok(None)
+}
+
+fn thread_select(luau: &Lua, multivalue: LuaMultiValue) -> LuaMultiResult {
+    let function_name = "thread.select(sources: { ThreadHandle | channel }, timeout: number?)";
+    let (sources, timeout) = select_args(luau, multivalue, function_name)?;
+    let received = select_blocking(&sources, timeout, function_name)?;
+    selected(luau, &sources, received)
+}
+
+/// `thread.select` for @std/task tasks, which yields to other tasks while waiting
+fn thread_select_start(luau: &Lua, multivalue: LuaMultiValue) -> LuaResult<LuaFunction> {
+    let function_name = "thread.select(sources: { ThreadHandle | channel }, timeout: number?)";
+    let (sources, timeout) = select_args(luau, multivalue, function_name)?;
+    let give_up_at = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+    std_task::poll_fn(luau, move |luau| {
+        match try_select(&sources, function_name)? {
+            Some(received) => Ok(Some(selected(luau, &sources, Some(received))?)),
+            None if give_up_at.is_some_and(|give_up_at| Instant::now() >= give_up_at) => {
+                Ok(Some(selected(luau, &sources, None)?))
+            },
+            None => Ok(None),
+        }
+    })
+}
+
+pub fn create(luau: &Lua) -> LuaResult<LuaFunction> {
+    std_task::yielding(luau, luau.create_function(thread_select)?, luau.create_function(thread_select_start)?)
+}
@@ -0,0 +1,315 @@
+//! `thread.shared`: state that every thread can read and write directly, instead of copying messages through channels.
+//!
+//! Shared objects are looked up by name in process-wide registries, so `thread.shared.counter("progress")`
+//! returns the same counter in whichever thread calls it. They live until seal exits.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, MutexGuard};
+
+use crate::prelude::*;
+use mluau::prelude::*;
+
+use super::transit;
+
+static COUNTERS: LazyLock<Mutex<HashMap<String, Arc<AtomicI64>>>> = LazyLock::new(Default::default);
+static STORES: LazyLock<Mutex<HashMap<String, Arc<Mutex<HashMap<String, Vec<u8>>>>>>> = LazyLock::new(Default::default);
+static BUFFERS: LazyLock<Mutex<HashMap<String, Arc<Mutex<Vec<u8>>>>>> = LazyLock::new(Default::default);
+
+fn lock<'a, T>(mutex: &'a Mutex<T>, function_name: &'static str) -> LuaResult<MutexGuard<'a, T>> {
+    match mutex.lock() {
+        Ok(guard) => Ok(guard),
+        // only happens if another thread panicked while holding the lock
+        Err(err) => wrap_err!("{}: unable to lock shared state due to err: {}", function_name, err),
+    }
+}
+
+/// the shared stores/buffers (by `key()`) this VM is in the middle of a transaction on
+#[derive(Default)]
+struct Transactions(HashSet<String>);
+
+/// locks `mutex`, unless this VM's already holding it in a transaction (where locking again would deadlock)
+fn lock_outside_transaction<'a, T>(luau: &Lua, mutex: &'a Mutex<T>, key: &str, function_name: &'static str) -> LuaResult<MutexGuard<'a, T>> {
+    if let Some(transactions) = luau.app_data_ref::<Transactions>() && transactions.0.contains(key) {
+        return wrap_err!("{}: can't use {} inside its own transaction; use the value passed to the transaction callback instead", function_name, key);
+    }
+    lock(mutex, function_name)
+}
+
+/// runs `f` while `key`'s marked as in a transaction
+fn in_transaction<R>(luau: &Lua, key: &str, f: impl FnOnce() -> LuaResult<R>) -> LuaResult<R> {
+    if luau.app_data_ref::<Transactions>().is_none() {
+        luau.set_app_data(Transactions::default());
+    }
+    if let Some(mut transactions) = luau.app_data_mut::<Transactions>() {
+        transactions.0.insert(key.to_string());
+    }
+    let result = f();
+    if let Some(mut transactions) = luau.app_data_mut::<Transactions>() {
+        transactions.0.remove(key);
+    }
+    result
+}
+
+fn name_arg(value: Option<LuaValue>, function_name: &'static str) -> LuaResult<String> {
+    match value {
+        Some(LuaValue::String(name)) => Ok(name.to_str()?.to_string()),
+        other => wrap_err!("{} expected name to be a string, got: {:?}", function_name, other),
+    }
+}
+
+fn integer_arg(value: LuaValue, parameter_name: &'static str, function_name: &'static str) -> LuaResult<i64> {
+    match value {
+        LuaValue::Integer(n) => Ok(n),
+        LuaValue::Number(n) if n.fract() == 0.0 => Ok(n as i64),
+        other => wrap_err!("{} expected {} to be an integer, got: {:?}", function_name, parameter_name, other),
+    }
+}
+
+/// an atomic integer from `thread.shared.counter`
+pub struct SharedCounter {
+    name: String,
+    value: Arc<AtomicI64>,
+}
+
+impl LuaUserData for SharedCounter {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_meta_field("__type", "SharedCounter");
+        fields.add_field_method_get("name", |_luau: &Lua, this: &SharedCounter| Ok(this.name.clone()));
+    }
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("get", |_luau: &Lua, this: &SharedCounter, _: LuaValue| -> LuaResult<i64> {
+            Ok(this.value.load(Ordering::SeqCst))
+        });
+        methods.add_method("set", |_luau: &Lua, this: &SharedCounter, value: LuaValue| -> LuaEmptyResult {
+            let function_name = "SharedCounter:set(value: number)";
+            this.value.store(integer_arg(value, "value", function_name)?, Ordering::SeqCst);
+            Ok(())
+        });
+        methods.add_method("add", |_luau: &Lua, this: &SharedCounter, delta: LuaValue| -> LuaResult<i64> {
+            let function_name = "SharedCounter:add(delta: number?)";
+            let delta = match delta {
+                LuaNil => 1,
+                delta => integer_arg(delta, "delta", function_name)?,
+            };
+            Ok(this.value.fetch_add(delta, Ordering::SeqCst).wrapping_add(delta))
+        });
+        methods.add_method("compare_and_set", |_luau: &Lua, this: &SharedCounter, (expected, new): (LuaValue, LuaValue)| -> LuaResult<(bool, i64)> {
+            let function_name = "SharedCounter:compare_and_set(expected: number, new: number)";
+            let expected = integer_arg(expected, "expected", function_name)?;
+            let new = integer_arg(new, "new", function_name)?;
+            match this.value.compare_exchange(expected, new, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(previous) => Ok((true, previous)),
+                Err(actual) => Ok((false, actual)),
+            }
+        });
+    }
+}
+
+/// `thread.shared.counter(name: string, initial: number?)`
+fn shared_counter(luau: &Lua, mut multivalue: LuaMultiValue) -> LuaValueResult {
+    let function_name = "thread.shared.counter(name: string, initial: number?)";
+    let name = name_arg(multivalue.pop_front(), function_name)?;
+    let initial = match multivalue.pop_front() {
+        Some(LuaNil) | None => 0,
+        Some(initial) => integer_arg(initial, "initial", function_name)?,
+    };
+    let value = Arc::clone(lock(&COUNTERS, function_name)?
+        .entry(name.clone())
+        .or_insert_with(|| Arc::new(AtomicI64::new(initial))));
+    ok_userdata(SharedCounter { name, value }, luau)
+}
+
+/// a lock-protected key/value store from `thread.shared.store`; values are kept in transit format
+pub struct SharedStore {
+    name: String,
+    entries: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+}
+
+impl SharedStore {
+    fn key(&self) -> String {
+        format!("SharedStore '{}'", self.name)
+    }
+}
+
+fn store_key(key: LuaValue, function_name: &'static str) -> LuaResult<String> {
+    match key {
+        LuaValue::String(key) => Ok(key.to_str()?.to_string()),
+        other => wrap_err!("{} expected key to be a string, got: {:?}", function_name, other),
+    }
+}
+
+fn store_value(luau: &Lua, entry: Option<&Vec<u8>>) -> LuaValueResult {
+    match entry {
+        Some(data) => transit::decode(luau, data),
+        None => Ok(LuaNil),
+    }
+}
+
+/// puts `value` in `entries` under `key`, or removes `key` if `value` is nil
+fn store_set(entries: &mut HashMap<String, Vec<u8>>, key: String, value: LuaValue, function_name: &'static str) -> LuaEmptyResult {
+    if value.is_nil() {
+        entries.remove(&key);
+    } else {
+        entries.insert(key, transit::encode(&value, function_name)?);
+    }
+    Ok(())
+}
+
+impl LuaUserData for SharedStore {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_meta_field("__type", "SharedStore");
+        fields.add_field_method_get("name", |_luau: &Lua, this: &SharedStore| Ok(this.name.clone()));
+    }
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("get", |luau: &Lua, this: &SharedStore, key: LuaValue| -> LuaValueResult {
+            let function_name = "SharedStore:get(key: string)";
+            let key = store_key(key, function_name)?;
+            let entries = lock_outside_transaction(luau, &this.entries, &this.key(), function_name)?;
+            store_value(luau, entries.get(&key))
+        });
+        methods.add_method("set", |luau: &Lua, this: &SharedStore, (key, value): (LuaValue, LuaValue)| -> LuaEmptyResult {
+            let function_name = "SharedStore:set(key: string, value: Sendable)";
+            let key = store_key(key, function_name)?;
+            let mut entries = lock_outside_transaction(luau, &this.entries, &this.key(), function_name)?;
+            store_set(&mut entries, key, value, function_name)
+        });
+        methods.add_method("update", |luau: &Lua, this: &SharedStore, (key, f): (LuaValue, LuaValue)| -> LuaValueResult {
+            let function_name = "SharedStore:update(key: string, f: (current: Sendable) -> Sendable)";
+            let key = store_key(key, function_name)?;
+            let LuaValue::Function(f) = f else {
+                return wrap_err!("{} expected f to be a function, got: {:?}", function_name, f);
+            };
+            let mut entries = lock_outside_transaction(luau, &this.entries, &this.key(), function_name)?;
+            let current = store_value(luau, entries.get(&key))?;
+            let new = in_transaction(luau, &this.key(), || f.call::<LuaValue>(current))?;
+            store_set(&mut entries, key, new.clone(), function_name)?;
+            Ok(new)
+        });
+        methods.add_method("transaction", |luau: &Lua, this: &SharedStore, f: LuaValue| -> LuaValueResult {
+            let function_name = "SharedStore:transaction(f: (entries: { [string]: Sendable }) -> any)";
+            let LuaValue::Function(f) = f else {
+                return wrap_err!("{} expected f to be a function, got: {:?}", function_name, f);
+            };
+            let mut entries = lock_outside_transaction(luau, &this.entries, &this.key(), function_name)?;
+            let contents = luau.create_table_with_capacity(0, entries.len())?;
+            for (key, data) in entries.iter() {
+                contents.raw_set(key.as_str(), transit::decode(luau, data)?)?;
+            }
+            let result = in_transaction(luau, &this.key(), || f.call::<LuaValue>(contents.clone()))?;
+            // only written back if f didn't error, so a failed transaction leaves the store as it was
+            let mut updated = HashMap::with_capacity(entries.len());
+            for pair in contents.pairs::<LuaValue, LuaValue>() {
+                let (key, value) = pair?;
+                store_set(&mut updated, store_key(key, function_name)?, value, function_name)?;
+            }
+            *entries = updated;
+            Ok(result)
+        });
+    }
+}
+
+/// `thread.shared.store(name: string)`
+fn shared_store(luau: &Lua, value: LuaValue) -> LuaValueResult {
+    let function_name = "thread.shared.store(name: string)";
+    let name = name_arg(Some(value), function_name)?;
+    let entries = Arc::clone(lock(&STORES, function_name)?
+        .entry(name.clone())
+        .or_default());
+    ok_userdata(SharedStore { name, entries }, luau)
+}
+
+/// fixed-size bytes from `thread.shared.buffer`
+pub struct SharedBuffer {
+    name: String,
+    bytes: Arc<Mutex<Vec<u8>>>,
+}
+
+impl SharedBuffer {
+    fn key(&self) -> String {
+        format!("SharedBuffer '{}'", self.name)
+    }
+}
+
+fn buffer_range(offset: LuaValue, count: usize, len: usize, function_name: &'static str) -> LuaResult<std::ops::Range<usize>> {
+    let offset = int_to_usize(integer_arg(offset, "offset", function_name)?, function_name, "offset")?;
+    match offset.checked_add(count) {
+        Some(end) if end <= len => Ok(offset..end),
+        _ => wrap_err!("{}: range {}..{} is out of bounds of the shared buffer (length {})", function_name, offset, offset.saturating_add(count), len),
+    }
+}
+
+impl LuaUserData for SharedBuffer {
+    fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
+        fields.add_meta_field("__type", "SharedBuffer");
+        fields.add_field_method_get("name", |_luau: &Lua, this: &SharedBuffer| Ok(this.name.clone()));
+    }
+    fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("len", |luau: &Lua, this: &SharedBuffer, _: LuaValue| -> LuaResult<usize> {
+            let function_name = "SharedBuffer:len()";
+            Ok(lock_outside_transaction(luau, &this.bytes, &this.key(), function_name)?.len())
+        });
+        methods.add_method("read", |luau: &Lua, this: &SharedBuffer, (offset, count): (LuaValue, LuaValue)| -> LuaValueResult {
+            let function_name = "SharedBuffer:read(offset: number, count: number)";
+            let count = int_to_usize(integer_arg(count, "count", function_name)?, function_name, "count")?;
+            let bytes = lock_outside_transaction(luau, &this.bytes, &this.key(), function_name)?;
+            let range = buffer_range(offset, count, bytes.len(), function_name)?;
+            ok_buffy(&bytes[range], luau)
+        });
+        methods.add_method("write", |luau: &Lua, this: &SharedBuffer, (offset, data): (LuaValue, LuaValue)| -> LuaEmptyResult {
+            let function_name = "SharedBuffer:write(offset: number, data: buffer | string)";
+            let data = match data {
+                LuaValue::Buffer(buffy) => buffy.to_vec(),
+                LuaValue::String(s) => s.as_bytes().to_owned(),
+                other => {
+                    return wrap_err!("{} expected data to be a buffer or string, got: {:?}", function_name, other);
+                }
+            };
+            let mut bytes = lock_outside_transaction(luau, &this.bytes, &this.key(), function_name)?;
+            let range = buffer_range(offset, data.len(), bytes.len(), function_name)?;
+            bytes[range].copy_from_slice(&data);
+            Ok(())
+        });
+        methods.add_method("transaction", |luau: &Lua, this: &SharedBuffer, f: LuaValue| -> LuaValueResult {
+            let function_name = "SharedBuffer:transaction(f: (contents: buffer) -> any)";
+            let LuaValue::Function(f) = f else {
+                return wrap_err!("{} expected f to be a function, got: {:?}", function_name, f);
+            };
+            let mut bytes = lock_outside_transaction(luau, &this.bytes, &this.key(), function_name)?;
+            let contents = luau.create_buffer(&bytes[..])?;
+            let result = in_transaction(luau, &this.key(), || f.call::<LuaValue>(contents.clone()))?;
+            // only written back if f didn't error
+            bytes.copy_from_slice(&contents.to_vec());
+            Ok(result)
+        });
+    }
+}
+
+/// `thread.shared.buffer(name: string, size: number)`
+fn shared_buffer(luau: &Lua, mut multivalue: LuaMultiValue) -> LuaValueResult {
+    let function_name = "thread.shared.buffer(name: string, size: number)";
+    let name = name_arg(multivalue.pop_front(), function_name)?;
+    let size = match multivalue.pop_front() {
+        Some(size) => int_to_usize(integer_arg(size, "size", function_name)?, function_name, "size")?,
+        None => {
+            return wrap_err!("{} called without required argument 'size'", function_name);
+        }
+    };
+    let bytes = Arc::clone(lock(&BUFFERS, function_name)?
+        .entry(name.clone())
+        .or_insert_with(|| Arc::new(Mutex::new(vec![0; size]))));
+    let shared_buffer = SharedBuffer { name, bytes };
+    let existing_size = lock_outside_transaction(luau, &shared_buffer.bytes, &shared_buffer.key(), function_name)?.len();
+    if existing_size != size {
+        return wrap_err!("{}: shared buffer '{}' already exists with size {}, not {}", function_name, shared_buffer.name, existing_size, size);
+    }
+    ok_userdata(shared_buffer, luau)
+}
+
+pub fn create(luau: &Lua) -> LuaResult<LuaTable> {
+    TableBuilder::create(luau)?
+        .with_function("counter", shared_counter)?
+        .with_function("store", shared_store)?
+        .with_function("buffer", shared_buffer)?
+        .build_readonly()
+}
@@ -1,6 +1,6 @@
 use crate::prelude::*;
 use crate::require::get_chunk_name_for_module;
-use crate::{globals, std_json};
+use crate::globals;
 use mluau::prelude::*;
 use petname::Generator;
 /// helper struct for ThreadSpawnOptions tables so we don't crowd std_thread
@@ -18,7 +18,7 @@ pub struct ThreadSpawnOptions {
     pub capacity: ChannelCapacity,
     pub src: Option<String>,
     pub path: Option<PathBuf>,
-    pub data: Option<String>,
+    pub data: Option<Vec<u8>>,
 }
 
 impl ThreadSpawnOptions {
@@ -77,11 +77,7 @@ impl ThreadSpawnOptions {
         }
         let data = match t.raw_get("data")? {
             LuaNil => None,
-            LuaValue::Table(data_table) => Some(std_json::encode(luau, data_table, std_json::EncodeOptions { pretty: false, sorted: false })?),
-            LuaValue::String(s) => Some(s.to_string_lossy()),
-            other => {
-                return wrap_err!("{}: ThreadSpawnOptions.data expected to be a table, string, or nil, got: {:?}", function_name, other);
-            }
+            data => Some(super::serialize_data_for_transit(luau, data, function_name)?),
         };
         let capacity = match t.raw_get("capacity")? {
             LuaNil => ChannelCapacity::default(),
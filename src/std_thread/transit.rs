@@ -0,0 +1,238 @@
+//! Binary structured-clone format for sending Luau values between threads (VMs).
+//!
+//! Every value starts with a tag byte, so nothing has to be guessed when decoding: strings stay strings,
+//! integers stay integers, and buffers, vectors, non-string keys, and sparse arrays all survive the trip.
+//! Tables are numbered in the order they're first seen; seeing one again writes a reference to its number instead,
+//! which keeps shared (and cyclic) tables shared on the other side. Metatables aren't sent.
+//!
+//! ```text
+//! data    := VERSION value
+//! value   := NIL | FALSE | TRUE
+//!          | INTEGER i64 | NUMBER f64 | VECTOR f32 f32 f32
+//!          | STRING u32 bytes | BUFFER u32 bytes
+//!          | TABLE u32 value* u32 (value value)*   -- array part, then remaining key/value pairs
+//!          | REFERENCE u32                        -- a table that's already been sent
+//! ```
+//! (all numbers little endian)
+
+use std::collections::HashMap;
+use std::ffi::c_void;
+
+use crate::prelude::*;
+use mluau::prelude::*;
+
+const VERSION: u8 = 1;
+
+const NIL: u8 = 0;
+const FALSE: u8 = 1;
+const TRUE: u8 = 2;
+const INTEGER: u8 = 3;
+const NUMBER: u8 = 4;
+const VECTOR: u8 = 5;
+const STRING: u8 = 6;
+const BUFFER: u8 = 7;
+const TABLE: u8 = 8;
+const REFERENCE: u8 = 9;
+
+/// deeply nested tables would otherwise overflow the stack
+const MAX_DEPTH: usize = 256;
+
+struct Encoder {
+    bytes: Vec<u8>,
+    /// table pointer -> its number, in the order we first saw it
+    seen: HashMap<*const c_void, u32>,
+    function_name: &'static str,
+}
+
+impl Encoder {
+    fn write_len(&mut self, len: usize) -> LuaEmptyResult {
+        match u32::try_from(len) {
+            Ok(len) => {
+                self.bytes.extend_from_slice(&len.to_le_bytes());
+                Ok(())
+            },
+            Err(_) => wrap_err!("{}: can't send something with {} bytes/entries, that's too many", self.function_name, len),
+        }
+    }
+
+    fn write_bytes(&mut self, tag: u8, bytes: &[u8]) -> LuaEmptyResult {
+        self.bytes.push(tag);
+        self.write_len(bytes.len())?;
+        self.bytes.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn write(&mut self, value: &LuaValue, depth: usize) -> LuaEmptyResult {
+        match value {
+            LuaValue::Nil => self.bytes.push(NIL),
+            LuaValue::Boolean(false) => self.bytes.push(FALSE),
+            LuaValue::Boolean(true) => self.bytes.push(TRUE),
+            LuaValue::Integer(i) => {
+                self.bytes.push(INTEGER);
+                self.bytes.extend_from_slice(&i.to_le_bytes());
+            },
+            LuaValue::Number(n) => {
+                self.bytes.push(NUMBER);
+                self.bytes.extend_from_slice(&n.to_le_bytes());
+            },
+            LuaValue::Vector(v) => {
+                self.bytes.push(VECTOR);
+                for component in [v.x(), v.y(), v.z()] {
+                    self.bytes.extend_from_slice(&component.to_le_bytes());
+                }
+            },
+            LuaValue::String(s) => self.write_bytes(STRING, &s.as_bytes())?,
+            LuaValue::Buffer(buffy) => self.write_bytes(BUFFER, &buffy.to_vec())?,
+            LuaValue::Table(t) => self.write_table(t, depth)?,
+            other => {
+                return wrap_err!("{}: can't send {} values to another thread, only nil, booleans, numbers, vectors, strings, buffers, and tables of them", self.function_name, other.type_name());
+            }
+        }
+        Ok(())
+    }
+
+    fn write_table(&mut self, t: &LuaTable, depth: usize) -> LuaEmptyResult {
+        if let Some(number) = self.seen.get(&t.to_pointer()) {
+            self.bytes.push(REFERENCE);
+            self.bytes.extend_from_slice(&number.to_le_bytes());
+            return Ok(());
+        }
+        if depth >= MAX_DEPTH {
+            return wrap_err!("{}: can't send tables nested more than {} deep", self.function_name, MAX_DEPTH);
+        }
+        let number = self.seen.len() as u32;
+        self.seen.insert(t.to_pointer(), number);
+
+        self.bytes.push(TABLE);
+        let array_len = t.raw_len();
+        self.write_len(array_len)?;
+        for index in 1..=array_len {
+            let value: LuaValue = t.raw_get(index)?;
+            self.write(&value, depth + 1)?;
+        }
+
+        let mut pairs = Vec::new();
+        for pair in t.pairs::<LuaValue, LuaValue>() {
+            let (key, value) = pair?;
+            if let LuaValue::Integer(i) = key && i >= 1 && (i as usize) <= array_len {
+                continue;
+            }
+            pairs.push((key, value));
+        }
+        self.write_len(pairs.len())?;
+        for (key, value) in pairs {
+            self.write(&key, depth + 1)?;
+            self.write(&value, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn encode(value: &LuaValue, function_name: &'static str) -> LuaResult<Vec<u8>> {
+    let mut encoder = Encoder {
+        bytes: vec![VERSION],
+        seen: HashMap::new(),
+        function_name,
+    };
+    encoder.write(value, 0)?;
+    Ok(encoder.bytes)
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    tables: Vec<LuaTable>,
+}
+
+impl Decoder<'_> {
+    fn take(&mut self, len: usize) -> LuaResult<&[u8]> {
+        match self.bytes.get(self.position..self.position + len) {
+            Some(taken) => {
+                self.position += len;
+                Ok(taken)
+            },
+            None => wrap_err!("thread transit: message ended unexpectedly (at byte {}); this is a bug in seal", self.position),
+        }
+    }
+
+    fn take_array<const N: usize>(&mut self) -> LuaResult<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn read_len(&mut self) -> LuaResult<usize> {
+        Ok(u32::from_le_bytes(self.take_array()?) as usize)
+    }
+
+    fn read(&mut self, luau: &Lua) -> LuaValueResult {
+        let tag = self.take_array::<1>()?[0];
+        Ok(match tag {
+            NIL => LuaNil,
+            FALSE => LuaValue::Boolean(false),
+            TRUE => LuaValue::Boolean(true),
+            INTEGER => LuaValue::Integer(LuaInteger::from_le_bytes(self.take_array()?)),
+            NUMBER => LuaValue::Number(f64::from_le_bytes(self.take_array()?)),
+            VECTOR => {
+                let x = f32::from_le_bytes(self.take_array()?);
+                let y = f32::from_le_bytes(self.take_array()?);
+                let z = f32::from_le_bytes(self.take_array()?);
+                LuaValue::Vector(LuaVector::new(x, y, z))
+            },
+            STRING => {
+                let len = self.read_len()?;
+                LuaValue::String(luau.create_string(self.take(len)?)?)
+            },
+            BUFFER => {
+                let len = self.read_len()?;
+                LuaValue::Buffer(luau.create_buffer(self.take(len)?)?)
+            },
+            TABLE => LuaValue::Table(self.read_table(luau)?),
+            REFERENCE => {
+                let number = self.read_len()?;
+                match self.tables.get(number) {
+                    Some(t) => LuaValue::Table(t.clone()),
+                    None => {
+                        return wrap_err!("thread transit: reference to table {} that hasn't been sent; this is a bug in seal", number);
+                    }
+                }
+            },
+            other => {
+                return wrap_err!("thread transit: unknown tag {} at byte {}; this is a bug in seal", other, self.position - 1);
+            }
+        })
+    }
+
+    fn read_table(&mut self, luau: &Lua) -> LuaResult<LuaTable> {
+        let array_len = self.read_len()?;
+        // lengths come from our own encoder, but don't preallocate more than the message could possibly hold
+        let t = luau.create_table_with_capacity(array_len.min(self.bytes.len()), 0)?;
+        // numbered before its contents are read so they can refer back to it
+        self.tables.push(t.clone());
+        for index in 1..=array_len {
+            t.raw_set(index, self.read(luau)?)?;
+        }
+        let pairs_len = self.read_len()?;
+        for _ in 0..pairs_len {
+            let key = self.read(luau)?;
+            let value = self.read(luau)?;
+            t.raw_set(key, value)?;
+        }
+        Ok(t)
+    }
+}
+
+pub fn decode(luau: &Lua, bytes: &[u8]) -> LuaValueResult {
+    match bytes.first() {
+        Some(&VERSION) => {},
+        other => {
+            return wrap_err!("thread transit: expected message to start with format version {}, got: {:?}; this is a bug in seal", VERSION, other);
+        }
+    }
+    let mut decoder = Decoder {
+        bytes,
+        position: 1,
+        tables: Vec::new(),
+    };
+    decoder.read(luau)
+}
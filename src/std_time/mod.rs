@@ -1,4 +1,5 @@
 use crate::prelude::*;
+use crate::std_task;
 use mluau::prelude::*;
 use std::time::Duration;
 
@@ -24,9 +25,15 @@ fn time_wait(_luau: &Lua, seconds: LuaNumber) -> LuaValueResult {
     Ok(LuaValue::Boolean(true)) // return true so while time.wait(1) loops still work
 }
 
+/// `time.wait` for @std/task tasks, which yields to other tasks instead of blocking them
+fn time_wait_start(luau: &Lua, seconds: LuaNumber) -> LuaValueResult {
+    let duration = Duration::from_millis((seconds * 1000.0) as u64);
+    std_task::wake_after(luau, duration, LuaMultiValue::from_vec(vec![LuaValue::Boolean(true)]), "time.wait(seconds: number)")
+}
+
 pub fn create(luau: &Lua) -> LuaResult<LuaTable> {
     TableBuilder::create(luau)?
-        .with_function("wait", time_wait)?
+        .with_value("wait", std_task::yielding(luau, luau.create_function(time_wait)?, luau.create_function(time_wait_start)?)?)?
         .with_value("datetime", datetime::create(luau)?)?
 
         .with_function("years", |luau: &Lua, value: LuaValue| -> LuaValueResult {